@@ -0,0 +1,306 @@
+//! The derive macro of opzioni. Use it through the `derive` feature of opzioni instead of depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Expr, Field, Fields, Ident, LitStr, Token, Type};
+
+/// Derives `opzioni::Describe` and `opzioni::Opzioni` for a config struct, see the documentation of opzioni.
+#[proc_macro_derive(Opzioni, attributes(opzioni))]
+pub fn derive_opzioni(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// The options set on a field with `#[opzioni(...)]`, together with what serde and the doc comments say about it.
+struct FieldOptions<'a> {
+    ident: &'a Ident,
+    ty: &'a Type,
+    key: String,
+    /// The other names serde accepts for the field when deserializing, from `#[serde(alias)]` and `#[serde(rename(deserialize))]`.
+    aliases: Vec<String>,
+    doc: Option<String>,
+    default: Option<LitStr>,
+    env: Option<LitStr>,
+    min: Option<Expr>,
+    max: Option<Expr>,
+    secret: bool,
+    nested: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(&input.ident, "Opzioni can only be derived for structs with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(&input.ident, "Opzioni can only be derived for structs")),
+    };
+    let rename_all = rename_all(&input.attrs)?;
+    let fields = fields.iter().map(|field| parse_field(field, rename_all.as_deref())).collect::<syn::Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let descriptions = fields.iter().filter_map(|field| {
+        let (key, aliases) = (&field.key, &field.aliases);
+        field.doc.as_ref().map(|doc| quote! { (#key #(| #aliases)*, None) => Some(#doc), })
+    });
+    let nested_descriptions = fields.iter().filter(|field| field.nested).map(|field| {
+        let (key, aliases, ty) = (&field.key, &field.aliases, field.ty);
+        quote! { (#key #(| #aliases)*, Some(rest)) => <#ty as ::opzioni::Describe>::describe(rest), }
+    });
+
+    let env = fields.iter().map(|field| {
+        let (ident, ty, key) = (field.ident, field.ty, &field.key);
+        let own = field.env.as_ref().map(|var| quote! {
+            if let Ok(value) = ::std::env::var(#var) {
+                self.#ident = <#ty as ::std::str::FromStr>::from_str(&value)
+                    .map_err(|_| format!("the environment variable {} is not a valid value for {}", #var, #key))?;
+            }
+        });
+        let nested = field.nested.then(|| quote! { <#ty as ::opzioni::Opzioni>::apply_env(&mut self.#ident)?; });
+        quote! { #own #nested }
+    });
+
+    let checks = fields.iter().map(|field| {
+        let (ident, ty, key) = (field.ident, field.ty, &field.key);
+        let min = field.min.as_ref().map(|min| quote! {
+            if self.#ident < (#min as #ty) {
+                return Err(format!("{} must be at least {}", #key, #min));
+            }
+        });
+        let max = field.max.as_ref().map(|max| quote! {
+            if self.#ident > (#max as #ty) {
+                return Err(format!("{} must be at most {}", #key, #max));
+            }
+        });
+        let nested = field.nested.then(|| quote! {
+            <#ty as ::opzioni::Opzioni>::check(&self.#ident).map_err(|err| format!("{}.{}", #key, err))?;
+        });
+        quote! { #min #max #nested }
+    });
+
+    let secrets = fields.iter().map(|field| {
+        let (ty, key) = (field.ty, &field.key);
+        let own = field.secret.then(|| quote! { secrets.push(#key.to_string()); });
+        let nested = field.nested.then(|| quote! {
+            secrets.extend(<#ty as ::opzioni::Opzioni>::secrets().into_iter().map(|path| format!("{}.{}", #key, path)));
+        });
+        quote! { #own #nested }
+    });
+
+    let default = fields.iter().any(|field| field.default.is_some()).then(|| {
+        let values = fields.iter().map(|field| {
+            let (ident, ty, key) = (field.ident, field.ty, &field.key);
+            match &field.default {
+                Some(default) => quote! {
+                    #ident: <#ty as ::std::str::FromStr>::from_str(#default)
+                        .unwrap_or_else(|_| panic!("the default {:?} is not a valid value for {}", #default, #key)),
+                },
+                None => quote! { #ident: ::std::default::Default::default(), },
+            }
+        });
+        quote! {
+            impl #impl_generics ::std::default::Default for #name #ty_generics #where_clause {
+                fn default() -> Self {
+                    Self { #(#values)* }
+                }
+            }
+        }
+    });
+
+    let debug = fields.iter().any(|field| field.secret).then(|| {
+        let name_str = name.to_string();
+        let values = fields.iter().map(|field| {
+            let ident = field.ident;
+            let ident_str = ident.to_string();
+            if field.secret {
+                quote! { .field(#ident_str, &format_args!("[redacted]")) }
+            } else {
+                quote! { .field(#ident_str, &self.#ident) }
+            }
+        });
+        quote! {
+            impl #impl_generics ::std::fmt::Debug for #name #ty_generics #where_clause {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    f.debug_struct(#name_str) #(#values)* .finish()
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::opzioni::Describe for #name #ty_generics #where_clause {
+            fn describe(path: &str) -> Option<&'static str> {
+                let (head, rest) = match path.split_once('.') {
+                    Some((head, rest)) => (head, Some(rest)),
+                    None => (path, None),
+                };
+                match (head, rest) {
+                    #(#descriptions)*
+                    #(#nested_descriptions)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl #impl_generics ::opzioni::Opzioni for #name #ty_generics #where_clause {
+            fn apply_env(&mut self) -> Result<(), String> {
+                #(#env)*
+                Ok(())
+            }
+
+            fn check(&self) -> Result<(), String> {
+                #(#checks)*
+                Ok(())
+            }
+
+            fn secrets() -> Vec<String> {
+                #[allow(unused_mut)]
+                let mut secrets = Vec::new();
+                #(#secrets)*
+                secrets
+            }
+        }
+
+        #default
+        #debug
+    })
+}
+
+fn parse_field<'a>(field: &'a Field, rename_all: Option<&str>) -> syn::Result<FieldOptions<'a>> {
+    let ident = field.ident.as_ref().expect("named fields have an ident");
+    let mut options = FieldOptions {
+        ident,
+        ty: &field.ty,
+        key: rename(&ident.to_string(), rename_all),
+        aliases: Vec::new(),
+        doc: doc(&field.attrs),
+        default: None,
+        env: None,
+        min: None,
+        max: None,
+        secret: false,
+        nested: false,
+    };
+    for attr in &field.attrs {
+        if attr.path().is_ident("opzioni") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("default") {
+                    options.default = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("env") {
+                    options.env = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("min") {
+                    options.min = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("max") {
+                    options.max = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("secret") {
+                    options.secret = true;
+                } else if meta.path.is_ident("nested") {
+                    options.nested = true;
+                } else {
+                    return Err(meta.error("unknown opzioni attribute, expected one of default, env, min, max, secret and nested"));
+                }
+                Ok(())
+            })?;
+        } else if attr.path().is_ident("serde") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") && meta.input.peek(Token![=]) {
+                    options.key = meta.value()?.parse::<LitStr>()?.value();
+                    return Ok(());
+                }
+                if meta.path.is_ident("rename") && meta.input.peek(syn::token::Paren) {
+                    return meta.parse_nested_meta(|rename| {
+                        let name = rename.value()?.parse::<LitStr>()?.value();
+                        match rename.path.is_ident("serialize") {
+                            true => options.key = name,
+                            false => options.aliases.push(name),
+                        }
+                        Ok(())
+                    });
+                }
+                if meta.path.is_ident("alias") {
+                    options.aliases.push(meta.value()?.parse::<LitStr>()?.value());
+                    return Ok(());
+                }
+                skip(&meta)
+            })?;
+        }
+    }
+    // The key is matched first, so a deserialize name equal to the key is no alias.
+    options.aliases.retain(|alias| *alias != options.key);
+    Ok(options)
+}
+
+/// Reads `#[serde(rename_all = "...")]` from the attributes of the struct. With different rules for serializing and deserializing, the one for serializing is used, because it names the keys in saved files.
+fn rename_all(attrs: &[Attribute]) -> syn::Result<Option<String>> {
+    let mut rename_all = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") && meta.input.peek(Token![=]) {
+                rename_all = Some(meta.value()?.parse::<LitStr>()?.value());
+                return Ok(());
+            }
+            if meta.path.is_ident("rename_all") && meta.input.peek(syn::token::Paren) {
+                return meta.parse_nested_meta(|rule| {
+                    let value = rule.value()?.parse::<LitStr>()?.value();
+                    if rule.path.is_ident("serialize") {
+                        rename_all = Some(value);
+                    }
+                    Ok(())
+                });
+            }
+            skip(&meta)
+        })?;
+    }
+    Ok(rename_all)
+}
+
+/// Consumes a serde option which is not relevant here, like `default`, `with = "..."` or `rename(serialize = "...")`.
+fn skip(meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|nested| skip(&nested))?;
+    }
+    Ok(())
+}
+
+/// Applies a serde `rename_all` rule to a snake case field name.
+fn rename(field: &str, rule: Option<&str>) -> String {
+    let words = field.split('_').filter(|word| !word.is_empty());
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+    };
+    match rule {
+        Some("lowercase") => field.to_lowercase(),
+        Some("UPPERCASE") => field.to_uppercase(),
+        Some("PascalCase") => words.map(capitalize).collect(),
+        Some("camelCase") => words
+            .enumerate()
+            .map(|(i, word)| if i == 0 { word.to_string() } else { capitalize(word) })
+            .collect(),
+        Some("SCREAMING_SNAKE_CASE") => field.to_uppercase(),
+        Some("kebab-case") => field.replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => field.replace('_', "-").to_uppercase(),
+        _ => field.to_string(),
+    }
+}
+
+/// Joins the doc comment lines of a field into a single description.
+fn doc(attrs: &[Attribute]) -> Option<String> {
+    let lines = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue { value: Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(doc), .. }), .. }) => {
+                Some(doc.value().trim().to_string())
+            }
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    (!lines.is_empty()).then(|| lines.join(" "))
+}
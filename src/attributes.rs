@@ -0,0 +1,24 @@
+/// Behavior of a config type which is declared with attributes on its fields, usually implemented with `#[derive(Opzioni)]` from the `derive` feature.
+/// Enable it for a config with [`crate::ConfigBuilder::use_attributes`].
+///
+/// The derive macro understands these field attributes:
+///
+/// - `#[opzioni(default = "8080")]`: the default value of the field, parsed with [`std::str::FromStr`]. If any field has a default, `Default` is implemented for the struct, with `Default::default()` for the other fields.
+/// - `#[opzioni(env = "PORT")]`: overrides the field with the value of the environment variable, parsed with [`std::str::FromStr`].
+/// - `#[opzioni(min = 1, max = 100)]`: the inclusive range of a numeric field, checked after loading and before saving.
+/// - `#[opzioni(secret)]`: redacts the field in the `Debug` output and in the configs logged by the `tracing` feature. If any field is a secret, `Debug` is implemented for the struct.
+/// - `#[opzioni(nested)]`: the field is a struct which derives `Opzioni` as well. Its descriptions, environment variables and ranges are included.
+///
+/// Doc comments of the fields become their descriptions in [`crate::Describe`], with the field names as serialized after `#[serde(rename)]` and `#[serde(rename_all)]`. Names from `#[serde(alias)]` are described as well.
+pub trait Opzioni: crate::Describe {
+    /// Overrides fields with the values of their environment variables. Returns an error message if a variable cannot be parsed.
+    fn apply_env(&mut self) -> Result<(), String>;
+
+    /// Checks the ranges of the fields. Returns an error message for the first field which is out of range.
+    fn check(&self) -> Result<(), String>;
+
+    /// Returns the dotted paths of the fields marked as secret, which are masked in the configs logged by the `tracing` feature.
+    fn secrets() -> Vec<String> {
+        Vec::new()
+    }
+}
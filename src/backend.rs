@@ -0,0 +1,158 @@
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{Error, Format, manager};
+use crate::config::{fingerprint, Settings};
+use crate::manager::ConfigManager;
+use crate::secret::wipe;
+
+/// A store other than a local file which holds the serialized config, like a key of a distributed key-value store.
+/// A config is loaded from a backend with [`crate::ConfigBuilder::load_from_backend`]. Saving and reloading it then go through the backend instead of a file.
+/// The methods block until the store answered. Implement the trait to keep configs in stores opzioni does not support itself.
+///
+/// # Example
+/// ```
+/// use std::sync::Mutex;
+/// use opzioni::{Backend, Config, Error, Format};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Debug, Default)]
+/// struct Memory(Mutex<Option<String>>);
+///
+/// impl Backend for Memory {
+///     fn read(&self) -> Result<Option<String>, Error> {
+///         Ok(self.0.lock().unwrap().clone())
+///     }
+///
+///     fn write(&self, data: &str) -> Result<(), Error> {
+///         *self.0.lock().unwrap() = Some(data.to_string());
+///         Ok(())
+///     }
+/// }
+///
+/// #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// let backend = Memory(Mutex::new(Some(r#"{ "name": "John", "age": 42 }"#.to_string())));
+/// let config = Config::<MyConfig>::configure().load_from_backend(backend, Format::Json).unwrap();
+/// assert!(format!("{:?}", config).contains("age: 42"));
+/// assert!(config.path().is_none());
+/// ```
+pub trait Backend: Debug + Send + Sync {
+    /// Reads the serialized config. Returns `None` if the store does not hold a config yet.
+    fn read(&self) -> Result<Option<String>, Error>;
+
+    /// Replaces the stored config with the given serialized config.
+    fn write(&self, data: &str) -> Result<(), Error>;
+
+    /// Blocks until the stored config may have changed since it was last read or written through this backend. Returning early is harmless, because reloading ignores unchanged configs.
+    /// The default implementation fails, because not every store can be watched.
+    fn wait_for_change(&self) -> Result<(), Error> {
+        Err(Error::load("the backend cannot be watched"))
+    }
+}
+
+/// Allows keeping a handle to a backend after loading a config from it, for example to lock it.
+impl<B> Backend for Arc<B>
+    where B: Backend + ?Sized {
+    fn read(&self) -> Result<Option<String>, Error> {
+        (**self).read()
+    }
+
+    fn write(&self, data: &str) -> Result<(), Error> {
+        (**self).write(data)
+    }
+
+    fn wait_for_change(&self) -> Result<(), Error> {
+        (**self).wait_for_change()
+    }
+}
+
+/// Returns the error for a backend which holds no config.
+pub(crate) fn missing() -> Error {
+    Error::load("the backend holds no config")
+}
+
+/// A backend together with the format of the config it stores, kept by a config loaded from the backend.
+#[derive(Debug, Clone)]
+pub(crate) struct Remote {
+    pub(crate) backend: Arc<dyn Backend>,
+    pub(crate) format: Format,
+}
+
+impl Remote {
+    pub(crate) fn new(backend: impl Backend + 'static, format: Format) -> Self {
+        Self { backend: Arc::new(backend), format }
+    }
+
+    /// Returns a manager reading and writing the config through the backend, in the given format or the one of the backend.
+    pub(crate) fn loader<T>(&self, format: Option<Format>) -> Box<dyn ConfigManager<T> + '_>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + Default,
+    {
+        Box::new(BackendLoader { remote: self, format: format.unwrap_or(self.format) })
+    }
+
+    /// Reads the serialized config from the backend. Fails if the backend holds no config or an empty one.
+    pub(crate) fn read(&self) -> Result<Vec<u8>, Error> {
+        let data = self.backend.read()?.ok_or_else(missing)?;
+        if data.is_empty() {
+            return Err(Error::EmptyFile { path: None, format: None });
+        }
+        Ok(data.into_bytes())
+    }
+
+    /// Parses a serialized config read from the backend. Returns the config and its fingerprint like [`Settings::read`], together with the fingerprint of the stored data if conflicts are detected.
+    pub(crate) fn parse<T>(&self, settings: &Settings<T>, data: Vec<u8>) -> Result<(T, Option<u64>, Option<u64>), Error>
+        where
+            T: serde::Serialize + serde::de::DeserializeOwned + Default,
+    {
+        let stored = settings.detect_conflicts.then(|| fingerprint(&data));
+        let (config, persisted) = settings.read(settings.open(manager::for_data(self.format, data)).as_ref())?;
+        Ok((config, persisted, stored))
+    }
+
+    /// Hashes the config currently held by the backend. Returns `None` if it holds none or cannot be read.
+    pub(crate) fn fingerprint(&self) -> Option<u64> {
+        let data = self.backend.read().ok()??;
+        let hash = fingerprint(&data);
+        wipe(data);
+        Some(hash)
+    }
+}
+
+/// Manages a config stored in a [`Backend`].
+struct BackendLoader<'a> {
+    remote: &'a Remote,
+    format: Format,
+}
+
+impl<T> ConfigManager<T> for BackendLoader<'_>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    fn format(&self) -> Format {
+        self.format
+    }
+
+    fn path(&self) -> &Path {
+        Path::new("")
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Error> {
+        #[cfg(feature = "tracing")]
+        trace!(backend = ?self.remote.backend, "loading config");
+        self.remote.read()
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), Error> {
+        #[cfg(feature = "tracing")]
+        trace!(backend = ?self.remote.backend, "saving config");
+        let data = std::str::from_utf8(data).map_err(|_| Error::serialize("backends only store text, use a text format"))?;
+        self.remote.backend.write(data)
+    }
+}
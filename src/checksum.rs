@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::{Error, Format};
+use crate::manager::ConfigManager;
+
+/// Returns the path of the file holding the checksum of the given config file, like `config.toml.sha256`.
+pub(crate) fn sidecar(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".sha256");
+    sidecar.into()
+}
+
+/// Returns the SHA-256 digest of the data in lowercase hex.
+fn digest(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Verifies the config read by another manager against the checksum next to its file and updates the checksum after every write, see [`crate::ConfigBuilder::with_checksum`].
+pub(crate) struct Checksummed<'a, T> {
+    pub(crate) inner: Box<dyn ConfigManager<T> + 'a>,
+}
+
+impl<T> ConfigManager<T> for Checksummed<'_, T>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    fn format(&self) -> Format {
+        self.inner.format()
+    }
+
+    fn path(&self) -> &Path {
+        self.inner.path()
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Error> {
+        let data = self.inner.read()?;
+        let sidecar = sidecar(self.inner.path());
+        // Files saved before checksums were enabled have none yet, they get one with the next save.
+        if !sidecar.exists() {
+            return Ok(data);
+        }
+        let stored = std::fs::read_to_string(&sidecar).map_err(|err| Error::from(err).at(&sidecar, None))?;
+        let expected = stored.split_whitespace().next().unwrap_or_default().to_ascii_lowercase();
+        let actual = digest(&data);
+        if expected != actual {
+            crate::secret::wipe(data);
+            return Err(Error::IntegrityError { path: None, format: None, expected, actual });
+        }
+        Ok(data)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), Error> {
+        self.inner.write(data)?;
+        // The file is read back, because the manager may have written something else, like the data merged into an existing file.
+        let written = self.inner.read()?;
+        let checksum = digest(&written);
+        crate::secret::wipe(written);
+        let path = self.inner.path();
+        let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let sidecar = sidecar(path);
+        std::fs::write(&sidecar, format!("{}  {}\n", checksum, name)).map_err(|err| Error::from(err).at(&sidecar, None))
+    }
+}
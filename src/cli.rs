@@ -0,0 +1,29 @@
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use crate::value::{self, Override};
+use crate::Error;
+
+/// Returns the arguments which were given on the command line, with their ids and their values as strings, see [`crate::ConfigBuilder::with_cli_overrides`].
+/// Arguments which only have their default value are left out, so they do not override the config file.
+pub(crate) fn given(matches: &ArgMatches) -> Vec<(String, Vec<String>)> {
+    matches.ids()
+        .map(|id| id.as_str())
+        .filter(|id| matches!(matches.value_source(id), Some(source) if source != ValueSource::DefaultValue))
+        .filter_map(|id| {
+            let values = matches.try_get_raw(id).ok()??;
+            Some((id.to_string(), values.map(|value| value.to_string_lossy().into_owned()).collect()))
+        })
+        .collect()
+}
+
+/// Sets the fields of the config at the ids of the arguments to their values.
+/// The values are parsed into the type of the current value of the field. Arguments which are not fields of the config, like the path of the config file, are skipped.
+pub(crate) fn apply<T>(config: &mut T, arguments: &[(String, Vec<String>)]) -> Result<(), Error>
+    where T: serde::Serialize + serde::de::DeserializeOwned {
+    value::override_fields(config, "the command line", |root| {
+        Ok(arguments.iter()
+            .filter(|(id, _)| value::get_path(root, id).is_ok())
+            .map(|(id, values)| Override { path: id.clone(), values: values.clone(), origin: format!("the argument `{}`", id) })
+            .collect())
+    })
+}
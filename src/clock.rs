@@ -0,0 +1,76 @@
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// A source of the current time.
+/// Every timestamp opzioni records (for example [`crate::Config::loaded_at`]) is taken from the clock passed to the `clock` method of the builder returned by [`crate::Config::configure`].
+/// The default is [`SystemClock`]. Tests can use [`ManualClock`] to get deterministic timestamps.
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// The default [`Clock`] which reads the time from [`SystemTime::now`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A [`Clock`] which only moves when it is told to.
+///
+/// # Example
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use opzioni::{Clock, ManualClock};
+///
+/// let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+/// clock.advance(Duration::from_secs(60));
+/// assert_eq!(clock.now(), SystemTime::UNIX_EPOCH + Duration::from_secs(60));
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<SystemTime>,
+}
+
+impl ManualClock {
+    /// Creates a new ManualClock starting at the given time.
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            now: Mutex::new(start),
+        }
+    }
+
+    /// Moves the clock forward by the given duration.
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|err| err.into_inner());
+        *now += by;
+    }
+
+    /// Sets the clock to the given time.
+    pub fn set(&self, time: SystemTime) {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner()) = time;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<C> Clock for std::sync::Arc<C>
+    where C: Clock + ?Sized {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+}
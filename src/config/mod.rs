@@ -1,5 +1,903 @@
-#[cfg(feature = "tokio")]
+use ::std::any::{Any, TypeId};
+use ::std::collections::HashMap;
+use ::std::path::{Path, PathBuf};
+use ::std::sync::{Arc, Mutex, OnceLock, PoisonError, Weak};
+use ::std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+use ::std::time::Duration;
+use ::std::time::SystemTime;
+
+use crate::{Clock, Error, Format, SaveOptions, SystemClock, manager};
+use crate::backend::Remote;
+use crate::manager::ConfigManager;
+use crate::secret::wipe;
+#[cfg(feature = "json")]
+use crate::{Change, EnvOptions, MergeStrategy, Migrations, UnsetVariable, value};
+#[cfg(feature = "keyring")]
+use crate::credentials::Keyring;
+#[cfg(feature = "vault")]
+use crate::Vault;
+#[cfg(feature = "encryption")]
+use crate::encryption::{Encrypted, Encryption};
+#[cfg(feature = "checksum")]
+use crate::checksum::Checksummed;
+#[cfg(feature = "json")]
+use crate::split::Split;
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
 pub mod sync;
 
-#[cfg(not(feature = "tokio"))]
-pub mod std;
\ No newline at end of file
+pub mod std;
+
+/// The state of a config, shared by all clones of a [`crate::Config`]. `L` is the lock of the blocking or the async config.
+pub(crate) struct Shared<T, L> {
+    pub(crate) config: L,
+    pub(crate) location: Mutex<Location>,
+    pub(crate) settings: Settings<T>,
+    pub(crate) loaded_at: Mutex<Option<SystemTime>>,
+    pub(crate) saved_at: Mutex<Option<SystemTime>>,
+    pub(crate) saving: AtomicBool,
+    pub(crate) persisted: Mutex<Option<u64>>,
+    pub(crate) disk: Mutex<Option<u64>>,
+    #[cfg(feature = "file-lock")]
+    pub(crate) file_locked: AtomicBool,
+    /// The config as it was last loaded, reloaded or saved, kept for [`PoisonPolicy::Restore`].
+    pub(crate) good: Mutex<Option<T>>,
+    /// Whether a poisoned lock was already handled by the [`PoisonPolicy`].
+    pub(crate) recovered: AtomicBool,
+}
+
+impl<T, L> Shared<T, L> {
+    pub(crate) fn new(config: T, path: Option<PathBuf>, settings: Settings<T>, loaded_at: Option<SystemTime>, persisted: Option<u64>) -> Self
+        where T: Clone, L: From<T> {
+        Self {
+            good: Mutex::new(settings.known_good(&config)),
+            recovered: AtomicBool::new(false),
+            config: L::from(config),
+            location: Mutex::new(Location { path, remote: None }),
+            settings,
+            loaded_at: Mutex::new(loaded_at),
+            saved_at: Mutex::new(None),
+            saving: AtomicBool::new(false),
+            persisted: Mutex::new(persisted),
+            disk: Mutex::new(None),
+            #[cfg(feature = "file-lock")]
+            file_locked: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns where the config is saved to and reloaded from. It is copied, so saving and reloading do not block [`crate::Config::set_path`].
+    pub(crate) fn location(&self) -> Location {
+        self.location.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+}
+
+/// What a config does when its lock was poisoned by a thread which panicked while holding the write lock, set with [`crate::blocking::ConfigBuilder::on_poison`].
+/// Only the blocking config can be poisoned, the locks of the async config are not.
+/// A panic can leave the config half changed, so the policy decides whether it is used as it is, replaced with the last good version or not used at all.
+/// Only the first poisoning of a config is handled, afterwards the lock is used as if it was not poisoned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PoisonPolicy {
+    /// The config is used as the panicking thread left it.
+    #[default]
+    Ignore,
+    /// The config is replaced with the version which was last loaded, reloaded or saved. A copy of it is kept for this purpose.
+    Restore,
+    /// Methods which return a `Result` fail with [`Error::Poisoned`] until the config is reloaded, so a half changed config is never saved.
+    /// [`crate::blocking::Config::read`], [`crate::blocking::Config::write`] and the other methods which cannot fail use the config as it is.
+    Error,
+}
+
+/// What to do when loading a config fails, decided by the callback set with [`crate::ConfigBuilder::on_error`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recovery<T> {
+    /// The default values of the config type are used, like with [`crate::ConfigBuilder::use_default_on_error`].
+    UseDefault,
+    /// Loading fails with the error.
+    Fail,
+    /// The given config is used, for example one restored from a backup or entered by the user.
+    UseValue(T),
+}
+
+/// A config file which was skipped by [`crate::ConfigBuilder::load_first_of`], because it could not be loaded.
+#[derive(Debug)]
+pub struct Skipped {
+    /// The path of the config file.
+    pub path: PathBuf,
+    /// The error which occurred while loading the config file.
+    pub reason: Error,
+}
+
+/// Where a config is saved to and reloaded from. A config has a file, a [`crate::Backend`] or neither.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Location {
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) remote: Option<Remote>,
+}
+
+/// Marks a config as being saved for as long as it is alive.
+/// Acquiring the guard while another one is alive fails with [`Error::ReentrantSave`] instead of recursing or deadlocking.
+pub(crate) struct SaveGuard<'a> {
+    saving: &'a AtomicBool,
+}
+
+impl<'a> SaveGuard<'a> {
+    pub(crate) fn acquire(saving: &'a AtomicBool) -> Result<Self, Error> {
+        match saving.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => Ok(Self { saving }),
+            Err(_) => Err(Error::ReentrantSave),
+        }
+    }
+}
+
+impl Drop for SaveGuard<'_> {
+    fn drop(&mut self) {
+        self.saving.store(false, Ordering::Release);
+    }
+}
+
+/// Hashes the serialized form of a config. Two configs with the same fingerprint serialize to the same file content.
+pub(crate) fn fingerprint(data: impl AsRef<[u8]>) -> u64 {
+    use ::std::hash::{Hash, Hasher};
+
+    let mut hasher = ::std::collections::hash_map::DefaultHasher::new();
+    data.as_ref().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns whether the file at the given path exists and is empty.
+pub(crate) fn is_empty_file(path: &Path) -> bool {
+    ::std::fs::metadata(path).map(|metadata| metadata.is_file() && metadata.len() == 0).unwrap_or(false)
+}
+
+/// Returns the directories in which [`crate::ConfigBuilder::discover`] looks for the config file of the given application, in the order they are searched.
+/// These are the current directory, the user config directory of the platform and the system config directories listed in `XDG_CONFIG_DIRS`.
+pub(crate) fn config_dirs(app: &str) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    dirs.extend(::std::env::current_dir().ok());
+    dirs.extend(user_config_dirs(app));
+    #[cfg(unix)]
+    {
+        let system = ::std::env::var("XDG_CONFIG_DIRS").ok().filter(|dirs| !dirs.is_empty()).unwrap_or_else(|| "/etc/xdg".to_string());
+        dirs.extend(system.split(':').filter(|dir| !dir.is_empty()).map(|dir| Path::new(dir).join(app)));
+    }
+    dirs
+}
+
+/// Returns the config directories of the user for the given application, the preferred one first.
+pub(crate) fn user_config_dirs(app: &str) -> Vec<PathBuf> {
+    let var = |name: &str| ::std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let mut dirs = Vec::new();
+    #[cfg(windows)]
+    dirs.extend(var("APPDATA").map(|dir| dir.join(app)));
+    #[cfg(target_os = "macos")]
+    dirs.extend(var("HOME").map(|home| home.join("Library").join("Application Support").join(app)));
+    #[cfg(unix)]
+    dirs.extend(var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|home| home.join(".config"))).map(|dir| dir.join(app)));
+    dirs
+}
+
+/// Returns the path of the `index`th backup of the given config file, like `config.toml.bak.1`.
+pub(crate) fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".bak.{}", index));
+    backup.into()
+}
+
+/// Copies the config file to its first backup, after moving every existing backup one slot up. The oldest backup beyond `count` is dropped.
+/// Does nothing if `count` is zero or the file does not exist yet.
+pub(crate) fn rotate_backups(path: &Path, count: usize) -> Result<(), Error> {
+    if count == 0 || !path.is_file() {
+        return Ok(());
+    }
+    for index in (1..count).rev() {
+        let backup = backup_path(path, index);
+        if backup.exists() {
+            ::std::fs::rename(&backup, backup_path(path, index + 1))?;
+        }
+    }
+    ::std::fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// The config loaded from one file into one type, if it is still alive. Each file has its own lock, so loading one file does not wait for the others.
+type Slot = Arc<Mutex<Option<Weak<dyn Any + Send + Sync>>>>;
+
+/// The configs loaded with `ConfigBuilder::load_shared`, keyed by canonical path and config type.
+/// Only weak references are kept, so a config is dropped as usual once the last handle is gone.
+static SHARED: OnceLock<Mutex<HashMap<(PathBuf, TypeId), Slot>>> = OnceLock::new();
+
+/// Returns the config of type `C` already loaded from the given path, or loads it with `load` and remembers it if `load` reports it as shareable.
+/// Only the slot of the file stays locked while loading, so concurrent calls for the same file load it only once and loads of other files, also from within `load`, go ahead.
+pub(crate) fn shared<C>(path: &Path, load: impl FnOnce() -> Result<(C, bool), Error>) -> Result<Arc<C>, Error>
+    where C: Any + Send + Sync {
+    let key = (canonical(path), TypeId::of::<C>());
+    let slot = {
+        let mut shared = SHARED.get_or_init(Default::default).lock().unwrap_or_else(PoisonError::into_inner);
+        // Slots which are in use are kept, even if their config is gone, because another call may be loading it.
+        shared.retain(|_, slot| Arc::strong_count(slot) > 1 || slot.try_lock().is_ok_and(|config| config.as_ref().is_some_and(|config| config.strong_count() > 0)));
+        shared.entry(key).or_default().clone()
+    };
+    let mut slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(config) = slot.as_ref().and_then(Weak::upgrade).and_then(|config| config.downcast::<C>().ok()) {
+        return Ok(config);
+    }
+    let (config, shareable) = load()?;
+    let config = Arc::new(config);
+    if shareable {
+        let weak: Weak<dyn Any + Send + Sync> = Arc::downgrade(&(config.clone() as Arc<dyn Any + Send + Sync>));
+        *slot = Some(weak);
+    }
+    Ok(config)
+}
+
+/// Returns the canonical form of the path, also for a file which does not exist yet, so a config created by `ConfigBuilder::create_if_missing` keeps its key once the file exists.
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => canonical(parent).join(name),
+        (Some(_), Some(name)) => Path::new(".").canonicalize().map_or_else(|_| path.to_path_buf(), |dir| dir.join(name)),
+        _ => path.to_path_buf(),
+    }
+}
+
+pub(crate) type Validator<T> = Arc<dyn Fn(&T) -> Result<(), String> + Send + Sync>;
+/// Selects a nested struct of a config for a `Section`.
+pub(crate) type Getter<T, S> = Arc<dyn Fn(&T) -> &S + Send + Sync>;
+/// Selects a nested struct of a config for changing it through a `Section`.
+pub(crate) type GetterMut<T, S> = Arc<dyn Fn(&mut T) -> &mut S + Send + Sync>;
+
+/// Boxes the accessor of a section. Taking it as `impl Fn` lets closures returning a reference into their argument be accepted.
+pub(crate) fn getter<T, S>(get: impl Fn(&T) -> &S + Send + Sync + 'static) -> Getter<T, S> {
+    Arc::new(get)
+}
+
+/// Boxes the mutable accessor of a section like [`getter`].
+pub(crate) fn getter_mut<T, S>(get_mut: impl Fn(&mut T) -> &mut S + Send + Sync + 'static) -> GetterMut<T, S> {
+    Arc::new(get_mut)
+}
+#[cfg(feature = "json")]
+pub(crate) type ChangeHandler = Arc<dyn Fn(&[Change]) + Send + Sync>;
+pub(crate) type Seed<T> = Arc<dyn Fn() -> T + Send + Sync>;
+pub(crate) type ErrorHandler<T> = Arc<dyn Fn(&Error, Option<&Path>) -> Recovery<T> + Send + Sync>;
+pub(crate) type EnvOverride<T> = fn(&mut T) -> Result<(), String>;
+pub(crate) type Check<T> = fn(&T) -> Result<(), String>;
+
+/// The options set on the builder which a loaded config keeps using, for example to validate before saving or to read the file again when reloading.
+pub(crate) struct Settings<T> {
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) validator: Option<Validator<T>>,
+    pub(crate) on_error: Option<ErrorHandler<T>>,
+    pub(crate) seed: Option<Seed<T>>,
+    #[cfg(feature = "json")]
+    pub(crate) on_change: Option<ChangeHandler>,
+    pub(crate) env: Option<EnvOverride<T>>,
+    pub(crate) check: Option<Check<T>>,
+    pub(crate) backups: usize,
+    pub(crate) detect_conflicts: bool,
+    pub(crate) format: Option<Format>,
+    pub(crate) mode: Option<u32>,
+    pub(crate) save: SaveOptions,
+    #[cfg(feature = "json")]
+    pub(crate) migrations: Option<Migrations<T>>,
+    #[cfg(feature = "json")]
+    pub(crate) deny_unknown_fields: bool,
+    #[cfg(feature = "json")]
+    pub(crate) merge_defaults: bool,
+    #[cfg(feature = "json")]
+    pub(crate) merge: MergeStrategy,
+    #[cfg(feature = "json")]
+    pub(crate) sections: Vec<(String, PathBuf)>,
+    #[cfg(feature = "json")]
+    pub(crate) expand_env: Option<UnsetVariable>,
+    /// The placeholders which were expanded while loading, so saving writes them back.
+    #[cfg(feature = "json")]
+    pub(crate) expanded: value::Replaced,
+    #[cfg(feature = "json")]
+    pub(crate) includes: bool,
+    /// The include directives and the values they added while loading, so saving keeps the directives instead of the values.
+    #[cfg(feature = "json")]
+    pub(crate) included: value::Replaced,
+    #[cfg(feature = "json")]
+    pub(crate) profile: Option<String>,
+    #[cfg(feature = "json")]
+    pub(crate) env_options: Option<EnvOptions>,
+    /// The values of the file which were overridden while loading, see [`Settings::apply_overrides`].
+    #[cfg(feature = "json")]
+    pub(crate) overridden: value::Replaced,
+    #[cfg(feature = "keyring")]
+    pub(crate) keyring: Option<Keyring>,
+    #[cfg(feature = "vault")]
+    pub(crate) vault: Option<Vault>,
+    #[cfg(feature = "encryption")]
+    pub(crate) encryption: Option<Encryption>,
+    #[cfg(feature = "encryption")]
+    pub(crate) plaintext_migration: bool,
+    #[cfg(feature = "checksum")]
+    pub(crate) checksum: bool,
+    #[cfg(feature = "clap")]
+    pub(crate) cli: Vec<(String, Vec<String>)>,
+    #[cfg(feature = "figment")]
+    pub(crate) providers: Option<figment::Figment>,
+    #[cfg(all(feature = "tracing", feature = "json"))]
+    pub(crate) redact: Option<fn() -> Vec<String>>,
+    pub(crate) poison: PoisonPolicy,
+}
+
+impl<T> Settings<T> {
+    /// Hashes the current content of the config file, if conflict detection is enabled and the file exists.
+    /// The raw bytes are hashed, so compressed and encrypted files are compared as they are stored.
+    pub(crate) fn disk_fingerprint(&self, path: &Path) -> Option<u64> {
+        if !self.detect_conflicts {
+            return None;
+        }
+        let data = ::std::fs::read(path).ok()?;
+        let hash = fingerprint(&data);
+        wipe(data);
+        Some(hash)
+    }
+
+    /// Returns a copy of the config to restore a poisoned lock with, if [`PoisonPolicy::Restore`] is set.
+    pub(crate) fn known_good(&self, config: &T) -> Option<T>
+        where T: Clone {
+        (self.poison == PoisonPolicy::Restore).then(|| config.clone())
+    }
+
+    /// Checks the ranges declared with attributes and runs the validator, if there is one.
+    pub(crate) fn validate(&self, config: &T) -> Result<(), Error> {
+        if let Some(check) = self.check {
+            check(config).map_err(|message| Error::ValidationError { path: None, format: None, message })?;
+        }
+        match &self.validator {
+            Some(validator) => validator(config).map_err(|message| Error::ValidationError { path: None, format: None, message }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the value missing config files are initialized with, set with [`crate::ConfigBuilder::or_init_with`], or else the default values of `T`.
+    pub(crate) fn initial(&self) -> T
+        where T: Default {
+        self.seed.as_ref().map_or_else(T::default, |seed| seed())
+    }
+
+    /// Overrides fields with the environment variables declared with attributes.
+    pub(crate) fn apply_env(&self, config: &mut T) -> Result<(), Error> {
+        match self.env {
+            Some(apply) => apply(config).map_err(Error::load),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns whether loaded configs are overridden by figment providers, environment variables or command line arguments.
+    pub(crate) fn overrides(&self) -> bool {
+        #[cfg(feature = "figment")]
+        if self.providers.is_some() {
+            return true;
+        }
+        #[cfg(feature = "clap")]
+        if !self.cli.is_empty() {
+            return true;
+        }
+        #[cfg(feature = "json")]
+        if self.env_options.is_some() {
+            return true;
+        }
+        self.env.is_some()
+    }
+}
+
+impl<T> Settings<T>
+    where T: serde::Serialize + serde::de::DeserializeOwned + Default {
+    /// Overrides fields with the figment providers, then with the environment variables named after the fields and the ones declared with attributes, and then with the command line arguments, which take precedence.
+    /// The overridden values are remembered, so saving writes them back in place of the overrides which were not changed since.
+    pub(crate) fn apply_overrides(&self, config: &mut T) -> Result<(), Error> {
+        #[cfg(feature = "json")]
+        let before = if self.overrides() { Some(serde_json::to_value(&*config).map_err(Error::serialize)?) } else { None };
+        #[cfg(feature = "figment")]
+        if let Some(providers) = &self.providers {
+            crate::provider::merge(config, providers)?;
+        }
+        #[cfg(feature = "json")]
+        if let Some(options) = &self.env_options {
+            value::override_fields(config, "the environment", |root| value::env_overrides(root, options))?;
+        }
+        self.apply_env(config)?;
+        #[cfg(feature = "clap")]
+        if !self.cli.is_empty() {
+            crate::cli::apply(config, &self.cli)?;
+        }
+        #[cfg(feature = "json")]
+        if let Some(before) = before {
+            self.overridden.record(&before, &serde_json::to_value(&*config).map_err(Error::serialize)?)?;
+        }
+        Ok(())
+    }
+
+    /// Overrides and validates a config which was not read with a loader, like one built by config-rs.
+    #[cfg(feature = "config-rs")]
+    pub(crate) fn adopt(&self, mut config: T) -> Result<T, Error> {
+        self.apply_overrides(&mut config)?;
+        self.validate(&config)?;
+        Ok(config)
+    }
+
+    /// Returns the loader for the config file at the given path, in the format set with [`crate::ConfigBuilder::format`] or else the one of its extension.
+    pub(crate) fn loader(&self, path: &Path) -> Result<Box<dyn ConfigManager<T> + '_>, Error> {
+        let format = match self.format {
+            Some(format) => format,
+            None => Format::from_path(path)?,
+        };
+        Ok(self.loader_for(format, path))
+    }
+
+    /// Returns the loader for the config file at the given path in the given format.
+    /// Sections mapped to their own files with [`crate::ConfigBuilder::map_section`] are read and written together with the file.
+    pub(crate) fn loader_for(&self, format: Format, path: &Path) -> Box<dyn ConfigManager<T> + '_> {
+        #[allow(unused_mut)]
+        let mut loader = manager::with_mode(manager::for_format(format, path), self.mode);
+        #[cfg(feature = "checksum")]
+        if self.checksum {
+            loader = Box::new(Checksummed { inner: loader });
+        }
+        let loader = self.open(loader);
+        #[cfg(feature = "json")]
+        if !self.sections.is_empty() {
+            return Box::new(Split::new(loader, &self.sections, path, self.mode, &self.save));
+        }
+        loader
+    }
+
+    /// Returns the format of the config file at the given path, like [`Settings::loader`] picks it.
+    pub(crate) fn format_of(&self, path: &Path) -> Option<Format> {
+        self.format.or_else(|| Format::from_path(path).ok())
+    }
+
+    /// Wraps the loader so it decrypts the config it reads and encrypts the config it writes, if an encryption key is set.
+    pub(crate) fn open<'a>(&self, loader: Box<dyn ConfigManager<T> + 'a>) -> Box<dyn ConfigManager<T> + 'a>
+        where T: 'a {
+        #[cfg(feature = "encryption")]
+        if let Some(encryption) = &self.encryption {
+            return manager::with_options(Box::new(Encrypted { inner: loader, encryption: encryption.clone(), plaintext: self.plaintext_migration }), &self.save);
+        }
+        manager::with_options(loader, &self.save)
+    }
+
+    /// Reads and validates the config with the given loader. Returns the config and the fingerprint of its serialized form.
+    /// The fingerprint is `None` if the config was migrated or completed with defaults, because the file on disk is outdated then.
+    /// Fields overridden by figment providers, environment variables or command line arguments do not count as changes.
+    pub(crate) fn read(&self, loader: &dyn ConfigManager<T>) -> Result<(T, Option<u64>), Error> {
+        self.read_with(loader).map_err(|err| err.at(loader.path(), Some(loader.format())))
+    }
+
+    /// Reads the config like [`Settings::read`], without adding the path and the format to errors.
+    fn read_with(&self, loader: &dyn ConfigManager<T>) -> Result<(T, Option<u64>), Error> {
+        let (mut config, mut outdated) = self.parse(loader)?;
+        // Secrets which were not taken from the keyring are still written in plain text in the file.
+        outdated |= self.secrets(&config)?.changed();
+        self.apply_overrides(&mut config)?;
+        self.validate(&config)?;
+        #[cfg(feature = "tracing")]
+        debug!(config = self.redacted(loader, &config), "loaded config");
+        let persisted = self.serialize(loader, &config).ok().filter(|_| !outdated).map(|data| {
+            let hash = fingerprint(&data);
+            wipe(data);
+            hash
+        });
+        Ok((config, persisted))
+    }
+
+    /// Serializes the config with the given loader. With a profile, the config is put into its section of the existing file.
+    /// With a keyring, every [`crate::Secret`] is replaced by a placeholder. The secrets are written to the keyring separately, see [`Settings::secrets`].
+    pub(crate) fn serialize(&self, loader: &dyn ConfigManager<T>, config: &T) -> Result<Vec<u8>, Error> {
+        self.serialize_with(loader, config).map_err(|err| err.at(loader.path(), Some(loader.format())))
+    }
+
+    /// Returns the fingerprint of the config serialized by the given loader, which `is_dirty` compares with the one of the last save.
+    pub(crate) fn fingerprint(&self, loader: &dyn ConfigManager<T>, config: &T) -> Result<u64, Error> {
+        let data = self.serialize(loader, config)?;
+        let hash = fingerprint(&data);
+        wipe(data);
+        Ok(hash)
+    }
+
+    /// Serializes the config like [`Settings::serialize`], without adding the path and the format to errors.
+    fn serialize_with(&self, loader: &dyn ConfigManager<T>, config: &T) -> Result<Vec<u8>, Error> {
+        #[cfg(feature = "json")]
+        if let Some(value) = self.stored_value(config)? {
+            return match &self.profile {
+                Some(profile) => self.serialize_profile(loader, profile, value),
+                None => loader.serialize_value(&value),
+            };
+        }
+        loader.serialize(config)
+    }
+
+    /// Returns the generic value which is stored instead of the config, if the keyring, overrides, Vault, expanded placeholders, includes or a profile need one. With a keyring, every [`crate::Secret`] is replaced by a placeholder.
+    /// Overridden fields which were not changed get the values of the file back, with Vault the secrets which were not changed are replaced by their references again, and expanded values which were not changed by their placeholders.
+    /// Values which were not changed since they were included are removed and the include directives are put back. With a profile this happens to the whole file, see [`Settings::serialize_profile`].
+    #[cfg(feature = "json")]
+    fn stored_value(&self, config: &T) -> Result<Option<serde_json::Value>, Error> {
+        #[allow(unused_mut)]
+        let mut value = None;
+        #[cfg(feature = "keyring")]
+        if let Some(keyring) = &self.keyring {
+            value = Some(keyring.split(config)?.0);
+        }
+        let includes = self.profile.is_none() && !self.included.is_empty();
+        if !self.overridden.is_empty() || self.uses_vault() || !self.expanded.is_empty() || includes {
+            let split = value.is_some();
+            let mut stored = match value.take() {
+                Some(value) => value,
+                None => serde_json::to_value(config).map_err(Error::serialize)?,
+            };
+            // Without anything to put back the config is serialized as it is, which keeps the order of its fields.
+            let mut restored = self.overridden.restore(&mut stored)?;
+            #[cfg(feature = "vault")]
+            if let Some(vault) = &self.vault {
+                vault.restore(&mut stored)?;
+            }
+            restored |= self.expanded.restore(&mut stored)?;
+            if includes {
+                restored |= self.included.restore(&mut stored)?;
+            }
+            if split || restored || self.uses_vault() {
+                value = Some(stored);
+            }
+        }
+        if value.is_none() && self.profile.is_some() {
+            value = Some(serde_json::to_value(config).map_err(Error::serialize)?);
+        }
+        Ok(value)
+    }
+
+    /// Serializes the config into its profile section of the existing file.
+    #[cfg(feature = "json")]
+    fn serialize_profile(&self, loader: &dyn ConfigManager<T>, profile: &str, config: serde_json::Value) -> Result<Vec<u8>, Error> {
+        let path = loader.path();
+        let existing = if path.exists() && !is_empty_file(path) { Some(loader.load_value()?) } else { None };
+        let mut document = value::store_profile(existing, profile, config);
+        // The includes were resolved in the whole file, before the profile was selected.
+        self.included.restore(&mut document)?;
+        loader.serialize_value(&document)
+    }
+
+    /// Serializes the config for the logs, with every [`crate::Secret`] and every field marked as secret with attributes masked. Secrets from Vault are logged as their references.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn redacted(&self, loader: &dyn ConfigManager<T>, config: &T) -> String {
+        let serialized = crate::secret::redacting(|| {
+            #[cfg(feature = "json")]
+            if self.redact.is_some() || self.uses_vault() {
+                let mut value = serde_json::to_value(config)?;
+                #[cfg(feature = "vault")]
+                if let Some(vault) = &self.vault {
+                    vault.restore(&mut value)?;
+                }
+                for path in self.redact.map(|secrets| secrets()).unwrap_or_default() {
+                    value::mask_path(&mut value, &path, crate::secret::REDACTED);
+                }
+                return loader.serialize_value(&value);
+            }
+            loader.serialize(config)
+        });
+        match serialized.map(String::from_utf8) {
+            Ok(Ok(serialized)) => serialized,
+            Ok(Err(err)) => format!("<{} bytes>", err.into_bytes().len()),
+            Err(err) => format!("<{}>", err),
+        }
+    }
+
+    /// Takes the [`crate::Secret`]s of the config which go to the keyring, so they can be stored together with the file. Without a keyring there are none.
+    #[allow(unused_variables)]
+    pub(crate) fn secrets(&self, config: &T) -> Result<Secrets<'_>, Error> {
+        #[cfg(feature = "keyring")]
+        if let Some(keyring) = &self.keyring {
+            let (_, secrets) = keyring.split(config)?;
+            return Ok(Secrets { pending: Some((keyring, secrets)) });
+        }
+        Ok(Secrets::default())
+    }
+
+    /// Compares two versions of the config for the callback set with [`crate::ConfigBuilder::on_change`]. Without a callback nothing is compared.
+    #[allow(unused_variables)]
+    pub(crate) fn changes(&self, old: &T, new: &T) -> Changes<'_> {
+        #[cfg(feature = "json")]
+        if let Some(on_change) = &self.on_change {
+            return Changes { pending: Some((on_change, crate::diff(old, new).unwrap_or_default())) };
+        }
+        Changes::default()
+    }
+
+    #[cfg(feature = "json")]
+    fn uses_keyring(&self) -> bool {
+        #[cfg(feature = "keyring")]
+        return self.keyring.is_some();
+        #[cfg(not(feature = "keyring"))]
+        false
+    }
+
+    #[cfg(feature = "json")]
+    fn uses_vault(&self) -> bool {
+        #[cfg(feature = "vault")]
+        return self.vault.is_some();
+        #[cfg(not(feature = "vault"))]
+        false
+    }
+
+    /// Parses the config with the given loader. Returns the config and whether it differs from the file, because it was migrated or completed with defaults.
+    fn parse(&self, loader: &dyn ConfigManager<T>) -> Result<(T, bool), Error> {
+        #[cfg(feature = "json")]
+        if self.migrations.is_some() || self.deny_unknown_fields || self.merge_defaults || self.expand_env.is_some() || self.includes || self.profile.is_some() || self.uses_keyring() || self.uses_vault() {
+            let mut value = loader.load_value()?;
+            if self.includes {
+                let directives = value.clone();
+                value::resolve_includes(&mut value, loader.path(), &self.merge)?;
+                self.included.record(&directives, &value)?;
+            }
+            if let Some(profile) = &self.profile {
+                value = value::select_profile(value, profile, &self.merge)?;
+            }
+            #[cfg(feature = "keyring")]
+            if let Some(keyring) = &self.keyring {
+                keyring.resolve(&mut value)?;
+            }
+            let mut outdated = false;
+            if let Some(migrations) = &self.migrations {
+                (value, outdated) = migrations.apply(value)?;
+            }
+            if let Some(unset) = self.expand_env {
+                let placeholders = value.clone();
+                value::expand_env(&mut value, unset)?;
+                self.expanded.record(&placeholders, &value)?;
+            }
+            #[cfg(feature = "vault")]
+            if let Some(vault) = &self.vault {
+                vault.resolve(&mut value)?;
+            }
+            if self.deny_unknown_fields || self.merge_defaults {
+                let defaults = serde_json::to_value(T::default()).map_err(Error::serialize)?;
+                if self.deny_unknown_fields {
+                    let unknown = value::unknown_keys(&value, &defaults);
+                    if !unknown.is_empty() {
+                        return Err(Error::UnknownFields { path: None, format: None, keys: unknown });
+                    }
+                }
+                if self.merge_defaults {
+                    outdated |= value::merge(&mut value, defaults, &self.merge);
+                }
+            }
+            return Ok((serde_json::from_value(value)?, outdated));
+        }
+        Ok((loader.load()?, false))
+    }
+}
+
+impl<T> Default for Settings<T> {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            validator: None,
+            on_error: None,
+            seed: None,
+            #[cfg(feature = "json")]
+            on_change: None,
+            env: None,
+            check: None,
+            backups: 0,
+            detect_conflicts: false,
+            format: None,
+            mode: None,
+            save: SaveOptions::default(),
+            #[cfg(feature = "json")]
+            migrations: None,
+            #[cfg(feature = "json")]
+            deny_unknown_fields: false,
+            #[cfg(feature = "json")]
+            merge_defaults: false,
+            #[cfg(feature = "json")]
+            merge: MergeStrategy::Merge,
+            #[cfg(feature = "json")]
+            sections: Vec::new(),
+            #[cfg(feature = "json")]
+            expand_env: None,
+            #[cfg(feature = "json")]
+            expanded: value::Replaced::default(),
+            #[cfg(feature = "json")]
+            includes: false,
+            #[cfg(feature = "json")]
+            included: value::Replaced::default(),
+            #[cfg(feature = "json")]
+            profile: None,
+            #[cfg(feature = "json")]
+            env_options: None,
+            #[cfg(feature = "json")]
+            overridden: value::Replaced::default(),
+            #[cfg(feature = "keyring")]
+            keyring: None,
+            #[cfg(feature = "vault")]
+            vault: None,
+            #[cfg(feature = "encryption")]
+            encryption: None,
+            #[cfg(feature = "encryption")]
+            plaintext_migration: false,
+            #[cfg(feature = "checksum")]
+            checksum: false,
+            #[cfg(feature = "clap")]
+            cli: Vec::new(),
+            #[cfg(feature = "figment")]
+            providers: None,
+            #[cfg(all(feature = "tracing", feature = "json"))]
+            redact: None,
+            poison: PoisonPolicy::Ignore,
+        }
+    }
+}
+
+impl<T> Clone for Settings<T> {
+    fn clone(&self) -> Self {
+        Self {
+            clock: self.clock.clone(),
+            validator: self.validator.clone(),
+            on_error: self.on_error.clone(),
+            seed: self.seed.clone(),
+            #[cfg(feature = "json")]
+            on_change: self.on_change.clone(),
+            env: self.env,
+            check: self.check,
+            backups: self.backups,
+            detect_conflicts: self.detect_conflicts,
+            format: self.format,
+            mode: self.mode,
+            save: self.save.clone(),
+            #[cfg(feature = "json")]
+            migrations: self.migrations.clone(),
+            #[cfg(feature = "json")]
+            deny_unknown_fields: self.deny_unknown_fields,
+            #[cfg(feature = "json")]
+            merge_defaults: self.merge_defaults,
+            #[cfg(feature = "json")]
+            merge: self.merge.clone(),
+            #[cfg(feature = "json")]
+            sections: self.sections.clone(),
+            #[cfg(feature = "json")]
+            expand_env: self.expand_env,
+            #[cfg(feature = "json")]
+            expanded: self.expanded.clone(),
+            #[cfg(feature = "json")]
+            includes: self.includes,
+            #[cfg(feature = "json")]
+            included: self.included.clone(),
+            #[cfg(feature = "json")]
+            profile: self.profile.clone(),
+            #[cfg(feature = "json")]
+            env_options: self.env_options.clone(),
+            #[cfg(feature = "json")]
+            overridden: self.overridden.clone(),
+            #[cfg(feature = "keyring")]
+            keyring: self.keyring.clone(),
+            #[cfg(feature = "vault")]
+            vault: self.vault.clone(),
+            #[cfg(feature = "encryption")]
+            encryption: self.encryption.clone(),
+            #[cfg(feature = "encryption")]
+            plaintext_migration: self.plaintext_migration,
+            #[cfg(feature = "checksum")]
+            checksum: self.checksum,
+            #[cfg(feature = "clap")]
+            cli: self.cli.clone(),
+            #[cfg(feature = "figment")]
+            providers: self.providers.clone(),
+            #[cfg(all(feature = "tracing", feature = "json"))]
+            redact: self.redact,
+            poison: self.poison,
+        }
+    }
+}
+
+impl<T> ::std::fmt::Debug for Settings<T> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        let mut settings = f.debug_struct("Settings");
+        settings
+            .field("clock", &self.clock)
+            .field("validator", &self.validator.is_some())
+            .field("on_error", &self.on_error.is_some())
+            .field("seed", &self.seed.is_some())
+            .field("attributes", &self.check.is_some())
+            .field("backups", &self.backups)
+            .field("detect_conflicts", &self.detect_conflicts)
+            .field("format", &self.format)
+            .field("mode", &self.mode)
+            .field("save", &self.save);
+        #[cfg(feature = "json")]
+        settings
+            .field("migrations", &self.migrations)
+            .field("deny_unknown_fields", &self.deny_unknown_fields)
+            .field("merge_defaults", &self.merge_defaults)
+            .field("merge", &self.merge)
+            .field("sections", &self.sections)
+            .field("on_change", &self.on_change.is_some())
+            .field("expand_env", &self.expand_env)
+            .field("includes", &self.includes)
+            .field("profile", &self.profile)
+            .field("env_options", &self.env_options);
+        #[cfg(feature = "keyring")]
+        settings.field("keyring", &self.keyring);
+        #[cfg(feature = "vault")]
+        settings.field("vault", &self.vault);
+        #[cfg(feature = "encryption")]
+        settings.field("encryption", &self.encryption).field("plaintext_migration", &self.plaintext_migration);
+        #[cfg(feature = "checksum")]
+        settings.field("checksum", &self.checksum);
+        #[cfg(feature = "clap")]
+        settings.field("cli", &self.cli.iter().map(|(id, _)| id).collect::<Vec<_>>());
+        #[cfg(feature = "figment")]
+        settings.field("providers", &self.providers.is_some());
+        settings.field("poison", &self.poison);
+        settings.finish()
+    }
+}
+
+/// The secrets of a config which are written to the keyring instead of the config file, see [`Settings::secrets`].
+#[derive(Default)]
+pub(crate) struct Secrets<'a> {
+    #[cfg(feature = "keyring")]
+    pending: Option<(&'a Keyring, Vec<(String, serde_json::Value)>)>,
+    #[cfg(not(feature = "keyring"))]
+    pending: ::std::marker::PhantomData<&'a ()>,
+}
+
+impl Secrets<'_> {
+    /// Returns whether a secret differs from the one in the keyring.
+    pub(crate) fn changed(&self) -> bool {
+        #[cfg(feature = "keyring")]
+        if let Some((keyring, secrets)) = &self.pending {
+            return keyring.changed(secrets);
+        }
+        false
+    }
+
+    /// Writes the secrets which changed to the keyring.
+    pub(crate) fn store(self) -> Result<(), Error> {
+        #[cfg(feature = "keyring")]
+        if let Some((keyring, secrets)) = self.pending {
+            return keyring.store(secrets);
+        }
+        Ok(())
+    }
+}
+
+/// The changes of a config which are reported to the callback of [`crate::ConfigBuilder::on_change`], see [`Settings::changes`].
+/// They are reported after the lock of the config was released, so the callback can read the config.
+#[derive(Default)]
+pub(crate) struct Changes<'a> {
+    #[cfg(feature = "json")]
+    pending: Option<(&'a ChangeHandler, Vec<Change>)>,
+    #[cfg(not(feature = "json"))]
+    pending: ::std::marker::PhantomData<&'a ()>,
+}
+
+impl Changes<'_> {
+    /// Calls the callback, if a value changed.
+    pub(crate) fn notify(self) {
+        #[cfg(feature = "json")]
+        if let Some((on_change, changes)) = self.pending {
+            if !changes.is_empty() {
+                on_change(&changes);
+            }
+        }
+    }
+}
+
+/// Returns a random duration between zero and `max`, used to spread out periodic work of many instances.
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+pub(crate) fn jitter(max: Duration) -> Duration {
+    use ::std::hash::{BuildHasher, Hasher};
+
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    // Every RandomState is seeded differently, which is random enough to avoid a thundering herd.
+    let random = ::std::collections::hash_map::RandomState::new().build_hasher().finish();
+    Duration::from_nanos(random % (max.as_nanos().min(u64::MAX as u128) as u64).saturating_add(1))
+}
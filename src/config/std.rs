@@ -1,25 +1,66 @@
+use std::io::{Read, Write};
 use std::marker::PhantomData;
-use std::path;
 use std::path::{Path, PathBuf};
-use crate::{Error, Lock, manager};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+use std::sync::atomic::Ordering;
+use std::time::SystemTime;
+use crate::{Backend, Clock, Describe, Error, Format, Opzioni, SaveOptions, SearchPaths, manager};
+use crate::backend::{self, Remote};
+use crate::config::{self, fingerprint, is_empty_file, rotate_backups, Location, PoisonPolicy, Recovery, SaveGuard, Settings, Shared, Skipped};
+use crate::secret::wipe;
+use crate::manager::ConfigManager;
+#[cfg(feature = "json")]
+use crate::{Diagnostic, EnvOptions, MergeStrategy, Migrations, UnsetVariable, value};
+#[cfg(feature = "file-lock")]
+use crate::file_lock::{self, FileLock};
+#[cfg(feature = "keyring")]
+use crate::credentials::Keyring;
+#[cfg(feature = "vault")]
+use crate::Vault;
+#[cfg(feature = "encryption")]
+use crate::encryption::Encryption;
 
-#[derive(Debug)]
+/// A typed config, together with the file or [`crate::Backend`] it is saved to and reloaded from.
+/// Cloning a config is cheap and returns a handle to the same config: all clones share the lock, the file and the background tasks, so a config can be handed to threads and tasks without wrapping it in an `Arc`.
+///
+/// # Example
+/// ```
+/// use opzioni::blocking::Config;
+/// use serde::{Serialize, Deserialize};
+/// use std::path::Path;
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+/// let handle = config.clone();
+/// std::thread::spawn(move || {
+///     handle.get().write().unwrap().age = 43;
+/// }).join().unwrap();
+/// assert_eq!(config.get().read().unwrap().age, 43);
+/// ```
 pub struct Config<T>
     where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
-    pub config: Lock<T>,
-    pub path: Option<path::PathBuf>,
+    pub(crate) inner: Arc<Shared<T, RwLock<T>>>,
 }
 
 impl<T> Config<T>
     where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    pub(crate) fn from_parts(config: T, path: Option<PathBuf>, settings: Settings<T>, loaded_at: Option<SystemTime>, persisted: Option<u64>) -> Self {
+        Self { inner: Arc::new(Shared::new(config, path, settings, loaded_at, persisted)) }
+    }
+
     /// Creates a new Config struct.
     /// The config struct uses the default values of the given type T.
-    /// It is better to directly load a config file with the [`crate::Config::configure`] method, because empty will panic if the save method is called.
+    /// It is better to directly load a config file with the [`Config::configure`] method. Use [`Config::in_memory`] for a config without a file.
     ///
     /// # Example
     /// ```
     /// use std::path::PathBuf;
-    /// use opzioni::Config;
+    /// use opzioni::blocking::Config;
     /// use serde::{Serialize, Deserialize};
     ///
     /// #[derive(Serialize, Deserialize, Default, Clone)]
@@ -31,18 +72,84 @@ impl<T> Config<T>
     /// let config = Config::<MyConfig>::new(MyConfig::default(), PathBuf::new());
     /// ```
     pub fn new(config: T, path: PathBuf) -> Self {
-        Self {
-            config: Lock::new(config),
-            path: Some(path),
-        }
+        Self::from_parts(config, Some(path), Settings::default(), None, None)
+    }
+
+    /// Parses a config from a string in the given [`crate::Format`], for example when it arrives through an API or a command line flag.
+    /// The returned config has no file. Use [`ConfigBuilder::load_from_str`] to apply options of the builder while parsing.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Format};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::from_str(r#"{ "name": "John", "age": 42 }"#, Format::Json).unwrap();
+    /// assert_eq!(config.get().read().unwrap().name, "John");
+    /// assert!(config.path().is_none());
+    /// ```
+    pub fn from_str(data: &str, format: Format) -> Result<Self, Error> {
+        Self::configure().load_from_str(data, format)
+    }
+
+    /// Creates a config which only lives in memory and has no file, for example for tests or ephemeral settings.
+    /// Saving, reloading and locking it returns [`Error::NoBackingFile`] until it is bound to a file with [`Config::attach`].
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Error};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config = Config::in_memory(MyConfig { name: "John".to_string(), age: 42 });
+    /// assert!(config.path().is_none());
+    /// assert!(matches!(config.save(), Err(Error::NoBackingFile)));
+    /// ```
+    pub fn in_memory(config: T) -> Self {
+        Self::from_parts(config, None, Settings::default(), None, None)
+    }
+
+    /// Binds the config to the given file, so it can be saved, like a config created with [`Config::in_memory`]. The file is not read.
+    /// This works like [`Config::set_path`], but takes and returns the config, so it can be chained to the constructor.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-attach.toml");
+    /// let config = Config::in_memory(MyConfig { name: "John".to_string(), age: 42 }).attach(path.clone());
+    /// config.save().unwrap();
+    /// assert!(std::fs::read_to_string(&path).unwrap().contains("age = 42"));
+    /// ```
+    pub fn attach(self, path: PathBuf) -> Self {
+        self.set_path(path);
+        self
     }
 
     /// Access the `Lock` of the config used to read and write the config.
-    /// To save the config to file use the [`crate::Config::save`] method.
+    /// To save the config to file use the [`Config::save`] method.
+    /// Whether readers or writers are preferred depends on the operating system, as documented for the std `RwLock`. Enable the `tokio` feature for a lock which is guaranteed to be fair.
     ///
     /// # Example
     /// ```
-    /// use opzioni::Config;
+    /// use opzioni::blocking::Config;
     /// use serde::{Serialize, Deserialize};
     ///
     /// #[derive(Serialize, Deserialize, Default, Clone)]
@@ -51,21 +158,22 @@ impl<T> Config<T>
     ///   age: u8,
     /// }
     ///
-    /// let config = Config::<MyConfig>::empty();
+    /// let config = Config::<MyConfig>::default();
     /// let config_lock = config.get();
     /// let mut config = config_lock.write().unwrap();
     /// config.name = "John".to_string();
     /// config.age = 42;
     /// ```
-    pub fn get(&self) -> &Lock<T> {
-        &self.config
+    pub fn get(&self) -> &RwLock<T> {
+        &self.inner.config
     }
 
-    /// Configure returns a new [`ConfigBuilder`] to load a config file from disk. See [`ConfigBuilder::load`] for more information.
+    /// Locks the config for reading, blocking until no writer holds the lock. Shorthand for `get().read()`.
+    /// A lock poisoned by a thread which panicked while writing is handled with the [`crate::PoisonPolicy`] of the builder instead of panicking. With [`crate::PoisonPolicy::Error`] the config is returned as it is.
     ///
     /// # Example
     /// ```
-    /// use opzioni::Config;
+    /// use opzioni::blocking::Config;
     /// use serde::{Serialize, Deserialize};
     /// use std::path::Path;
     ///
@@ -75,145 +183,2634 @@ impl<T> Config<T>
     ///   age: u8,
     /// }
     ///
-    /// let config: Config<MyConfig> = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// assert_eq!(config.read().name, "John");
+    /// config.write().age += 1;
+    /// assert_eq!(config.read().age, 43);
     /// ```
-    pub fn configure() -> ConfigBuilder<T> {
-        ConfigBuilder {
-            _p: PhantomData,
-            use_default_on_error: false,
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        if self.needs_recovery() {
+            drop(self.write());
         }
+        self.inner.config.read().unwrap_or_else(PoisonError::into_inner)
     }
 
-    /// Saves the config to file. The file extension of the config file determines the format of the config file.
-    /// The currently supported formats are JSON, TOML and YAML.
-    /// The config file is overwritten.
-    /// If the config file could not be saved, an error is returned.
-    /// If the config file was loaded from disk, the config is saved to the same file.
-    /// If the config file was created with [`crate::Config::empty`], the method returns an error.
+    /// Locks the config for writing, blocking until no other reader or writer holds the lock. Shorthand for `get().write()`, see [`Config::read`].
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        let mut config = self.inner.config.write().unwrap_or_else(PoisonError::into_inner);
+        if self.needs_recovery() {
+            let _ = self.recover(&mut config);
+        }
+        config
+    }
+
+    /// Locks the config for reading if no writer holds the lock, without blocking. Returns `None` otherwise, so latency-sensitive code can fall back to a previous value instead of waiting.
+    /// Like [`Config::read`] it handles a poisoned lock with the [`crate::PoisonPolicy`] of the builder.
     ///
     /// # Example
     /// ```
-    /// use opzioni::Config;
+    /// use opzioni::blocking::Config;
     /// use serde::{Serialize, Deserialize};
     /// use std::path::Path;
     ///
     /// #[derive(Serialize, Deserialize, Default, Clone)]
     /// struct MyConfig {
-    ///  name: String,
-    ///  age: u8,
+    ///   name: String,
+    ///   age: u8,
     /// }
     ///
-    /// let config: Config<MyConfig> = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
-    /// config.get().write().unwrap().name = "John".to_string();
-    /// config.get().write().unwrap().age = 42;
-    /// config.save().unwrap();
+    /// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// let writer = config.write();
+    /// assert!(config.try_read().is_none());
+    /// drop(writer);
+    /// assert_eq!(config.try_read().unwrap().age, 42);
     /// ```
-    #[cfg(not(feature = "tokio"))]
-    pub fn save(&self) -> Result<(), Error> {
-        match &self.path {
-            Some(path) => match manager::for_file::<T>(path) {
-                Ok(loader) => loader.save(&self.config.read().unwrap()),
-                Err(err) => Err(err),
-            },
-            None => Err(Error::ConfigLoadError(None)),
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        if self.needs_recovery() {
+            drop(self.try_write()?);
+        }
+        match self.inner.config.try_read() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::Poisoned(err)) => Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
         }
     }
-}
 
-impl<T> Default for Config<T>
-    where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
-    fn default() -> Self {
-        Self {
-            path: None,
-            config: Lock::new(T::default()),
+    /// Locks the config for writing if no other reader or writer holds the lock, without blocking. Returns `None` otherwise, see [`Config::try_read`].
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        let mut config = match self.inner.config.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+        if self.needs_recovery() {
+            let _ = self.recover(&mut config);
         }
+        Some(config)
     }
-}
 
-/// The ConfigBuilder struct is used to load a config file from disk. See [`ConfigBuilder::load`] for more information.
-pub struct ConfigBuilder<T> where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
-    _p: PhantomData<T>,
-    use_default_on_error: bool,
-}
+    /// Locks the config for reading, failing with [`Error::Poisoned`] if the lock is poisoned and [`crate::PoisonPolicy::Error`] is set.
+    fn lock_read(&self) -> Result<RwLockReadGuard<'_, T>, Error> {
+        if self.needs_recovery() {
+            drop(self.lock_write()?);
+        }
+        Ok(self.inner.config.read().unwrap_or_else(PoisonError::into_inner))
+    }
 
-impl<T> ConfigBuilder<T> where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
-    fn handle_load_err(&self, err: Error, path: &Path) -> Result<crate::Config<T>, Error>
-    {
-        if !self.use_default_on_error {
-            return Err(err);
+    /// Locks the config for writing, failing with [`Error::Poisoned`] if the lock is poisoned and [`crate::PoisonPolicy::Error`] is set.
+    fn lock_write(&self) -> Result<RwLockWriteGuard<'_, T>, Error> {
+        let mut config = self.inner.config.write().unwrap_or_else(PoisonError::into_inner);
+        if self.needs_recovery() {
+            self.recover(&mut config)?;
+        }
+        Ok(config)
+    }
+
+    /// Returns whether the lock was poisoned and the poisoning was not handled yet.
+    fn needs_recovery(&self) -> bool {
+        self.inner.config.is_poisoned() && !self.inner.recovered.load(Ordering::Acquire)
+    }
+
+    /// Handles a poisoned lock with the [`crate::PoisonPolicy`] of the builder, while holding the write lock.
+    fn recover(&self, config: &mut T) -> Result<(), Error> {
+        match self.inner.settings.poison {
+            PoisonPolicy::Ignore => {}
+            PoisonPolicy::Restore => {
+                if let Some(good) = self.inner.good.lock().unwrap_or_else(PoisonError::into_inner).clone() {
+                    *config = good;
+                }
+            }
+            PoisonPolicy::Error => return Err(Error::Poisoned),
         }
         #[cfg(feature = "tracing")]
-        trace!(
-            error = err.to_string(),
-            "using default config because of error"
-        );
-        return Ok(crate::Config {
-            config: Lock::new(T::default()),
-            path: Some(path.to_path_buf()),
-        });
+        warn!(policy = ?self.inner.settings.poison, "recovered config from a poisoned lock");
+        // The poison flag of the lock can only be cleared from Rust 1.77 on, so the handled poisoning is remembered instead.
+        self.inner.recovered.store(true, Ordering::Release);
+        Ok(())
     }
 
-    /// If this method is called, the config will use the default values of the given type `T` if an error occurs while loading the config file.
-    /// It causes the [`ConfigBuilder::load`] method to return a [`crate::Config`] struct with the default values of the given type `T` instead of an error.
+    /// Remembers the config as the last good version for [`crate::PoisonPolicy::Restore`].
+    fn keep_good(&self, good: Option<T>) {
+        if good.is_some() {
+            *self.inner.good.lock().unwrap_or_else(PoisonError::into_inner) = good;
+        }
+    }
+
+    /// Returns a copy of the current config. The read lock is only held while cloning, so the copy can be kept or moved into another thread without blocking writers.
     ///
     /// # Example
     /// ```
-    /// use opzioni::Config;
+    /// use opzioni::blocking::Config;
     /// use serde::{Serialize, Deserialize};
     /// use std::path::Path;
     ///
-    /// #[derive(Serialize, Deserialize, Clone)]
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
     /// struct MyConfig {
     ///   name: String,
     ///   age: u8,
     /// }
     ///
-    /// impl Default for MyConfig {
-    ///   fn default() -> Self {
-    ///     Self {
-    ///       name: "John".to_string(),
-    ///       age: 42,
-    ///     }
-    ///   }
+    /// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// let snapshot = config.snapshot();
+    /// config.write().age += 1;
+    /// assert_eq!(snapshot.age, 42);
+    /// ```
+    pub fn snapshot(&self) -> T {
+        self.read().clone()
+    }
+
+    /// Replaces the whole config with a new value, for example one built from a settings form, and returns the previous one.
+    /// Readers see either the old or the new config, never a mix of both. Like changes made through the lock, the new config is validated when it is saved.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
     /// }
     ///
-    /// let config: Config<MyConfig> = Config::<MyConfig>::configure().use_default_on_error().load(Path::new("testconfig.json")).unwrap();
+    /// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// let previous = config.replace(MyConfig { name: "Jane".to_string(), age: 36 });
+    /// assert_eq!(previous.name, "John");
+    ///
+    /// let mut other = MyConfig::default();
+    /// config.swap(&mut other);
+    /// assert_eq!(other.name, "Jane");
+    /// assert_eq!(config.read().name, "");
     /// ```
-    pub fn use_default_on_error(&mut self) -> &mut Self {
-        self.use_default_on_error = true;
-        self
+    pub fn replace(&self, config: T) -> T {
+        let mut current = self.write();
+        let changes = self.inner.settings.changes(&current, &config);
+        let previous = std::mem::replace(&mut *current, config);
+        drop(current);
+        changes.notify();
+        previous
     }
 
-    /// Loads a config file from disk. The file extension of the config file determines the format of the config file.
-    /// The currently supported formats are JSON, TOML and YAML.
-    /// The config file must contain a valid config of the given type `T`.
-    /// If the config file does not exist or is invalid, an error is returned. To use the default values of the given type `T` instead of an error, set [`ConfigBuilder::use_default_on_error`].
+    /// Exchanges the config with the given value, like [`Config::replace`].
+    pub fn swap(&self, config: &mut T) {
+        let mut current = self.write();
+        let changes = self.inner.settings.changes(&current, config);
+        std::mem::swap(&mut *current, config);
+        drop(current);
+        changes.notify();
+    }
+
+    /// Changes the config through a draft, which only replaces the config if the update and the validation succeed.
+    /// The update gets a copy of the current config. If it returns an error, or the changed copy is rejected by the checks and the validator of the builder, the config is left untouched and the error is returned.
+    /// The write lock is held for the whole transaction, so no other change is lost.
     ///
     /// # Example
     /// ```
-    /// use opzioni::Config;
+    /// use opzioni::{blocking::Config, Error};
     /// use serde::{Serialize, Deserialize};
     /// use std::path::Path;
     ///
     /// #[derive(Serialize, Deserialize, Default, Clone)]
     /// struct MyConfig {
-    ///    name: String,
-    ///    age: u8,
+    ///   name: String,
+    ///   age: u8,
     /// }
     ///
-    /// let config: Config<MyConfig> = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// let config = Config::<MyConfig>::configure()
+    ///     .validate(|config: &MyConfig| if config.age > 150 { Err(format!("{} is too old", config.age)) } else { Ok(()) })
+    ///     .load(Path::new("testconfig.json"))
+    ///     .unwrap();
+    ///
+    /// let failed = config.transaction(|draft| {
+    ///     draft.name = "Jane".to_string();
+    ///     draft.age = 200;
+    ///     Ok::<_, Error>(())
+    /// });
+    /// assert!(matches!(failed, Err(Error::ValidationError { .. })));
+    /// assert_eq!(config.read().name, "John");
+    ///
+    /// config.transaction(|draft| {
+    ///     draft.age += 1;
+    ///     Ok::<_, Error>(())
+    /// }).unwrap();
+    /// assert_eq!(config.read().age, 43);
     /// ```
-    pub fn load(&mut self, path: &Path) -> Result<crate::Config<T>, Error>
-    {
-        match manager::for_file(path) {
-            Ok(loader) => match loader.load() {
-                Ok(config) => Ok(crate::Config {
-                    config: Lock::new(config),
-                    path: Some(path.to_path_buf()),
-                }),
-                Err(err) => self.handle_load_err(err, &path),
-            },
-            Err(err) => self.handle_load_err(err, &path),
-        }
+    pub fn transaction<F, E>(&self, update: F) -> Result<(), E>
+        where F: FnOnce(&mut T) -> Result<(), E>, E: From<Error> {
+        self.commit(update).map(|_| ())
+    }
+
+    /// Changes the config through a draft like [`Config::transaction`] and saves it if the transaction succeeded.
+    /// The write lock is held until the draft is saved, and the config is only replaced if saving succeeded, so the config in memory stays the one on disk and no change of another writer is lost.
+    pub fn transaction_and_save<F, E>(&self, update: F) -> Result<(), E>
+        where F: FnOnce(&mut T) -> Result<(), E>, E: From<Error> {
+        let guard = SaveGuard::acquire(&self.inner.saving)?;
+        let mut current = self.lock_write()?;
+        let mut draft = current.clone();
+        update(&mut draft)?;
+        self.persist_config(Some(&draft), false, None, false)?;
+        let changes = self.inner.settings.changes(&current, &draft);
+        *current = draft;
+        drop(current);
+        drop(guard);
+        changes.notify();
+        Ok(())
+    }
+
+    /// Applies a transaction and returns the config it replaced.
+    fn commit<F, E>(&self, update: F) -> Result<T, E>
+        where F: FnOnce(&mut T) -> Result<(), E>, E: From<Error> {
+        let mut current = self.lock_write()?;
+        let mut draft = current.clone();
+        update(&mut draft)?;
+        self.inner.settings.validate(&draft)?;
+        let changes = self.inner.settings.changes(&current, &draft);
+        let previous = std::mem::replace(&mut *current, draft);
+        drop(current);
+        changes.notify();
+        Ok(previous)
+    }
+
+    /// Returns the path of the config file, which is where the config is saved to and reloaded from.
+    /// Returns `None` if the config has no file, for example because it was created with [`Config::in_memory`].
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// assert_eq!(config.path().as_deref(), Some(Path::new("testconfig.json")));
+    /// ```
+    pub fn path(&self) -> Option<PathBuf> {
+        self.inner.location().path
+    }
+
+    /// Changes the path of the config file, so future saves and reloads of the config and all its clones use the new file. The format is detected from the extension of the new path.
+    /// The config in memory is kept and counts as changed, so [`Config::save_if_changed`] writes it to the new file. A config loaded from a [`crate::Backend`] is detached from it. An existing file at the new path is overwritten by the next save, even with [`ConfigBuilder::detect_conflicts`].
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// let path = std::env::temp_dir().join("opzioni-set-path.yaml");
+    /// config.set_path(path.clone());
+    /// assert_eq!(config.path().as_deref(), Some(path.as_path()));
+    /// config.save().unwrap();
+    /// assert!(std::fs::read_to_string(&path).unwrap().contains("name: John"));
+    /// ```
+    pub fn set_path(&self, path: PathBuf) {
+        let mut location = self.inner.location.lock().unwrap();
+        *self.inner.disk.lock().unwrap() = self.inner.settings.disk_fingerprint(&path);
+        *self.inner.persisted.lock().unwrap() = None;
+        *location = Location { path: Some(path), remote: None };
+    }
+
+    /// Returns the time the config was loaded from disk, as reported by the configured [`crate::Clock`].
+    /// Every successful [`Config::reload`] which picks up a changed file updates the time.
+    /// Returns `None` if the config was not loaded from a file, for example because it was created with [`Config::new`] or the default was used after an error.
+    ///
+    /// # Example
+    /// ```
+    /// use std::sync::Arc;
+    /// use std::time::SystemTime;
+    /// use opzioni::{blocking::Config, ManualClock};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let clock = Arc::new(ManualClock::new(SystemTime::UNIX_EPOCH));
+    /// let config = Config::<MyConfig>::configure().clock(clock.clone()).load(Path::new("testconfig.json")).unwrap();
+    /// assert_eq!(config.loaded_at(), Some(SystemTime::UNIX_EPOCH));
+    /// ```
+    pub fn loaded_at(&self) -> Option<SystemTime> {
+        *self.inner.loaded_at.lock().unwrap()
+    }
+
+    /// Returns the time the config was last saved with [`Config::save`], as reported by the configured [`crate::Clock`].
+    /// Returns `None` if the config was never saved.
+    pub fn saved_at(&self) -> Option<SystemTime> {
+        *self.inner.saved_at.lock().unwrap()
+    }
+
+    /// Returns the config value at the given dotted path, like `server.port`, without knowing the config type at compile time.
+    /// Together with [`Config::set_value`] this allows generic tools, like a `config get` and `config set` command line, to work on any config.
+    /// If the path does not exist, [`Error::InvalidPath`] is returned. If the value does not fit `V`, an [`Error::ParseError`] is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Error};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// assert_eq!(config.get_value::<u8>("age").unwrap(), 42);
+    /// assert!(matches!(config.get_value::<u8>("height"), Err(Error::InvalidPath(_))));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn get_value<V>(&self, path: &str) -> Result<V, Error>
+        where V: serde::de::DeserializeOwned {
+        let root = serde_json::to_value(&*self.lock_read()?).map_err(Error::serialize)?;
+        Ok(V::deserialize(value::get_path(&root, path)?)?)
+    }
+
+    /// Sets the config value at the given dotted path, like `server.port`, without knowing the config type at compile time.
+    /// Missing or null intermediate maps are created, so `plugins.foo.enabled` can be set even if there is no `foo` plugin yet, as long as the resulting config can be deserialized into `T`.
+    /// The whole update happens under the write lock, so concurrent updates do not overwrite each other.
+    /// If the path does not lead through maps, [`Error::InvalidPath`] is returned. If the result does not fit `T`, an [`Error::ParseError`] is returned. In both cases the config is left unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// use std::collections::HashMap;
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+    /// struct Plugin {
+    ///   enabled: bool,
+    /// }
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+    /// struct MyConfig {
+    ///   plugins: HashMap<String, Plugin>,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::default();
+    /// config.set_value("plugins.foo.enabled", true).unwrap();
+    /// assert!(format!("{:?}", config).contains("enabled: true"));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn set_value<V>(&self, path: &str, value: V) -> Result<(), Error>
+        where V: serde::Serialize {
+        let value = serde_json::to_value(value).map_err(Error::serialize)?;
+        let mut config = self.lock_write()?;
+        let mut root = serde_json::to_value(&*config).map_err(Error::serialize)?;
+        value::set_path(&mut root, path, value)?;
+        let updated = serde_json::from_value(root)?;
+        let changes = self.inner.settings.changes(&config, &updated);
+        *config = updated;
+        drop(config);
+        changes.notify();
+        Ok(())
     }
-}
\ No newline at end of file
+
+    /// Returns a handle to a nested struct of the config, selected by a pair of accessors like `|config| &config.database` and `|config| &mut config.database`. See [`Section`].
+    pub fn section<S>(&self, get: impl Fn(&T) -> &S + Send + Sync + 'static, get_mut: impl Fn(&mut T) -> &mut S + Send + Sync + 'static) -> Section<T, S> {
+        Section { config: self.clone(), get: config::getter(get), get_mut: config::getter_mut(get_mut) }
+    }
+
+    /// Returns the JSON Schema of the config type, generated from its `schemars::JsonSchema` implementation, so editors can validate and complete config files.
+    /// See [`ConfigBuilder::write_schema`] for writing it to a file.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use schemars::JsonSchema;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, JsonSchema, Default, Clone)]
+    /// struct MyConfig {
+    ///   /// The name of the user.
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let schema = Config::<MyConfig>::json_schema();
+    /// assert_eq!(schema["properties"]["name"]["description"], "The name of the user.");
+    /// assert_eq!(schema["properties"]["age"]["type"], "integer");
+    /// ```
+    #[cfg(feature = "schemars")]
+    pub fn json_schema() -> serde_json::Value
+        where T: schemars::JsonSchema {
+        crate::schema::json_schema::<T>()
+    }
+
+    /// Returns the description of the config field at the given dotted path, for example to show help text for a setting.
+    /// The descriptions come from the [`crate::Describe`] implementation of `T`. See [`crate::Describe`] for an example.
+    pub fn describe(path: &str) -> Option<&'static str>
+        where T: Describe {
+        T::describe(path)
+    }
+
+    /// Writes `T::default()` to the given file as an example config, with the descriptions of [`crate::Describe`] as comments above the keys, so a commented `config.example.toml` does not have to be maintained by hand.
+    /// The format is chosen by the extension of the file. Comments are written for TOML and YAML, other formats get the default config without them.
+    /// With the `derive` feature, `#[derive(Opzioni)]` takes the descriptions from the doc comments of the fields. The file is overwritten if it exists.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use opzioni::Describe;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   port: u16,
+    /// }
+    ///
+    /// impl Default for MyConfig {
+    ///   fn default() -> Self {
+    ///     Self { name: "myapp".to_string(), port: 8080 }
+    ///   }
+    /// }
+    ///
+    /// impl Describe for MyConfig {
+    ///   fn describe(path: &str) -> Option<&'static str> {
+    ///     match path {
+    ///       "port" => Some("The port the server listens on"),
+    ///       _ => None,
+    ///     }
+    ///   }
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-example.toml");
+    /// Config::<MyConfig>::write_example(&path).unwrap();
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap(), "name = \"myapp\"\n# The port the server listens on\nport = 8080\n");
+    /// ```
+    pub fn write_example(path: impl AsRef<Path>) -> Result<(), Error>
+        where T: Describe {
+        crate::example::write::<T>(path.as_ref())
+    }
+
+    /// Configure returns a new [`ConfigBuilder`] to load a config file from disk. See [`ConfigBuilder::load`] for more information.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config: Config<MyConfig> = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// ```
+    pub fn configure() -> ConfigBuilder<T> {
+        ConfigBuilder {
+            _p: PhantomData,
+            use_default_on_error: false,
+            create_if_missing: false,
+            treat_empty_as_missing: false,
+            path_env: None,
+            settings: Settings::default(),
+        }
+    }
+
+    /// Saves the config to file. The file extension of the config file determines the format of the config file.
+    /// The currently supported formats are JSON, TOML and YAML.
+    /// The config file is overwritten in place, so it keeps its permissions and owner unless [`ConfigBuilder::file_mode`] sets other permissions.
+    /// If the config file could not be saved, an error is returned.
+    /// If the config file was loaded from disk, the config is saved to the same file.
+    /// If the config has no file, because it was created with [`Config::in_memory`], [`Error::NoBackingFile`] is returned.
+    /// If the config is already being saved, for example because a save is triggered again while the first one is still running, [`Error::ReentrantSave`] is returned.
+    /// If [`ConfigBuilder::detect_conflicts`] is set and the file was changed by someone else since it was loaded, [`Error::Conflict`] is returned.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///  name: String,
+    ///  age: u8,
+    /// }
+    ///
+    /// let config: Config<MyConfig> = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// config.get().write().unwrap().name = "John".to_string();
+    /// config.get().write().unwrap().age = 42;
+    /// config.save().unwrap();
+    /// ```
+    pub fn save(&self) -> Result<(), Error> {
+        self.persist(false, None, false).map(|_| ())
+    }
+
+    /// Saves the config like [`Config::save`], but skips the check for changes made to the file by someone else, overwriting them.
+    /// Only makes a difference if [`ConfigBuilder::detect_conflicts`] is set, see there for an example.
+    pub fn force_save(&self) -> Result<(), Error> {
+        self.persist(false, None, true).map(|_| ())
+    }
+
+    /// Writes the config in the given [`crate::Format`] to a writer instead of its file, for example to a network stream or an archive.
+    /// The config is validated first. The file of the config is neither written nor marked as saved.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Format};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// let mut buffer = Vec::new();
+    /// config.save_to_writer(&mut buffer, Format::Toml).unwrap();
+    /// assert_eq!(String::from_utf8(buffer).unwrap(), "name = \"John\"\nage = 42\n");
+    /// ```
+    pub fn save_to_writer(&self, mut writer: impl Write, format: Format) -> Result<(), Error> {
+        let cfg = self.lock_read()?;
+        self.inner.settings.validate(&cfg)?;
+        let data = format.serialize_with(&*cfg, &self.inner.settings.save)?;
+        drop(cfg);
+        let written = writer.write_all(&data).and_then(|_| writer.flush());
+        wipe(data);
+        Ok(written?)
+    }
+
+    /// Saves the config like [`Config::save`], but serializes it with the given [`crate::Format`] instead of the one matching the file extension.
+    /// This allows saving to files without or with an ambiguous extension.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Format};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///  name: String,
+    ///  age: u8,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::new(MyConfig::default(), std::env::temp_dir().join("opzioni-save-with"));
+    /// config.save_with(Format::Yaml).unwrap();
+    /// ```
+    pub fn save_with(&self, format: Format) -> Result<(), Error> {
+        self.persist(false, Some(format), false).map(|_| ())
+    }
+
+    /// Returns whether the config was changed since it was last loaded or saved.
+    /// The config counts as changed if its serialized form differs from the one written to or read from disk.
+    /// A config which was not loaded from disk, for example because the default was used after an error, is always dirty.
+    /// Like [`Config::save`] this method returns [`Error::NoBackingFile`] if the config has no file.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///  name: String,
+    ///  age: u8,
+    /// }
+    ///
+    /// let config: Config<MyConfig> = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// assert!(!config.is_dirty().unwrap());
+    /// config.get().write().unwrap().age += 1;
+    /// assert!(config.is_dirty().unwrap());
+    /// ```
+    pub fn is_dirty(&self) -> Result<bool, Error> {
+        let location = self.inner.location();
+        let loader = match (&location.path, &location.remote) {
+            (Some(path), _) => self.inner.settings.loader(path)?,
+            (None, Some(remote)) => self.inner.settings.open(remote.loader::<T>(None)),
+            (None, None) => return Err(Error::NoBackingFile),
+        };
+        let cfg = self.lock_read()?;
+        let data = self.inner.settings.serialize(&*loader, &cfg)?;
+        let hash = fingerprint(&data);
+        wipe(data);
+        Ok(*self.inner.persisted.lock().unwrap() != Some(hash) || self.inner.settings.secrets(&cfg)?.changed())
+    }
+
+    /// Compares the config in memory with its file and returns every value which was changed in memory, or in the file, since the config was loaded. See [`crate::diff`].
+    /// The file is read like [`Config::reload`] reads it, but the config is not changed. The changes lead from the file to the config in memory, so `old` is the value in the file.
+    /// Like [`Config::save`] this method returns [`Error::NoBackingFile`] if the config has no file.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// config.get().write().unwrap().age = 43;
+    /// let changes = config.diff_with_file().unwrap();
+    /// assert_eq!(changes[0].path, "age");
+    /// assert_eq!(changes[0].old, Some(42.into()));
+    /// assert_eq!(changes[0].new, Some(43.into()));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn diff_with_file(&self) -> Result<Vec<crate::Change>, Error> {
+        let location = self.inner.location();
+        let loader = match (&location.path, &location.remote) {
+            (Some(path), _) => self.inner.settings.loader(path)?,
+            (None, Some(remote)) => self.inner.settings.open(remote.loader::<T>(None)),
+            (None, None) => return Err(Error::NoBackingFile),
+        };
+        let (disk, _) = self.inner.settings.read(loader.as_ref())?;
+        crate::diff(&disk, &*self.lock_read()?)
+    }
+
+    /// Locks the config file against other processes until the returned [`crate::FileLock`] is dropped, blocking until the lock is available.
+    /// Loading and saving always lock the file for their own duration. This method extends the lock over a whole read-modify-write cycle, so changes of other instances of the application are not lost.
+    /// The lock is advisory, so it only coordinates processes using opzioni with the `file-lock` feature. It is kept in a separate file next to the config file, like `config.toml.lock`.
+    /// Returns [`Error::AlreadyLocked`] if this config already holds the lock and [`Error::NoBackingFile`] if the config has no file.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///  name: String,
+    ///  age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-lock.json");
+    /// std::fs::write(&path, r#"{ "name": "John", "age": 42 }"#).unwrap();
+    /// let config = Config::<MyConfig>::configure().load(&path).unwrap();
+    /// let lock = config.lock_exclusive().unwrap();
+    /// config.reload().unwrap();
+    /// config.get().write().unwrap().age += 1;
+    /// config.save().unwrap();
+    /// drop(lock);
+    /// ```
+    #[cfg(feature = "file-lock")]
+    pub fn lock_exclusive(&self) -> Result<FileLock<'_>, Error> {
+        match &self.inner.location().path {
+            Some(path) => FileLock::acquire(path, &self.inner.file_locked),
+            None => Err(Error::NoBackingFile),
+        }
+    }
+
+    /// Saves the config like [`Config::save`], but only if it was changed since it was last loaded or saved (see [`Config::is_dirty`]).
+    /// Returns whether the config was written to disk.
+    pub fn save_if_changed(&self) -> Result<bool, Error> {
+        self.persist(true, None, false)
+    }
+
+    /// Reads the config file again, applying the same migrations, checks and validation as the builder which loaded the config.
+    /// If the file content differs from what was last loaded or saved, the in-memory config is replaced and `true` is returned.
+    /// If the file did not change, the in-memory config is kept as is, including changes which were not saved yet, and `false` is returned.
+    /// If reading the file fails, the error is returned and the config is left unchanged. An empty file always fails with [`Error::EmptyFile`], because it is usually being written at the moment.
+    /// The file is read and parsed before the write lock is taken, which is only held to swap in the new config, so readers are blocked as briefly as possible.
+    /// Like [`Config::save`] this method returns [`Error::NoBackingFile`] if the config has no file.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///  name: String,
+    ///  age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-reload.json");
+    /// std::fs::write(&path, r#"{ "name": "John", "age": 42 }"#).unwrap();
+    /// let config = Config::<MyConfig>::configure().load(&path).unwrap();
+    /// assert!(!config.reload().unwrap());
+    ///
+    /// std::fs::write(&path, r#"{ "name": "John", "age": 43 }"#).unwrap();
+    /// assert!(config.reload().unwrap());
+    /// assert_eq!(config.get().read().unwrap().age, 43);
+    /// ```
+    pub fn reload(&self) -> Result<bool, Error> {
+        let location = self.inner.location();
+        let (config, persisted) = match (&location.path, &location.remote) {
+            (Some(path), _) => self.reload_file(path).map_err(|err| err.at(path, self.inner.settings.format_of(path)))?,
+            (None, Some(remote)) => {
+                let (config, persisted, stored) = remote.parse(&self.inner.settings, remote.read()?)?;
+                *self.inner.disk.lock().unwrap() = stored;
+                (config, persisted)
+            }
+            (None, None) => return Err(Error::NoBackingFile),
+        };
+        // A reloaded config replaces a poisoned one as a whole, so the poisoning does not matter anymore.
+        let mut current = self.inner.config.write().unwrap_or_else(PoisonError::into_inner);
+        let mut last = self.inner.persisted.lock().unwrap();
+        if persisted.is_some() && *last == persisted && !self.needs_recovery() {
+            return Ok(false);
+        }
+        #[cfg(feature = "tracing")]
+        debug!(file = ?location.path, backend = ?location.remote.as_ref().map(|remote| &remote.backend), "reloaded changed config");
+        if self.needs_recovery() {
+            self.inner.recovered.store(true, Ordering::Release);
+        }
+        self.keep_good(self.inner.settings.known_good(&config));
+        let changes = self.inner.settings.changes(&current, &config);
+        *current = config;
+        *last = persisted;
+        *self.inner.loaded_at.lock().unwrap() = Some(self.inner.settings.clock.now());
+        drop(last);
+        drop(current);
+        changes.notify();
+        Ok(true)
+    }
+
+    /// Reads the config file again for [`Config::reload`]. The errors do not carry the path and the format yet.
+    fn reload_file(&self, path: &Path) -> Result<(T, Option<u64>), Error> {
+        if is_empty_file(path) {
+            return Err(Error::EmptyFile { path: None, format: None });
+        }
+        let loader = self.inner.settings.loader(path)?;
+        #[cfg(feature = "file-lock")]
+        let _lock = file_lock::acquire_unless_held(path, false, &self.inner.file_locked)?;
+        let read = self.inner.settings.read(loader.as_ref())?;
+        *self.inner.disk.lock().unwrap() = self.inner.settings.disk_fingerprint(path);
+        Ok(read)
+    }
+
+    fn persist(&self, only_if_changed: bool, format: Option<Format>, force: bool) -> Result<bool, Error> {
+        let _guard = SaveGuard::acquire(&self.inner.saving)?;
+        self.persist_config(None, only_if_changed, format, force)
+    }
+
+    /// Saves the given config, or the current one if there is none, to the file or backend of the config.
+    fn persist_config(&self, config: Option<&T>, only_if_changed: bool, format: Option<Format>, force: bool) -> Result<bool, Error> {
+        let location = self.inner.location();
+        match &location.path {
+            Some(path) => self.persist_file(path, config, only_if_changed, format, force).map_err(|err| err.at(path, format.or_else(|| self.inner.settings.format_of(path)))),
+            None => match &location.remote {
+                Some(remote) => self.persist_remote(remote, config, only_if_changed, format, force),
+                None => Err(Error::NoBackingFile),
+            },
+        }
+    }
+
+    /// Saves the config to its file. The errors do not carry the path and the format yet.
+    fn persist_file(&self, path: &Path, config: Option<&T>, only_if_changed: bool, format: Option<Format>, force: bool) -> Result<bool, Error> {
+        let loader = match format {
+            Some(format) => self.inner.settings.loader_for(format, path),
+            None => self.inner.settings.loader(path)?,
+        };
+        let current = match config {
+            Some(_) => None,
+            None => Some(self.lock_read()?),
+        };
+        let cfg = config.or(current.as_deref()).unwrap();
+        self.inner.settings.validate(cfg)?;
+        let data = self.inner.settings.serialize(&*loader, cfg)?;
+        let secrets = self.inner.settings.secrets(cfg)?;
+        #[cfg(feature = "tracing")]
+        let logged = self.inner.settings.redacted(&*loader, cfg);
+        let good = self.inner.settings.known_good(cfg);
+        // `save_with` may write another format than the one of the file, while `is_dirty` compares with what `save` writes.
+        let saved = match format {
+            Some(format) if self.inner.settings.format_of(path) != Some(format) => self.inner.settings.loader(path).and_then(|own| self.inner.settings.fingerprint(&*own, cfg)).ok(),
+            _ => None,
+        };
+        drop(current);
+        let hash = fingerprint(&data);
+        if only_if_changed && *self.inner.persisted.lock().unwrap() == Some(hash) && !secrets.changed() {
+            wipe(data);
+            return Ok(false);
+        }
+        #[cfg(feature = "file-lock")]
+        let _lock = file_lock::acquire_unless_held(path, true, &self.inner.file_locked)?;
+        if self.inner.settings.detect_conflicts && !force && self.inner.settings.disk_fingerprint(path) != *self.inner.disk.lock().unwrap() {
+            wipe(data);
+            return Err(Error::Conflict { path: None, format: None });
+        }
+        let written = secrets.store().and_then(|_| rotate_backups(path, self.inner.settings.backups)).and_then(|_| loader.write(&data));
+        wipe(data);
+        written?;
+        #[cfg(feature = "tracing")]
+        debug!(file = ?path, config = logged, "saved config");
+        *self.inner.disk.lock().unwrap() = self.inner.settings.disk_fingerprint(path);
+        *self.inner.persisted.lock().unwrap() = Some(saved.unwrap_or(hash));
+        *self.inner.saved_at.lock().unwrap() = Some(self.inner.settings.clock.now());
+        self.keep_good(good);
+        Ok(true)
+    }
+
+    /// Saves the config to the backend it was loaded from.
+    fn persist_remote(&self, remote: &Remote, config: Option<&T>, only_if_changed: bool, format: Option<Format>, force: bool) -> Result<bool, Error> {
+        let loader = self.inner.settings.open(remote.loader::<T>(format));
+        let current = match config {
+            Some(_) => None,
+            None => Some(self.lock_read()?),
+        };
+        let cfg = config.or(current.as_deref()).unwrap();
+        self.inner.settings.validate(cfg)?;
+        let data = self.inner.settings.serialize(&*loader, cfg)?;
+        let secrets = self.inner.settings.secrets(cfg)?;
+        #[cfg(feature = "tracing")]
+        let logged = self.inner.settings.redacted(&*loader, cfg);
+        let good = self.inner.settings.known_good(cfg);
+        // `save_with` may write another format than the one of the file, while `is_dirty` compares with what `save` writes.
+        let saved = match format {
+            Some(format) if format != remote.format => self.inner.settings.fingerprint(&*self.inner.settings.open(remote.loader::<T>(None)), cfg).ok(),
+            _ => None,
+        };
+        drop(current);
+        let hash = fingerprint(&data);
+        if only_if_changed && *self.inner.persisted.lock().unwrap() == Some(hash) && !secrets.changed() {
+            wipe(data);
+            return Ok(false);
+        }
+        if self.inner.settings.detect_conflicts && !force && remote.fingerprint() != *self.inner.disk.lock().unwrap() {
+            wipe(data);
+            return Err(Error::Conflict { path: None, format: None });
+        }
+        let written = secrets.store().and_then(|_| loader.write(&data));
+        wipe(data);
+        written?;
+        #[cfg(feature = "tracing")]
+        debug!(backend = ?remote.backend, config = logged, "saved config");
+        *self.inner.disk.lock().unwrap() = self.inner.settings.detect_conflicts.then_some(hash);
+        *self.inner.persisted.lock().unwrap() = Some(saved.unwrap_or(hash));
+        *self.inner.saved_at.lock().unwrap() = Some(self.inner.settings.clock.now());
+        self.keep_good(good);
+        Ok(true)
+    }
+}
+
+impl<T> Clone for Config<T>
+    where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    /// Returns a handle to the same config, see [`Config`].
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone() }
+    }
+}
+
+impl<T> std::fmt::Debug for Config<T>
+    where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone + std::fmt::Debug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let location = self.inner.location();
+        f.debug_struct("Config")
+            .field("config", &self.inner.config)
+            .field("path", &location.path)
+            .field("settings", &self.inner.settings)
+            .field("loaded_at", &self.inner.loaded_at)
+            .field("saved_at", &self.inner.saved_at)
+            .field("remote", &location.remote)
+            .finish()
+    }
+}
+
+impl<T> Default for Config<T>
+    where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    fn default() -> Self {
+        Self::from_parts(T::default(), None, Settings::default(), None, None)
+    }
+}
+
+/// The ConfigBuilder struct is used to load a config file from disk. See [`ConfigBuilder::load`] for more information.
+/// The builder methods take and return the builder by value, so a builder can be configured in one expression and stored or cloned to load several files with the same settings.
+///
+/// # Example
+/// ```
+/// use opzioni::blocking::{Config, ConfigBuilder};
+/// use serde::{Serialize, Deserialize};
+/// use std::path::Path;
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// let builder: ConfigBuilder<MyConfig> = Config::configure().use_default_on_error();
+/// let config = builder.load(Path::new("testconfig.json")).unwrap();
+/// let missing = builder.load(Path::new("missing.json")).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConfigBuilder<T> where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    _p: PhantomData<T>,
+    use_default_on_error: bool,
+    create_if_missing: bool,
+    treat_empty_as_missing: bool,
+    path_env: Option<String>,
+    settings: Settings<T>,
+}
+
+impl<T> ConfigBuilder<T> where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    fn handle_load_err(&self, err: Error, path: Option<&Path>) -> Result<LoadOutcome<T>, Error>
+    {
+        let Some(config) = self.recover(&err, path) else {
+            return Err(err);
+        };
+        #[cfg(feature = "tracing")]
+        trace!(
+            error = err.to_string(),
+            "using default config because of error"
+        );
+        Ok(LoadOutcome::Defaulted {
+            config: Config::from_parts(config, path.map(Path::to_path_buf), self.settings.clone(), None, None),
+            reason: err,
+        })
+    }
+
+    /// Returns the config to use instead of failing with the error, as decided by [`ConfigBuilder::on_error`] or [`ConfigBuilder::use_default_on_error`].
+    fn recover(&self, err: &Error, path: Option<&Path>) -> Option<T> {
+        let recovery = match &self.settings.on_error {
+            Some(on_error) => on_error(err, path),
+            None if self.use_default_on_error => Recovery::UseDefault,
+            None => Recovery::Fail,
+        };
+        match recovery {
+            Recovery::UseDefault => Some(self.settings.initial()),
+            Recovery::UseValue(config) => Some(config),
+            Recovery::Fail => None,
+        }
+    }
+
+    /// If this method is called, the config will use the default values of the given type `T` if an error occurs while loading the config file.
+    /// It causes the [`ConfigBuilder::load`] method to return a [`Config`] struct with the default values of the given type `T` instead of an error.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// impl Default for MyConfig {
+    ///   fn default() -> Self {
+    ///     Self {
+    ///       name: "John".to_string(),
+    ///       age: 42,
+    ///     }
+    ///   }
+    /// }
+    ///
+    /// let config: Config<MyConfig> = Config::<MyConfig>::configure().use_default_on_error().load(Path::new("testconfig.json")).unwrap();
+    /// ```
+    pub fn use_default_on_error(mut self) -> Self {
+        self.use_default_on_error = true;
+        self
+    }
+
+    /// Decides what happens when loading the config fails, instead of always failing or always using the default config like [`ConfigBuilder::use_default_on_error`].
+    /// The callback gets the error and the path of the config file, if the config is loaded from a file, so the application can log it, ask the user or restore a backup.
+    /// A config used because of [`Recovery::UseDefault`] or [`Recovery::UseValue`] is returned as [`LoadOutcome::Defaulted`] and is not validated. If the callback is set, [`ConfigBuilder::use_default_on_error`] has no effect.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Recovery};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let outcome = Config::<MyConfig>::configure()
+    ///     .on_error(|err, path| {
+    ///         eprintln!("could not load {:?}: {}", path, err);
+    ///         Recovery::UseValue(MyConfig { name: "backup".to_string(), age: 7 })
+    ///     })
+    ///     .load_with_outcome(Path::new("missing.json"))
+    ///     .unwrap();
+    /// assert!(outcome.is_defaulted());
+    /// assert_eq!(outcome.into_config().read().age, 7);
+    /// ```
+    pub fn on_error<F>(mut self, on_error: F) -> Self
+        where F: Fn(&Error, Option<&Path>) -> Recovery<T> + Send + Sync + 'static {
+        self.settings.on_error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// If this method is called and the config file does not exist, [`ConfigBuilder::load`] writes the default values of the given type `T`, or the value of [`ConfigBuilder::or_init_with`], to the file and returns them.
+    /// The format is chosen by the file extension as usual and missing parent directories are created.
+    /// This is the usual first run behavior of applications which keep their settings in a file.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-create-if-missing").join("config.toml");
+    /// let _ = std::fs::remove_file(&path);
+    /// let config = Config::<MyConfig>::configure().create_if_missing().load(&path).unwrap();
+    /// assert!(path.exists());
+    /// ```
+    pub fn create_if_missing(mut self) -> Self {
+        self.create_if_missing = true;
+        self
+    }
+
+    /// Initializes a missing config file with the value returned by `init` instead of the default values of `T`, for values which are only known at runtime, like a generated machine ID or the locale of the user.
+    /// It enables [`ConfigBuilder::create_if_missing`], so the value is written to the file and only computed on the first run.
+    /// The value is also used instead of the default values by [`ConfigBuilder::use_default_on_error`] and [`Recovery::UseDefault`].
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-or-init-with.toml");
+    /// let _ = std::fs::remove_file(&path);
+    /// let builder = Config::<MyConfig>::configure().or_init_with(|| MyConfig { name: format!("machine-{}", std::process::id()), age: 0 });
+    /// builder.load(&path).unwrap();
+    /// // The file exists now, so the value is not computed again.
+    /// let config = Config::<MyConfig>::configure().or_init_with(|| MyConfig { name: "other".to_string(), age: 0 }).load(&path).unwrap();
+    /// assert_eq!(config.get().read().unwrap().name, format!("machine-{}", std::process::id()));
+    /// ```
+    pub fn or_init_with<F>(mut self, init: F) -> Self
+        where F: Fn() -> T + Send + Sync + 'static {
+        self.settings.seed = Some(Arc::new(init));
+        self.create_if_missing = true;
+        self
+    }
+
+    /// Handles an empty config file like a missing one instead of failing with [`Error::EmptyFile`].
+    /// Together with [`ConfigBuilder::create_if_missing`] the default config is written to the empty file, otherwise loading fails like for a missing file and [`ConfigBuilder::use_default_on_error`] applies.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-empty.yaml");
+    /// std::fs::write(&path, "").unwrap();
+    /// let config = Config::<MyConfig>::configure().treat_empty_as_missing().create_if_missing().load(&path).unwrap();
+    /// assert!(!std::fs::read_to_string(&path).unwrap().is_empty());
+    /// ```
+    pub fn treat_empty_as_missing(mut self) -> Self {
+        self.treat_empty_as_missing = true;
+        self
+    }
+
+    /// Reads the path of the config file from the given environment variable, so deployments can point the application at another file without code changes.
+    /// If the variable is set and not empty, [`ConfigBuilder::load`] loads the file it names instead of the path passed to it, which becomes the fallback.
+    /// The format is detected from the extension of the actual file as usual.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// std::env::set_var("MYAPP_CONFIG", "testconfig.json");
+    /// let config = Config::<MyConfig>::configure().path_from_env("MYAPP_CONFIG").load(Path::new("/etc/myapp/config.toml")).unwrap();
+    /// assert_eq!(config.get().read().unwrap().age, 42);
+    /// ```
+    pub fn path_from_env(mut self, var: &str) -> Self {
+        self.path_env = Some(var.to_string());
+        self
+    }
+
+    /// Keeps up to `count` copies of the config file before it is overwritten by a save, as a safety net for users who edit the file by hand.
+    /// Before every write the existing backups are moved one slot up, for example `config.toml.bak.1` to `config.toml.bak.2`, the oldest one beyond `count` is dropped and the current file is copied to `config.toml.bak.1`.
+    /// If rotating the backups fails, the save fails without touching the config file.
+    /// The copies keep the permissions of the config file, but belong to the user running the program.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-backups.json");
+    /// std::fs::write(&path, r#"{ "name": "John", "age": 42 }"#).unwrap();
+    /// let config = Config::<MyConfig>::configure().keep_backups(3).load(&path).unwrap();
+    /// config.save().unwrap();
+    /// assert!(std::env::temp_dir().join("opzioni-backups.json.bak.1").exists());
+    /// ```
+    pub fn keep_backups(mut self, count: usize) -> Self {
+        self.settings.backups = count;
+        self
+    }
+
+    /// Remembers the content of the config file when it is loaded, reloaded or saved, and lets saves fail with [`Error::Conflict`] if the file was changed by someone else since.
+    /// This prevents overwriting changes a user made to the file while the application was running. Use [`Config::reload`] to pick up the changes or [`Config::force_save`] to overwrite them.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Error};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-conflict.json");
+    /// std::fs::write(&path, r#"{ "name": "John", "age": 42 }"#).unwrap();
+    /// let config = Config::<MyConfig>::configure().detect_conflicts().load(&path).unwrap();
+    /// std::fs::write(&path, r#"{ "name": "Jane", "age": 42 }"#).unwrap();
+    /// assert!(matches!(config.save(), Err(Error::Conflict { .. })));
+    /// config.force_save().unwrap();
+    /// ```
+    pub fn detect_conflicts(mut self) -> Self {
+        self.settings.detect_conflicts = true;
+        self
+    }
+
+    /// Uses the given format for the config file instead of detecting it from the file extension, for files without or with an unusual extension.
+    /// The format is kept by the loaded config for saving and reloading. Formats without a file extension, like `Format::Bincode`, can only be used this way.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Format};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-format.conf");
+    /// std::fs::write(&path, "name: John\nage: 42\n").unwrap();
+    /// let config = Config::<MyConfig>::configure().format(Format::Yaml).load(&path).unwrap();
+    /// assert_eq!(config.get().read().unwrap().age, 42);
+    /// config.save().unwrap();
+    /// ```
+    pub fn format(mut self, format: Format) -> Self {
+        self.settings.format = Some(format);
+        self
+    }
+
+    /// Sets the permissions of the config file, like `0o600` to keep a file with credentials from being readable by other users.
+    /// The file is created with the permissions, so its content is never readable by others, and an existing file gets them on every save.
+    /// Files of sections mapped with [`ConfigBuilder::map_section`] get the same permissions. Backups keep the permissions of the file they were copied from.
+    /// Only has an effect on unix, on Windows files keep the access control list they inherit from their directory.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   token: String,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-file-mode.json");
+    /// # std::fs::remove_file(&path).ok();
+    /// let config = Config::<MyConfig>::configure().file_mode(0o600).create_if_missing().load(&path).unwrap();
+    /// config.save().unwrap();
+    /// #[cfg(unix)]
+    /// {
+    ///     use std::os::unix::fs::PermissionsExt;
+    ///     assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    /// }
+    /// ```
+    pub fn file_mode(mut self, mode: u32) -> Self {
+        self.settings.mode = Some(mode);
+        self
+    }
+
+    /// Sets how the config is laid out when it is saved, for example compact JSON for configs which are only read by programs, or the indentation of pretty printed JSON.
+    /// Applies to every save and to [`Config::save_to_writer`]. See [`SaveOptions`] for the formats each option applies to.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, SaveOptions};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-save-options.json");
+    /// std::fs::write(&path, r#"{ "name": "John", "age": 42 }"#).unwrap();
+    /// let config = Config::<MyConfig>::configure().save_options(SaveOptions::new().compact().trailing_newline(true)).load(&path).unwrap();
+    /// config.save().unwrap();
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"name\":\"John\",\"age\":42}\n");
+    /// ```
+    pub fn save_options(mut self, options: SaveOptions) -> Self {
+        self.settings.save = options;
+        self
+    }
+
+    /// Sets the [`crate::Clock`] used for every timestamp recorded by the loaded config. Defaults to [`crate::SystemClock`].
+    /// See [`Config::loaded_at`] for an example.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.settings.clock = Arc::new(clock);
+        self
+    }
+
+    /// Validates the config after it was loaded and before every save.
+    /// If the validator returns an error, loading respectively saving fails with [`Error::ValidationError`] containing the message.
+    /// A config which fails validation while loading is treated like any other load error, so [`ConfigBuilder::use_default_on_error`] applies.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Error};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::configure()
+    ///     .validate(|config: &MyConfig| if config.age > 150 { Err(format!("{} is too old", config.age)) } else { Ok(()) })
+    ///     .load(Path::new("testconfig.json"))
+    ///     .unwrap();
+    /// config.get().write().unwrap().age = 200;
+    /// assert!(matches!(config.save(), Err(Error::ValidationError { .. })));
+    /// ```
+    pub fn validate<F>(mut self, validator: F) -> Self
+        where F: Fn(&T) -> Result<(), String> + Send + Sync + 'static {
+        self.settings.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Calls `on_change` with the values which changed, see [`crate::diff`], whenever the config is changed by [`Config::reload`], [`Config::replace`], [`Config::swap`], [`Config::transaction`] or [`Config::set_value`].
+    /// Services can use the paths of the changes to reconfigure only the parts which are affected. Changes made through the write lock are not reported, because the config cannot tell when they are complete.
+    /// The callback runs on the thread which made the change, after the lock of the config was released, so it can read the config.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let changed = Arc::new(Mutex::new(Vec::new()));
+    /// let seen = changed.clone();
+    /// let config = Config::<MyConfig>::configure()
+    ///     .on_change(move |changes| seen.lock().unwrap().extend(changes.iter().map(|change| change.path.clone())))
+    ///     .load(Path::new("testconfig.json"))
+    ///     .unwrap();
+    /// config.set_value("age", 43).unwrap();
+    /// assert_eq!(*changed.lock().unwrap(), vec!["age"]);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn on_change<F>(mut self, on_change: F) -> Self
+        where F: Fn(&[crate::Change]) + Send + Sync + 'static {
+        self.settings.on_change = Some(Arc::new(on_change));
+        self
+    }
+
+    /// Sets what the config does when its lock was poisoned by a thread which panicked while holding the write lock. Defaults to [`crate::PoisonPolicy::Ignore`].
+    /// Without a policy one panic would make every later access to the config panic as well. See [`crate::PoisonPolicy`] for the options.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, PoisonPolicy};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::configure().on_poison(PoisonPolicy::Restore).load(Path::new("testconfig.json")).unwrap();
+    /// let handle = config.clone();
+    /// let _ = std::thread::spawn(move || {
+    ///     let mut config = handle.write();
+    ///     config.age = 0;
+    ///     panic!("half changed");
+    /// }).join();
+    /// assert_eq!(config.read().age, 42);
+    /// ```
+    pub fn on_poison(mut self, policy: PoisonPolicy) -> Self {
+        self.settings.poison = policy;
+        self
+    }
+
+    /// Applies the behavior declared with attributes on the fields of `T`, usually with `#[derive(Opzioni)]`, see [`crate::Opzioni`].
+    /// After loading, fields are overridden with their environment variables. Values from the environment do not count as changes. With the `json` feature, saving keeps the values of the file in place of the ones which were not changed in memory, so they are not written to the file.
+    /// The ranges of the fields are checked after loading and before every save, failing with [`Error::ValidationError`] like [`ConfigBuilder::validate`].
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Describe, Opzioni};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// // This is what #[derive(Opzioni)] generates for #[opzioni(env = "MYAPP_AGE", max = 150)] on age.
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// impl Describe for MyConfig {
+    ///   fn describe(path: &str) -> Option<&'static str> {
+    ///     None
+    ///   }
+    /// }
+    ///
+    /// impl Opzioni for MyConfig {
+    ///   fn apply_env(&mut self) -> Result<(), String> {
+    ///     if let Ok(age) = std::env::var("MYAPP_AGE") {
+    ///       self.age = age.parse().map_err(|_| "MYAPP_AGE is not a valid age".to_string())?;
+    ///     }
+    ///     Ok(())
+    ///   }
+    ///
+    ///   fn check(&self) -> Result<(), String> {
+    ///     if self.age > 150 { Err("age must be at most 150".to_string()) } else { Ok(()) }
+    ///   }
+    /// }
+    ///
+    /// std::env::set_var("MYAPP_AGE", "43");
+    /// let config = Config::<MyConfig>::configure().use_attributes().load(Path::new("testconfig.json")).unwrap();
+    /// assert_eq!(config.get().read().unwrap().age, 43);
+    /// ```
+    pub fn use_attributes(mut self) -> Self
+        where T: Opzioni {
+        self.settings.env = Some(T::apply_env);
+        self.settings.check = Some(T::check);
+        #[cfg(all(feature = "tracing", feature = "json"))]
+        {
+            self.settings.redact = Some(T::secrets);
+        }
+        self
+    }
+
+    /// Overrides every field with the environment variable named after it, like `MYAPP_SERVER_PORT` for `server.port` with the prefix `MYAPP`.
+    /// The names are built from the keys as serde writes them, so a field renamed by `#[serde(rename)]` or `#[serde(rename_all)]` to `max-connections` is set by `MYAPP_MAX_CONNECTIONS`.
+    /// Every character of a key which is not a letter or a digit becomes an underscore and the names are uppercase. An empty prefix uses the bare names. Use [`ConfigBuilder::env_options`] for other separators, cases and prefixes per section.
+    /// The values are parsed into the type of the field and lists take comma separated values. Fields which serde skips when serializing, like `None` with `skip_serializing_if`, have no variable.
+    /// Variables declared with attributes, see [`ConfigBuilder::use_attributes`], take precedence over these. Like them, the values do not count as changes and are not written to the file when it is saved, unless they were changed in memory. This keeps secrets read from files with [`EnvOptions::file_suffix`] out of the config file.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Format};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// #[serde(rename_all = "kebab-case")]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   max_connections: u32,
+    /// }
+    ///
+    /// std::env::set_var("MYAPP_MAX_CONNECTIONS", "64");
+    /// let data = r#"{ "name": "John", "max-connections": 8 }"#;
+    /// let config = Config::<MyConfig>::configure().env_prefix("MYAPP").load_from_str(data, Format::Json).unwrap();
+    /// assert_eq!(config.get().read().unwrap().max_connections, 64);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn env_prefix(self, prefix: &str) -> Self {
+        self.env_options(EnvOptions::new().prefix(prefix))
+    }
+
+    /// Overrides every field with the environment variable named after it like [`ConfigBuilder::env_prefix`], with the names built as set in the [`EnvOptions`].
+    /// Deployments name their variables differently, like `MYAPP__SERVER__PORT` in Docker and Kubernetes, which keeps the keys apart from the underscores within them. See [`EnvOptions`] for an example.
+    /// With [`EnvOptions::file_suffix`] fields are also read from the files named by variables like `MYAPP_DB_PASSWORD_FILE`, the way Docker and Kubernetes pass secrets.
+    #[cfg(feature = "json")]
+    pub fn env_options(mut self, options: EnvOptions) -> Self {
+        self.settings.env_options = Some(options);
+        self
+    }
+
+    /// Upgrades old config files with the given [`crate::Migrations`] before deserializing them. See [`crate::Migrations`] for an example.
+    #[cfg(feature = "json")]
+    pub fn with_migrations(mut self, migrations: Migrations<T>) -> Self {
+        self.settings.migrations = Some(migrations);
+        self
+    }
+
+    /// Rejects config files containing keys which `T` does not know, for example because of a typo.
+    /// The file is compared against the serialized form of `T::default()` and loading fails with [`Error::UnknownFields`] listing the dotted paths of all unknown keys.
+    /// Maps which are empty in the default config (like an empty `HashMap`) accept any key.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Error};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    /// }
+    ///
+    /// // testconfig.json also contains "age"
+    /// let result = Config::<MyConfig>::configure().deny_unknown_fields().load(Path::new("testconfig.json"));
+    /// assert!(matches!(result, Err(Error::UnknownFields { keys, .. }) if keys == vec!["age".to_string()]));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn deny_unknown_fields(mut self) -> Self {
+        self.settings.deny_unknown_fields = true;
+        self
+    }
+
+    /// Completes config files which lack some fields of `T` with the values from `T::default()`, instead of failing with an [`Error::ParseError`].
+    /// The file is merged key by key over the serialized default config, so fields added in an update of the application get their default values while everything set in the file is kept.
+    /// A completed config counts as dirty (see [`Config::is_dirty`]), so the next save writes the missing fields back to the file.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    ///   theme: String,
+    /// }
+    ///
+    /// impl Default for MyConfig {
+    ///   fn default() -> Self {
+    ///     Self { name: String::new(), age: 0, theme: "dark".to_string() }
+    ///   }
+    /// }
+    ///
+    /// // testconfig.json has no theme
+    /// let config = Config::<MyConfig>::configure().merge_defaults().load(Path::new("testconfig.json")).unwrap();
+    /// assert_eq!(config.get().read().unwrap().theme, "dark");
+    /// assert!(config.is_dirty().unwrap());
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn merge_defaults(mut self) -> Self {
+        self.settings.merge_defaults = true;
+        self
+    }
+
+    /// Sets how values are merged over each other: the including file over the included ones with [`ConfigBuilder::allow_includes`], a profile over the `default` section with [`ConfigBuilder::profile`] and the config file over the default config with [`ConfigBuilder::merge_defaults`].
+    /// Defaults to [`MergeStrategy::Merge`], which merges maps key by key and replaces arrays.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, MergeStrategy};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+    /// struct Plugin {
+    ///   name: String,
+    ///   enabled: bool,
+    /// }
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   plugins: Vec<Plugin>,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-merge-strategy.yaml");
+    /// std::fs::write(&path, "default:\n  plugins:\n    - { name: lint, enabled: true }\n    - { name: fmt, enabled: true }\nproduction:\n  plugins:\n    - { name: fmt, enabled: false }\n").unwrap();
+    /// let config = Config::<MyConfig>::configure()
+    ///     .profile("production")
+    ///     .merge_strategy(MergeStrategy::MergeByKey("name".to_string()))
+    ///     .load(&path)
+    ///     .unwrap();
+    /// assert_eq!(config.get().read().unwrap().plugins, vec![
+    ///     Plugin { name: "lint".to_string(), enabled: true },
+    ///     Plugin { name: "fmt".to_string(), enabled: false },
+    /// ]);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn merge_strategy(mut self, strategy: MergeStrategy) -> Self {
+        self.settings.merge = strategy;
+        self
+    }
+
+    /// Replaces `${NAME}` placeholders in the string values of the config file with the values of the environment variables, like `${HOME}/data`.
+    /// `unset` decides what happens to placeholders of variables which are not set. Write `$${` for a literal `${`.
+    /// The expansion happens after migrations and before the config is deserialized. Saving writes the placeholders back in place of the expanded values which were not changed in memory, so the file keeps them and values like passwords in the environment are not written to it.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, UnsetVariable};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   data_dir: String,
+    ///   cache_dir: String,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-expand-env.json");
+    /// std::fs::write(&path, r#"{ "data_dir": "${HOME}/data", "cache_dir": "${OPZIONI_UNSET}/cache" }"#).unwrap();
+    /// std::env::set_var("HOME", "/home/john");
+    /// let config = Config::<MyConfig>::configure().expand_env(UnsetVariable::Empty).load(&path).unwrap();
+    /// assert_eq!(config.get().read().unwrap().data_dir, "/home/john/data");
+    /// assert_eq!(config.get().read().unwrap().cache_dir, "/cache");
+    ///
+    /// config.get().write().unwrap().cache_dir = "/var/cache".to_string();
+    /// config.save().unwrap();
+    /// let saved = std::fs::read_to_string(&path).unwrap();
+    /// assert!(saved.contains("${HOME}/data"));
+    /// assert!(saved.contains("/var/cache"));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn expand_env(mut self, unset: UnsetVariable) -> Self {
+        self.settings.expand_env = Some(unset);
+        self
+    }
+
+    /// Selects a profile of a config file which contains one section per profile, like `[default]`, `[development]` and `[production]`.
+    /// The section of the profile is deep merged over the `default` section, see [`ConfigBuilder::merge_strategy`], so profiles only need to contain what differs. One of the two sections must exist.
+    /// Saving writes the config into the section of the profile, keeping only the values which differ from the `default` section, and leaves the other sections untouched.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   port: u16,
+    ///   workers: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-profiles.toml");
+    /// std::fs::write(&path, "[default]\nport = 8080\nworkers = 2\n\n[production]\nworkers = 16\n").unwrap();
+    /// let config = Config::<MyConfig>::configure().profile("production").load(&path).unwrap();
+    /// assert_eq!(config.get().read().unwrap().port, 8080);
+    /// assert_eq!(config.get().read().unwrap().workers, 16);
+    ///
+    /// config.get().write().unwrap().workers = 32;
+    /// config.save().unwrap();
+    /// assert!(std::fs::read_to_string(&path).unwrap().contains("[default]"));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn profile(mut self, profile: &str) -> Self {
+        self.settings.profile = Some(profile.to_string());
+        self
+    }
+
+    /// Composes the config from several files. A `$include` key in any map of the config file names another file, or a list of files, whose content is merged into that map.
+    /// Paths are relative to the file containing the directive and included files may include further files. Every format can include every other format.
+    /// Keys of the including map override the included ones, and later files in a list override earlier ones. Include cycles fail with [`Error::ConfigLoadError`].
+    /// The includes are resolved before anything else happens to the file. Saving keeps the directives in the main file and writes only the values which differ from the included ones, so the included files are never written.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct Database {
+    ///   url: String,
+    ///   pool: u8,
+    /// }
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   database: Database,
+    /// }
+    ///
+    /// let dir = std::env::temp_dir().join("opzioni-includes");
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("database.toml"), "url = \"postgres://localhost\"\npool = 4\n").unwrap();
+    /// std::fs::write(dir.join("config.json"), r#"{ "name": "app", "database": { "$include": "database.toml", "pool": 8 } }"#).unwrap();
+    /// let config = Config::<MyConfig>::configure().allow_includes().load(&dir.join("config.json")).unwrap();
+    /// assert_eq!(config.get().read().unwrap().database.url, "postgres://localhost");
+    /// assert_eq!(config.get().read().unwrap().database.pool, 8);
+    ///
+    /// config.get().write().unwrap().name = "server".to_string();
+    /// config.save().unwrap();
+    /// let saved = std::fs::read_to_string(dir.join("config.json")).unwrap();
+    /// assert!(saved.contains("database.toml"));
+    /// assert!(!saved.contains("postgres://localhost"));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn allow_includes(mut self) -> Self {
+        self.settings.includes = true;
+        self
+    }
+
+    /// Keeps the top-level section `key` of the config in its own file, for example secrets which must not be committed or need stricter permissions than the rest of the config.
+    /// The path of the file is relative to the directory of the config file and its format is chosen by its extension. It can be called once per section.
+    /// Loading puts the content of the file into the section, replacing the section of the config file if it has one. A missing file leaves the section to the config file.
+    /// Saving writes the section to its file and the rest of the config to the config file. Backups and conflict detection only cover the config file.
+    /// A section file created by a save gets the permissions of the config file.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct Secrets {
+    ///   token: String,
+    /// }
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   secrets: Secrets,
+    /// }
+    ///
+    /// let dir = std::env::temp_dir().join("opzioni-map-section");
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("config.toml"), "name = \"John\"\n").unwrap();
+    /// std::fs::write(dir.join("secrets.toml"), "token = \"hunter2\"\n").unwrap();
+    /// let config = Config::<MyConfig>::configure().map_section("secrets", "secrets.toml").load(&dir.join("config.toml")).unwrap();
+    /// assert_eq!(config.read().secrets.token, "hunter2");
+    ///
+    /// config.write().secrets.token = "correct horse".to_string();
+    /// config.save().unwrap();
+    /// assert!(std::fs::read_to_string(dir.join("secrets.toml")).unwrap().contains("correct horse"));
+    /// assert!(!std::fs::read_to_string(dir.join("config.toml")).unwrap().contains("token"));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn map_section(mut self, key: &str, path: impl AsRef<Path>) -> Self {
+        self.settings.sections.push((key.to_string(), path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Keeps every [`crate::Secret`] of the config in the credential store of the operating system, like the macOS Keychain, the Windows Credential Manager or the Secret Service on Linux, instead of the config file.
+    /// The file only holds a placeholder for each secret. The secrets are stored under the given service name, with the dotted path of the field, like `database.password`, as the user name.
+    /// Saving writes the secrets which changed to the credential store before writing the file. Loading a file which still holds a secret in plain text works and makes the config dirty, so the next save moves the secret to the credential store.
+    /// Loading fails with [`Error::ConfigLoadError`] if a placeholder has no secret in the credential store.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use opzioni::{blocking::Config, Secret};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   user: String,
+    ///   password: Secret<String>,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::configure().use_keyring("myapp").load(Path::new("myapp.toml")).unwrap();
+    /// // myapp.toml contains `password = "<stored in keyring>"`
+    /// ```
+    #[cfg(feature = "keyring")]
+    pub fn use_keyring(mut self, service: &str) -> Self {
+        self.settings.keyring = Some(Keyring::new(service));
+        self
+    }
+
+    /// Replaces references like `vault:secret/myapp/db#password` in the string values of the config file with the secrets of the given HashiCorp Vault server while loading, see [`crate::Vault`].
+    /// The secrets are read with every load and reload, after the environment variables are expanded, so a reference may contain placeholders like `vault:secret/${APP_ENV}/db#password`.
+    /// Saving writes the references back in place of the secrets which were not changed, so the secrets are never written to the file. Secrets which were changed are saved as they are, because they are not written to Vault.
+    /// References are resolved in maps, but not in lists.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use opzioni::{blocking::Config, Vault};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   db_password: String,
+    /// }
+    ///
+    /// // myapp.json contains `"db_password": "vault:secret/myapp/db#password"`
+    /// let config = Config::<MyConfig>::configure().with_vault(Vault::from_env().unwrap()).load(Path::new("myapp.json")).unwrap();
+    /// ```
+    #[cfg(feature = "vault")]
+    pub fn with_vault(mut self, vault: Vault) -> Self {
+        self.settings.vault = Some(vault);
+        self
+    }
+
+    /// Encrypts the config file with AES-256-GCM and the given 32 byte key, so tokens and other credentials in it are protected at rest.
+    /// Every save encrypts the config with a new random nonce. Encrypted files start with a header, and files without it fail to load with [`Error::IntegrityError`], because anyone who can write the file could have replaced it. Use [`ConfigBuilder::allow_plaintext_migration`] to encrypt existing files.
+    /// Loading fails with [`Error::ConfigLoadError`] if the key is wrong or the file was tampered with. The key is best taken from a secret store rather than the source code.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-encrypted.json");
+    /// let _ = std::fs::remove_file(&path);
+    /// let key = [7u8; 32];
+    /// let config = Config::<MyConfig>::configure().with_encryption_key(&key).create_if_missing().load(&path).unwrap();
+    /// config.save().unwrap();
+    /// assert!(!std::fs::read_to_string(&path).unwrap().contains("age"));
+    /// assert!(Config::<MyConfig>::configure().with_encryption_key(&key).load(&path).is_ok());
+    /// assert!(Config::<MyConfig>::configure().with_encryption_key(&[8u8; 32]).load(&path).is_err());
+    /// ```
+    #[cfg(feature = "encryption")]
+    pub fn with_encryption_key(mut self, key: &[u8; 32]) -> Self {
+        self.settings.encryption = Some(Encryption::new(key));
+        self
+    }
+
+    /// Loads config files which are not encrypted yet as plain text when [`ConfigBuilder::with_encryption_key`] is set, so the next save encrypts them.
+    /// Only enable it while migrating existing files: files without the header of encrypted files are not authenticated, so anyone who can write the file can change the config.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Error};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+    /// struct MyConfig {
+    ///   name: String,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-plaintext.json");
+    /// std::fs::write(&path, r#"{ "name": "John" }"#).unwrap();
+    /// let key = [7u8; 32];
+    /// let err = Config::<MyConfig>::configure().with_encryption_key(&key).load(&path).unwrap_err();
+    /// assert!(matches!(err, Error::IntegrityError { .. }));
+    ///
+    /// let config = Config::<MyConfig>::configure().with_encryption_key(&key).allow_plaintext_migration().load(&path).unwrap();
+    /// config.save().unwrap();
+    /// assert!(!std::fs::read_to_string(&path).unwrap().contains("John"));
+    /// assert!(Config::<MyConfig>::configure().with_encryption_key(&key).load(&path).is_ok());
+    /// ```
+    #[cfg(feature = "encryption")]
+    pub fn allow_plaintext_migration(mut self) -> Self {
+        self.settings.plaintext_migration = true;
+        self
+    }
+
+    /// Keeps the SHA-256 checksum of the config file in a file next to it, like `config.json.sha256`, and verifies the config file against it on every load and reload.
+    /// Loading fails with [`Error::IntegrityError`] if the file was corrupted, truncated or changed without opzioni. Edit such files with opzioni, or delete the checksum to accept them.
+    /// Files without a checksum, like ones saved before it was enabled, are loaded as they are and get one with the next save.
+    /// The checksum file has the format of `sha256sum`, so uncompressed files can also be checked with `sha256sum -c`. Files of sections mapped with [`ConfigBuilder::map_section`] are not covered.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Error};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-checksum.json");
+    /// let _ = std::fs::remove_file(&path);
+    /// let _ = std::fs::remove_file(path.with_extension("json.sha256"));
+    /// let config = Config::<MyConfig>::configure().with_checksum().create_if_missing().load(&path).unwrap();
+    /// config.save().unwrap();
+    /// assert!(Config::<MyConfig>::configure().with_checksum().load(&path).is_ok());
+    ///
+    /// std::fs::write(&path, r#"{ "name": "" "#).unwrap();
+    /// let err = Config::<MyConfig>::configure().with_checksum().load(&path).unwrap_err();
+    /// assert!(matches!(err, Error::IntegrityError { .. }));
+    /// ```
+    #[cfg(feature = "checksum")]
+    pub fn with_checksum(mut self) -> Self {
+        self.settings.checksum = true;
+        self
+    }
+
+    /// Overrides fields of the config with the arguments given on the command line, so the config file is overridden by the environment variables of [`ConfigBuilder::use_attributes`] and both by the command line.
+    /// The id of each argument is the dotted path of the field it overrides, like `age` or `server.port`. With the clap derive macro the id is the name of the field, nested fields set it with `#[arg(id = "server.port", long = "port")]`.
+    /// Only arguments which were given are applied, so the defaults of clap do not override the config file. Arguments which are not fields of the config, like the path of the config file, are skipped.
+    /// The values are parsed into the type of the field. Arguments with several values override lists. Like values from the environment, they do not count as changes and are not written to the file when it is saved, unless they were changed in memory.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use clap::{Arg, Command};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let command = Command::new("app")
+    ///     .arg(Arg::new("config").long("config"))
+    ///     .arg(Arg::new("name").long("name").default_value("Jane"))
+    ///     .arg(Arg::new("age").long("age"));
+    /// let matches = command.get_matches_from(["app", "--config", "testconfig.json", "--age", "43"]);
+    /// let config = Config::<MyConfig>::configure().with_cli_overrides(&matches).load(Path::new("testconfig.json")).unwrap();
+    /// assert_eq!(config.get().read().unwrap().age, 43);
+    /// assert_eq!(config.get().read().unwrap().name, "John");
+    /// ```
+    #[cfg(feature = "clap")]
+    pub fn with_cli_overrides(mut self, matches: &clap::ArgMatches) -> Self {
+        self.settings.cli = crate::cli::given(matches);
+        self
+    }
+
+    /// Merges the values of a figment provider over the config file, like the ones of a `figment::Figment` a project already builds.
+    /// Providers are merged in the order they are added, over the config file and below the environment variables of [`ConfigBuilder::use_attributes`] and the command line arguments of `with_cli_overrides`.
+    /// figment reads each provider when it is added, so reloading the config file does not read the providers again. Like values from the environment, their values do not count as changes. With the `json` feature, saving keeps the values of the file in place of the ones which were not changed in memory.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use figment::providers::Serialized;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::configure().merge_provider(Serialized::default("age", 43)).load(Path::new("testconfig.json")).unwrap();
+    /// assert_eq!(config.get().read().unwrap().age, 43);
+    /// assert_eq!(config.get().read().unwrap().name, "John");
+    /// ```
+    #[cfg(feature = "figment")]
+    pub fn merge_provider(mut self, provider: impl figment::Provider) -> Self {
+        let providers = self.settings.providers.take().unwrap_or_default();
+        self.settings.providers = Some(providers.merge(provider));
+        self
+    }
+
+    /// Checks the config file at the given path and returns a structured report of everything which is wrong with it, instead of failing on the first error.
+    /// The file is compared against the serialized form of `T::default()` to find unknown keys and values of the wrong type.
+    /// If the file cannot be deserialized, the report also contains missing keys and the error of the parser, with line and column where the format reports them.
+    /// An empty report means the file is fine. Migrations and other options of the builder are not applied.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, DiagnosticKind};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: String,
+    ///   email: String,
+    /// }
+    ///
+    /// let report = Config::<MyConfig>::configure().diagnose(Path::new("testconfig.json")).unwrap();
+    /// for diagnostic in &report {
+    ///     println!("{}", diagnostic);
+    /// }
+    /// assert!(report.iter().any(|d| d.path == "email" && d.kind == DiagnosticKind::MissingKey));
+    /// assert!(report.iter().any(|d| d.path == "age" && matches!(d.kind, DiagnosticKind::InvalidType { .. })));
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn diagnose(&self, path: &Path) -> Result<Vec<Diagnostic>, Error> {
+        self.settings.loader(path)?.diagnose()
+    }
+
+    /// Writes the JSON Schema of the config type to the given file, see [`Config::json_schema`]. The schema is laid out like saved configs, see [`ConfigBuilder::save_options`].
+    /// Editors pick it up for JSON files with a `"$schema"` key pointing to it, and for YAML files with a `# yaml-language-server: $schema=<path>` comment.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use schemars::JsonSchema;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, JsonSchema, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-schema.json");
+    /// Config::<MyConfig>::configure().write_schema(&path).unwrap();
+    /// assert!(std::fs::read_to_string(&path).unwrap().contains(r#""age": {"#));
+    /// ```
+    #[cfg(feature = "schemars")]
+    pub fn write_schema(&self, path: impl AsRef<Path>) -> Result<(), Error>
+        where T: schemars::JsonSchema {
+        let path = path.as_ref();
+        let data = Format::Json.serialize_with(&Config::<T>::json_schema(), &self.settings.save).map_err(|err| err.at(path, Some(Format::Json)))?;
+        std::fs::write(path, data).map_err(|err| Error::from(err).at(path, Some(Format::Json)))
+    }
+
+    /// Writes the default config with the given loader and returns it. The path is `None` if the loader writes to a backend.
+    fn create(&self, loader: &dyn ConfigManager<T>, path: Option<&Path>) -> Result<Config<T>, Error> {
+        let mut config = self.settings.initial();
+        self.settings.validate(&config)?;
+        if let Some(parent) = path.and_then(Path::parent).filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        #[cfg(feature = "file-lock")]
+        let _lock = path.map(|path| file_lock::acquire(path, true)).transpose()?;
+        #[cfg(feature = "tracing")]
+        trace!(file = ?path, "creating missing config file");
+        let data = self.settings.serialize(loader, &config)?;
+        let hash = fingerprint(&data);
+        let written = self.settings.secrets(&config)?.store().and_then(|_| loader.write(&data));
+        wipe(data);
+        written?;
+        let mut persisted = Some(hash);
+        if self.settings.overrides() {
+            self.settings.apply_overrides(&mut config)?;
+            self.settings.validate(&config)?;
+            persisted = self.settings.serialize(loader, &config).ok().map(|data| {
+                let hash = fingerprint(&data);
+                wipe(data);
+                hash
+            });
+        }
+        let config = Config::from_parts(config, path.map(Path::to_path_buf), self.settings.clone(), None, persisted);
+        *config.inner.disk.lock().unwrap() = match path {
+            Some(path) => self.settings.disk_fingerprint(path),
+            None => self.settings.detect_conflicts.then_some(hash),
+        };
+        Ok(config)
+    }
+
+    /// Loads a config file from disk. The file extension of the config file determines the format of the config file.
+    /// The currently supported formats are JSON, TOML and YAML.
+    /// The config file must contain a valid config of the given type `T`.
+    /// If the config file does not exist or is invalid, an error is returned. To use the default values of the given type `T` instead of an error, set [`ConfigBuilder::use_default_on_error`].
+    /// An empty config file fails with [`Error::EmptyFile`] unless [`ConfigBuilder::treat_empty_as_missing`] is set.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///    name: String,
+    ///    age: u8,
+    /// }
+    ///
+    /// let config: Config<MyConfig> = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+    /// ```
+    pub fn load(&self, path: &Path) -> Result<Config<T>, Error>
+    {
+        self.load_with_outcome(path).map(LoadOutcome::into_config)
+    }
+
+    /// Loads a config file from disk like [`ConfigBuilder::load`], but tells whether the file was actually loaded.
+    /// If [`ConfigBuilder::use_default_on_error`] is set and loading fails, [`LoadOutcome::Defaulted`] carries the default config together with the error which caused it to be used.
+    /// This allows applications to inform the user that their config file was ignored and why.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::{Config, LoadOutcome};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///    name: String,
+    ///    age: u8,
+    /// }
+    ///
+    /// let config = match Config::<MyConfig>::configure().use_default_on_error().load_with_outcome(Path::new("missing.json")).unwrap() {
+    ///     LoadOutcome::Loaded(config) => config,
+    ///     LoadOutcome::Defaulted { config, reason } => {
+    ///         eprintln!("ignoring missing.json: {}", reason);
+    ///         config
+    ///     }
+    /// };
+    /// ```
+    pub fn load_with_outcome(&self, path: &Path) -> Result<LoadOutcome<T>, Error>
+    {
+        let path = self.resolve(path);
+        match self.try_load(&path) {
+            Ok(config) => Ok(LoadOutcome::Loaded(config)),
+            Err(err) => self.handle_load_err(err, Some(&path)),
+        }
+    }
+
+    /// Loads a config file like [`ConfigBuilder::load`], but shares the config with every other caller loading the same file into the same type in this process.
+    /// The file is only read and parsed the first time, every later call returns a handle to the same config, as long as one of the handles is still alive.
+    /// This avoids loading the same file over and over when, for example, every plugin of an application loads the shared application config.
+    /// The options of the builder which loaded the file first apply to the shared config. A config which was defaulted because of [`ConfigBuilder::use_default_on_error`] is not shared.
+    /// Concurrent calls for the same file wait for the first one to load it, while other files load independently, so callbacks like the validator may load other shared configs.
+    ///
+    /// # Example
+    /// ```
+    /// use std::sync::Arc;
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///    name: String,
+    ///    age: u8,
+    /// }
+    ///
+    /// let builder = Config::<MyConfig>::configure();
+    /// let first = builder.load_shared(Path::new("testconfig.json")).unwrap();
+    /// let second = builder.load_shared(Path::new("testconfig.json")).unwrap();
+    /// assert!(Arc::ptr_eq(&first, &second));
+    /// ```
+    pub fn load_shared(&self, path: &Path) -> Result<Arc<Config<T>>, Error>
+        where T: Send + Sync + 'static {
+        let path = self.resolve(path);
+        config::shared(&path, || match self.try_load(&path) {
+            Ok(config) => Ok((config, true)),
+            Err(err) => self.handle_load_err(err, Some(&path)).map(|outcome| (outcome.into_config(), false)),
+        })
+    }
+
+    /// Loads a config file like [`ConfigBuilder::load`] into a [`crate::RcuConfig`], for read-heavy services whose readers should not lock the config.
+    #[cfg(all(feature = "rcu", not(any(feature = "tokio", feature = "async-std", feature = "smol"))))]
+    pub fn load_rcu(&self, path: &Path) -> Result<crate::RcuConfig<T>, Error>
+        where T: Send + Sync {
+        self.load(path).and_then(crate::RcuConfig::new)
+    }
+
+    /// Searches the standard config locations for a file called `name` and loads the first one found like [`ConfigBuilder::load`].
+    /// The current directory is searched first, then the config directory of the user, like `$XDG_CONFIG_HOME/<app>` or `~/.config/<app>` on Linux,
+    /// `~/Library/Application Support/<app>` and `~/.config/<app>` on macOS and `%APPDATA%\<app>` on Windows, and finally the system directories in `XDG_CONFIG_DIRS`, like `/etc/xdg/<app>`, on Unix.
+    /// In each directory every enabled format is tried, in the order `json`, `toml`, `yaml` and `yml`.
+    /// If no file is found, the file with the first enabled format in the config directory of the user is loaded, so [`ConfigBuilder::create_if_missing`] and [`ConfigBuilder::use_default_on_error`] work as usual.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let config = Config::<MyConfig>::configure().discover("myapp", "testconfig").unwrap();
+    /// assert_eq!(config.get().read().unwrap().name, "John");
+    /// ```
+    pub fn discover(&self, app: &str, name: &str) -> Result<Config<T>, Error>
+    {
+        let search = config::config_dirs(app).iter().fold(SearchPaths::new(), |search, dir| search.dir(dir)).name(name);
+        let path = search.find().unwrap_or_else(|| {
+            let dir = config::user_config_dirs(app).into_iter().next().unwrap_or_default();
+            SearchPaths::new().dir(&dir).name(name).candidates().next().unwrap_or(dir)
+        });
+        self.load(&path)
+    }
+
+    /// Loads the first existing file of the given [`SearchPaths`] like [`ConfigBuilder::load`]. Use [`Config::path`] to find out which file was chosen.
+    /// If no file exists, the first candidate is loaded, so [`ConfigBuilder::create_if_missing`] creates it and [`ConfigBuilder::use_default_on_error`] works as usual.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, SearchPaths};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let search = SearchPaths::new().dir(Path::new("config")).dir(Path::new(".")).name("myapp").name("testconfig");
+    /// let config = Config::<MyConfig>::configure().search(&search).unwrap();
+    /// assert_eq!(config.path().as_deref(), Some(Path::new("./testconfig.json")));
+    /// ```
+    pub fn search(&self, search: &SearchPaths) -> Result<Config<T>, Error>
+    {
+        let path = search.find().or_else(|| search.candidates().next()).ok_or(Error::ConfigLoadError { path: None, format: None, message: None })?;
+        self.load(&path)
+    }
+
+    /// Loads the first of the given config files which loads successfully, for example an optional override file before the file of the user and the one of the system.
+    /// Every file before it is skipped, also if it exists but fails to load, and returned with the error it failed with, so the application can tell the user why a file was ignored.
+    /// The files are loaded like with [`ConfigBuilder::load`], except that [`ConfigBuilder::create_if_missing`] only creates the first file if none of them loads.
+    /// If none of them loads and [`ConfigBuilder::use_default_on_error`] or [`ConfigBuilder::on_error`] recovers from the error of the first file, the recovered config is saved to the first file.
+    /// Otherwise the error of the first file is returned. If the path is overridden with [`ConfigBuilder::path_from_env`], only that file is loaded.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    /// use std::path::Path;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let (config, skipped) = Config::<MyConfig>::configure()
+    ///     .load_first_of(&["missing-override.json", "testconfig.json"])
+    ///     .unwrap();
+    /// assert_eq!(config.path().as_deref(), Some(Path::new("testconfig.json")));
+    /// assert_eq!(skipped[0].path, Path::new("missing-override.json"));
+    /// assert_eq!(config.get().read().unwrap().age, 42);
+    /// ```
+    pub fn load_first_of<P>(&self, paths: &[P]) -> Result<(Config<T>, Vec<Skipped>), Error>
+        where P: AsRef<Path> {
+        if let Some(path) = self.env_path() {
+            return self.load(&path).map(|config| (config, Vec::new()));
+        }
+        let Some(first) = paths.first().map(AsRef::as_ref) else {
+            return Err(Error::ConfigLoadError { path: None, format: None, message: Some("no config files given".to_string()) });
+        };
+        let chain = Self { create_if_missing: false, ..self.clone() };
+        let mut skipped = Vec::new();
+        for path in paths.iter().map(AsRef::as_ref) {
+            match chain.try_load(path) {
+                Ok(config) => return Ok((config, skipped)),
+                Err(reason) => skipped.push(Skipped { path: path.to_path_buf(), reason }),
+            }
+        }
+        if self.create_if_missing && !first.exists() {
+            return self.try_load(first).map(|config| (config, skipped));
+        }
+        match self.recover(&skipped[0].reason, Some(first)) {
+            Some(config) => Ok((Config::from_parts(config, Some(first.to_path_buf()), self.settings.clone(), None, None), skipped)),
+            None => Err(skipped.swap_remove(0).reason),
+        }
+    }
+
+    /// Loads a config from a reader instead of a file, for example from an embedded resource or a network stream. The data must be in the given [`crate::Format`].
+    /// The options of the builder apply like for [`ConfigBuilder::load`], except the ones about files. The returned config has no file, so saving it returns [`Error::NoBackingFile`] until it is given one with [`Config::set_path`].
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Format};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let data = b"name: John\nage: 42\n";
+    /// let config = Config::<MyConfig>::configure().load_from_reader(&data[..], Format::Yaml).unwrap();
+    /// assert_eq!(config.get().read().unwrap().age, 42);
+    /// assert!(config.path().is_none());
+    /// ```
+    pub fn load_from_reader(&self, mut reader: impl Read, format: Format) -> Result<Config<T>, Error>
+    {
+        let mut data = Vec::new();
+        if let Err(err) = reader.read_to_end(&mut data) {
+            wipe(data);
+            return self.handle_load_err(err.into(), None).map(LoadOutcome::into_config);
+        }
+        self.load_data(data, format)
+    }
+
+    /// Loads a config from a string in the given [`crate::Format`] like [`ConfigBuilder::load_from_reader`], for example from a command line flag or a test fixture.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, Format};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let builder = Config::<MyConfig>::configure().validate(|config| if config.age >= 18 { Ok(()) } else { Err("too young".to_string()) });
+    /// assert!(builder.load_from_str("name = \"Tim\"\nage = 12", Format::Toml).is_err());
+    /// let config = builder.load_from_str("name = \"John\"\nage = 42", Format::Toml).unwrap();
+    /// assert_eq!(config.get().read().unwrap().age, 42);
+    /// ```
+    pub fn load_from_str(&self, data: &str, format: Format) -> Result<Config<T>, Error>
+    {
+        self.load_data(data.as_bytes().to_vec(), format)
+    }
+
+    /// Builds a config from a `config::Config` of the config-rs crate, so an application can keep its config-rs sources while it moves to opzioni.
+    /// config-rs merges its sources and converts their values into the types of `T`. The options of the builder apply like for [`ConfigBuilder::load_from_reader`], except the ones about files and formats.
+    /// The returned config has no file. Give it one with [`Config::attach`] to save it, for example to migrate the sources of config-rs into a single file.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let source = config_rs::Config::builder().set_default("name", "John").unwrap().set_override("age", 42).unwrap().build().unwrap();
+    /// let config = Config::<MyConfig>::configure().load_from_config_rs(&source).unwrap();
+    /// assert_eq!(config.get().read().unwrap().name, "John");
+    /// assert_eq!(config.get().read().unwrap().age, 42);
+    /// ```
+    #[cfg(feature = "config-rs")]
+    pub fn load_from_config_rs(&self, source: &config_rs::Config) -> Result<Config<T>, Error> {
+        let config = source.clone().try_deserialize::<T>().map_err(|err| Error::load(err.to_string()));
+        match config.and_then(|config| self.settings.adopt(config)) {
+            Ok(config) => Ok(Config::from_parts(config, None, self.settings.clone(), Some(self.settings.clock.now()), None)),
+            Err(err) => self.handle_load_err(err, None).map(LoadOutcome::into_config),
+        }
+    }
+
+    /// Loads a config from a [`crate::Backend`] instead of a file, for example from a key of etcd. The data must be in the given [`crate::Format`].
+    /// The returned config keeps the backend, so [`Config::save`] and [`Config::reload`] go through it. The options of the builder apply like for [`ConfigBuilder::load`], except the ones about files.
+    /// If the backend holds no config, [`ConfigBuilder::create_if_missing`] stores the default config in it. A config which was defaulted because of [`ConfigBuilder::use_default_on_error`] also keeps the backend, so saving it stores it there.
+    /// See [`crate::Backend`] for an example.
+    pub fn load_from_backend(&self, backend: impl Backend + 'static, format: Format) -> Result<Config<T>, Error>
+    {
+        let remote = Remote::new(backend, format);
+        let config = match self.try_load_remote(&remote) {
+            Ok(config) => config,
+            Err(err) => self.handle_load_err(err, None)?.into_config(),
+        };
+        config.inner.location.lock().unwrap().remote = Some(remote);
+        Ok(config)
+    }
+
+    fn try_load_remote(&self, remote: &Remote) -> Result<Config<T>, Error>
+    {
+        let data = remote.backend.read()?;
+        let empty = data.as_deref() == Some("");
+        if empty && !self.treat_empty_as_missing {
+            return Err(Error::EmptyFile { path: None, format: None });
+        }
+        if self.create_if_missing && (empty || data.is_none()) {
+            return self.create(self.settings.open(remote.loader::<T>(None)).as_ref(), None);
+        }
+        let data = data.filter(|data| !data.is_empty()).ok_or_else(backend::missing)?;
+        let (config, persisted, stored) = remote.parse(&self.settings, data.into_bytes())?;
+        let config = Config::from_parts(config, None, self.settings.clone(), Some(self.settings.clock.now()), persisted);
+        *config.inner.disk.lock().unwrap() = stored;
+        Ok(config)
+    }
+
+    /// Parses config data which is already in memory into a config without a file.
+    fn load_data(&self, data: Vec<u8>, format: Format) -> Result<Config<T>, Error>
+    {
+        match self.settings.read(self.settings.open(manager::for_data(format, data)).as_ref()) {
+            Ok((config, _)) => Ok(Config::from_parts(config, None, self.settings.clone(), Some(self.settings.clock.now()), None)),
+            Err(err) => self.handle_load_err(err, None).map(LoadOutcome::into_config),
+        }
+    }
+
+    /// Returns the path from the environment variable set with [`ConfigBuilder::path_from_env`], falling back to the given path.
+    fn resolve(&self, fallback: &Path) -> PathBuf {
+        self.env_path().unwrap_or_else(|| fallback.to_path_buf())
+    }
+
+    /// Returns the path in the environment variable set with [`ConfigBuilder::path_from_env`], if it is set and not empty.
+    fn env_path(&self) -> Option<PathBuf> {
+        self.path_env
+            .as_deref()
+            .and_then(std::env::var_os)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    }
+
+    fn try_load(&self, path: &Path) -> Result<Config<T>, Error>
+    {
+        self.try_load_file(path).map_err(|err| err.at(path, self.settings.format_of(path)))
+    }
+
+    /// Loads the config file for [`ConfigBuilder::load`]. The errors do not carry the path and the format yet.
+    fn try_load_file(&self, path: &Path) -> Result<Config<T>, Error>
+    {
+        let loader = self.settings.loader(path)?;
+        let empty = is_empty_file(path);
+        if empty && !self.treat_empty_as_missing {
+            return Err(Error::EmptyFile { path: None, format: None });
+        }
+        if self.create_if_missing && (empty || !path.exists()) {
+            return self.create(loader.as_ref(), Some(path));
+        }
+        if empty {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "the config file is empty").into());
+        }
+        // Locking is best effort while loading, so config files in read-only directories can still be loaded.
+        #[cfg(feature = "file-lock")]
+        let _lock = path.exists().then(|| file_lock::acquire(path, false).ok()).flatten();
+        let (config, persisted) = self.settings.read(loader.as_ref())?;
+        let config = Config::from_parts(
+            config,
+            Some(path.to_path_buf()),
+            self.settings.clone(),
+            Some(self.settings.clock.now()),
+            persisted,
+        );
+        *config.inner.disk.lock().unwrap() = self.settings.disk_fingerprint(path);
+        Ok(config)
+    }
+}
+
+/// The result of [`ConfigBuilder::load_with_outcome`].
+#[derive(Debug)]
+pub enum LoadOutcome<T> where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    /// The config was loaded from the file.
+    Loaded(Config<T>),
+    /// The config file could not be loaded and the default config is used instead, because [`ConfigBuilder::use_default_on_error`] is set or [`ConfigBuilder::on_error`] recovered from the error.
+    Defaulted {
+        /// The default config, or the one returned with [`Recovery::UseValue`].
+        config: Config<T>,
+        /// The error which occurred while loading the config file.
+        reason: Error,
+    },
+}
+
+impl<T> LoadOutcome<T> where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    /// Returns the config, regardless of whether it was loaded or defaulted.
+    pub fn into_config(self) -> Config<T> {
+        match self {
+            LoadOutcome::Loaded(config) => config,
+            LoadOutcome::Defaulted { config, .. } => config,
+        }
+    }
+
+    /// Returns whether the default config is used because loading the config file failed.
+    pub fn is_defaulted(&self) -> bool {
+        matches!(self, LoadOutcome::Defaulted { .. })
+    }
+}
+
+/// A handle to one nested struct of a config, returned by [`Config::section`], so a module only needs to know the part of the config it uses.
+/// A section does not lock less than its config. Reading locks the whole config for reading and only clones the section.
+/// Updating goes through [`Config::transaction`], so the whole config is cloned and validated like in any transaction, and the config counts as changed until it is saved (see [`Config::is_dirty`]).
+/// Cloning the handle is cheap and all clones refer to the same config.
+///
+/// # Example
+/// ```
+/// use opzioni::blocking::Config;
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct Database {
+///   url: String,
+///   pool: u8,
+/// }
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   name: String,
+///   database: Database,
+/// }
+///
+/// let config = Config::<MyConfig>::in_memory(MyConfig::default());
+/// let database = config.section(|config| &config.database, |config| &mut config.database);
+/// database.update(|database| database.pool = 8).unwrap();
+/// assert_eq!(database.read().pool, 8);
+/// assert_eq!(config.read().database.pool, 8);
+/// ```
+pub struct Section<T, S> where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    config: Config<T>,
+    get: config::Getter<T, S>,
+    get_mut: config::GetterMut<T, S>,
+}
+
+impl<T, S> Section<T, S>
+    where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    /// Returns the whole config the section belongs to.
+    pub fn config(&self) -> &Config<T> {
+        &self.config
+    }
+
+    /// Returns a copy of the section.
+    pub fn read(&self) -> S
+        where S: Clone {
+        (self.get)(&*self.config.read()).clone()
+    }
+
+    /// Replaces the section with a new value.
+    pub fn write(&self, section: S) -> Result<(), Error> {
+        self.update(|current| *current = section)
+    }
+
+    /// Changes the section in a copy of the config, like [`Config::transaction`] changes the whole config. If the changed config is rejected by the validation, it is left untouched and the error is returned.
+    pub fn update<F>(&self, update: F) -> Result<(), Error>
+        where F: FnOnce(&mut S) {
+        self.config.transaction(|draft| {
+            update((self.get_mut)(draft));
+            Ok(())
+        })
+    }
+
+    /// Returns a handle to a section nested in this one, selected by accessors relative to it.
+    pub fn section<U>(&self, get: impl Fn(&S) -> &U + Send + Sync + 'static, get_mut: impl Fn(&mut S) -> &mut U + Send + Sync + 'static) -> Section<T, U>
+        where T: 'static, S: 'static {
+        let (outer, outer_mut) = (self.get.clone(), self.get_mut.clone());
+        Section {
+            config: self.config.clone(),
+            get: config::getter(move |config| get(outer(config))),
+            get_mut: config::getter_mut(move |config| get_mut(outer_mut(config))),
+        }
+    }
+}
+
+impl<T, S> Clone for Section<T, S> where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    fn clone(&self) -> Self {
+        Self { config: self.config.clone(), get: self.get.clone(), get_mut: self.get_mut.clone() }
+    }
+}
+
+impl<T, S> std::fmt::Debug for Section<T, S> where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Section").field("file", &self.config.path()).finish()
+    }
+}
+
+/// The operations of a config in a [`ConfigRegistry`] which do not depend on its type.
+trait Registered: Send + Sync {
+    /// Returns the config as [`std::any::Any`], so it can be downcast to its type.
+    fn any(&self) -> &dyn std::any::Any;
+    fn save(&self) -> Result<(), Error>;
+    fn reload(&self) -> Result<bool, Error>;
+}
+
+impl<T> Registered for Config<T>
+    where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone + Send + Sync + 'static {
+    fn any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        Config::save(self)
+    }
+
+    fn reload(&self) -> Result<bool, Error> {
+        Config::reload(self)
+    }
+}
+
+/// Manages several named configs of different types, for applications which keep their settings in several files, like `ui.toml` and `network.toml`.
+/// The configs are saved and reloaded together and retrieved by name and type.
+///
+/// # Example
+/// ```
+/// use opzioni::blocking::{Config, ConfigRegistry};
+/// use serde::{Serialize, Deserialize};
+/// use std::path::Path;
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct Person {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct UiConfig {
+///   theme: String,
+/// }
+///
+/// let ui = std::env::temp_dir().join("opzioni-registry-ui.json");
+/// std::fs::write(&ui, r#"{ "theme": "dark" }"#).unwrap();
+///
+/// let mut registry = ConfigRegistry::new();
+/// registry.register::<Person>("person", Path::new("testconfig.json")).unwrap();
+/// registry.insert("ui", Config::<UiConfig>::configure().create_if_missing().load(&ui).unwrap());
+///
+/// assert_eq!(registry.get::<Person>("person").unwrap().get().read().unwrap().name, "John");
+/// assert_eq!(registry.get::<UiConfig>("ui").unwrap().get().read().unwrap().theme, "dark");
+/// assert!(registry.get::<Person>("ui").is_none());
+/// assert_eq!(registry.reload_all().unwrap(), Vec::<String>::new());
+/// ```
+#[derive(Default)]
+pub struct ConfigRegistry {
+    configs: Vec<(String, Box<dyn Registered>)>,
+}
+
+impl ConfigRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the config file at the given path like [`ConfigBuilder::load`] and registers it under the given name.
+    /// Use [`ConfigRegistry::insert`] for configs which need other options of the builder.
+    pub fn register<T>(&mut self, name: &str, path: &Path) -> Result<Config<T>, Error>
+        where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone + Send + Sync + 'static {
+        Ok(self.insert(name, Config::<T>::configure().load(path)?))
+    }
+
+    /// Registers an already loaded config under the given name. A config registered earlier under the same name is replaced.
+    /// Returns a clone of the config, which stays valid when it is removed from the registry.
+    pub fn insert<T>(&mut self, name: &str, config: Config<T>) -> Config<T>
+        where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone + Send + Sync + 'static {
+        let entry = (name.to_string(), Box::new(config.clone()) as Box<dyn Registered>);
+        match self.configs.iter_mut().find(|(registered, _)| registered == name) {
+            Some(existing) => *existing = entry,
+            None => self.configs.push(entry),
+        }
+        config
+    }
+
+    /// Returns the config registered under the given name, or `None` if there is none or it is not a config of type `T`.
+    pub fn get<T>(&self, name: &str) -> Option<Config<T>>
+        where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone + Send + Sync + 'static {
+        let (_, config) = self.configs.iter().find(|(registered, _)| registered == name)?;
+        config.any().downcast_ref().cloned()
+    }
+
+    /// Removes the config registered under the given name. Returns whether there was one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let count = self.configs.len();
+        self.configs.retain(|(registered, _)| registered != name);
+        self.configs.len() != count
+    }
+
+    /// Returns the names of the registered configs, in the order they were registered.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.configs.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Saves every config like [`Config::save`], in the order they were registered.
+    /// A failing save does not stop the others. The error of the first one which failed is returned after all configs were saved.
+    pub fn save_all(&self) -> Result<(), Error> {
+        let mut result = Ok(());
+        for (_, config) in &self.configs {
+            let saved = config.save();
+            if result.is_ok() {
+                result = saved;
+            }
+        }
+        result
+    }
+
+    /// Reloads every config like [`Config::reload`], in the order they were registered. Returns the names of the configs which changed.
+    /// A failing reload does not stop the others. The error of the first one which failed is returned after all configs were reloaded.
+    pub fn reload_all(&self) -> Result<Vec<String>, Error> {
+        let mut changed = Vec::new();
+        let mut error = None;
+        for (name, config) in &self.configs {
+            match config.reload() {
+                Ok(true) => changed.push(name.clone()),
+                Ok(false) => {}
+                Err(err) if error.is_none() => error = Some(err),
+                Err(_) => {}
+            }
+        }
+        match error {
+            Some(err) => Err(err),
+            None => Ok(changed),
+        }
+    }
+}
+
+impl std::fmt::Debug for ConfigRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfigRegistry").field("configs", &self.names().collect::<Vec<_>>()).finish()
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::Read;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{Backend, Error};
+
+/// A [`Backend`] keeping the config in a key of the Consul KV store, so the instances of a clustered service share one config.
+/// With the `tokio` feature, [`crate::Config::watch_backend`] uses blocking queries on the key to reload the config as soon as another instance saves it.
+/// [`Consul::lock`] coordinates the instances through a Consul session, so their read-modify-write cycles do not interleave. Load the config from an `Arc<Consul>` to keep the backend at hand for locking.
+///
+/// # Example
+/// ```no_run
+/// use std::sync::Arc;
+/// use opzioni::{Config, Consul, Format};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// let consul = Arc::new(Consul::new("http://127.0.0.1:8500", "myapp/config"));
+/// let config = Config::<MyConfig>::configure().create_if_missing().load_from_backend(consul.clone(), Format::Json).unwrap();
+/// let lock = consul.lock().unwrap();
+/// // ... reload, change and save the config ...
+/// drop(lock);
+/// ```
+#[derive(Debug)]
+pub struct Consul {
+    endpoint: String,
+    key: String,
+    token: Option<String>,
+    session_ttl: Duration,
+    index: Mutex<u64>,
+}
+
+impl Consul {
+    /// Creates a backend for the given key of the Consul agent at `endpoint`, like `http://127.0.0.1:8500`.
+    pub fn new(endpoint: &str, key: &str) -> Self {
+        Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            key: key.trim_start_matches('/').to_string(),
+            token: None,
+            session_ttl: Duration::from_secs(60),
+            index: Mutex::new(0),
+        }
+    }
+
+    /// Sends the given ACL token with every request, for Consul clusters with ACLs enabled.
+    pub fn token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    /// Sets how long the session behind [`Consul::lock`] lives, 60 seconds by default. Consul releases the lock when the session expires, so a crashed instance cannot hold it forever.
+    /// The lock is not renewed, so it should be held for less than the TTL. Consul accepts TTLs between 10 seconds and 24 hours.
+    pub fn session_ttl(mut self, ttl: Duration) -> Self {
+        self.session_ttl = ttl;
+        self
+    }
+
+    /// Locks the config against the other instances using [`Consul::lock`] on the same key, blocking until the lock is available. The lock is released when the returned [`ConsulLock`] is dropped.
+    /// The lock is kept in the key next to the config with `.lock` appended and only coordinates instances which take it, like [`crate::Config::lock_exclusive`] does for files.
+    pub fn lock(&self) -> Result<ConsulLock<'_>, Error> {
+        let session: Session = self.request("PUT", "session/create")
+            .send_json(serde_json::json!({ "Name": format!("opzioni {}", self.key), "TTL": format!("{}s", self.session_ttl.as_secs()), "Behavior": "release" }))?
+            .into_json()?;
+        let lock = ConsulLock { consul: self, session: session.id };
+        let mut index = 0;
+        loop {
+            let acquired = self.request("PUT", &format!("kv/{}.lock", self.key)).query("acquire", &lock.session).call()?.into_string()?;
+            if acquired.trim() == "true" {
+                return Ok(lock);
+            }
+            // Blocks until the lock key changes, which happens when the holder releases it.
+            index = match self.request("GET", &format!("kv/{}.lock", self.key)).query("index", &index.to_string()).query("wait", "1m").call() {
+                Ok(response) => consul_index(&response),
+                Err(ureq::Error::Status(404, response)) => consul_index(&response),
+                Err(err) => return Err(err.into()),
+            };
+        }
+    }
+
+    fn request(&self, method: &str, endpoint: &str) -> ureq::Request {
+        let request = ureq::request(method, &format!("{}/v1/{}", self.endpoint, endpoint));
+        match &self.token {
+            Some(token) => request.set("X-Consul-Token", token),
+            None => request,
+        }
+    }
+
+    /// Reads the key, waiting for a change after the given index of Consul if there is one. Returns the value and the new index.
+    fn get(&self, wait_after: Option<u64>) -> Result<(Option<String>, u64), Error> {
+        let mut request = self.request("GET", &format!("kv/{}", self.key)).query("raw", "");
+        if let Some(index) = wait_after {
+            request = request.query("index", &index.to_string()).query("wait", "5m");
+        }
+        match request.call() {
+            Ok(response) => {
+                let index = consul_index(&response);
+                let mut data = String::new();
+                response.into_reader().read_to_string(&mut data)?;
+                Ok((Some(data), index))
+            }
+            Err(ureq::Error::Status(404, response)) => Ok((None, consul_index(&response))),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl Backend for Consul {
+    fn read(&self) -> Result<Option<String>, Error> {
+        let (data, index) = self.get(None)?;
+        *self.index.lock().unwrap() = index;
+        Ok(data)
+    }
+
+    fn write(&self, data: &str) -> Result<(), Error> {
+        let written = self.request("PUT", &format!("kv/{}", self.key)).send_string(data)?.into_string()?;
+        if written.trim() != "true" {
+            return Err(Error::io("Consul did not store the config"));
+        }
+        Ok(())
+    }
+
+    fn wait_for_change(&self) -> Result<(), Error> {
+        let last = *self.index.lock().unwrap();
+        loop {
+            let (_, index) = self.get(Some(last))?;
+            // A blocking query returns the same index when it timed out without a change. A lower index means Consul reset it.
+            if index != last {
+                *self.index.lock().unwrap() = index;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// A lock on a config stored in Consul, returned by [`Consul::lock`]. The lock is released and its session destroyed when it is dropped.
+#[derive(Debug)]
+pub struct ConsulLock<'a> {
+    consul: &'a Consul,
+    session: String,
+}
+
+impl Drop for ConsulLock<'_> {
+    fn drop(&mut self) {
+        let _ = self.consul.request("PUT", &format!("kv/{}.lock", self.consul.key)).query("release", &self.session).call();
+        let _ = self.consul.request("PUT", &format!("session/destroy/{}", self.session)).call();
+    }
+}
+
+/// Returns the index of Consul sent with a response, which increases with every change of the requested key.
+fn consul_index(response: &ureq::Response) -> u64 {
+    response.header("X-Consul-Index").and_then(|index| index.parse().ok()).unwrap_or(0)
+}
+
+#[derive(Deserialize)]
+struct Session {
+    #[serde(rename = "ID")]
+    id: String,
+}
@@ -0,0 +1,153 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use crate::Error;
+use crate::config::fingerprint;
+use crate::secret::wipe;
+
+/// Written to the config file in place of a [`crate::Secret`] which is kept in the keyring.
+const PLACEHOLDER: &str = "<stored in keyring>";
+
+/// Marks a [`crate::Secret`] in the generic value while splitting a config. The NUL character keeps it apart from real strings.
+const MARKER: &str = "\u{0}opzioni-secret:";
+
+thread_local! {
+    /// The values of the secrets serialized while a config is split on this thread, `None` if no config is split.
+    static COLLECTED: RefCell<Option<Vec<Value>>> = const { RefCell::new(None) };
+}
+
+/// Takes the value of a [`crate::Secret`] which is being serialized while a config is split, returning the marker to serialize instead.
+/// Returns `None` if no config is split, so the secret is serialized as usual.
+pub(crate) fn collect<V>(value: &V) -> Option<String>
+    where V: serde::Serialize + ?Sized {
+    COLLECTED.with(|collected| {
+        let mut collected = collected.borrow_mut();
+        let secrets = collected.as_mut()?;
+        let value = serde_json::to_value(value).ok()?;
+        secrets.push(value);
+        Some(format!("{}{}", MARKER, secrets.len() - 1))
+    })
+}
+
+/// The keyring service the secrets of a config are stored under, together with what the keyring is known to hold.
+#[derive(Debug)]
+pub(crate) struct Keyring {
+    service: String,
+    /// The fingerprints of the secrets in the keyring by their dotted path, so unchanged secrets are not written again.
+    stored: Mutex<HashMap<String, u64>>,
+}
+
+impl Keyring {
+    pub(crate) fn new(service: &str) -> Self {
+        Self { service: service.to_string(), stored: Mutex::new(HashMap::new()) }
+    }
+
+    /// Serializes the config into a generic value with a placeholder for every [`crate::Secret`]. Returns the value and the secrets by their dotted path.
+    pub(crate) fn split<T>(&self, config: &T) -> Result<(Value, Vec<(String, Value)>), Error>
+        where T: serde::Serialize {
+        COLLECTED.with(|collected| *collected.borrow_mut() = Some(Vec::new()));
+        let value = serde_json::to_value(config);
+        let collected = COLLECTED.with(|collected| collected.borrow_mut().take()).unwrap_or_default();
+        let mut value = value.map_err(Error::serialize)?;
+        let mut secrets = Vec::new();
+        replace_markers(&mut value, String::new(), &collected, &mut secrets);
+        Ok((value, secrets))
+    }
+
+    /// Returns whether one of the given secrets differs from the one in the keyring.
+    pub(crate) fn changed(&self, secrets: &[(String, Value)]) -> bool {
+        let stored = self.stored.lock().unwrap();
+        secrets.iter().any(|(path, value)| stored.get(path) != Some(&hash(value)))
+    }
+
+    /// Writes the given secrets to the keyring, skipping the ones which did not change.
+    pub(crate) fn store(&self, secrets: Vec<(String, Value)>) -> Result<(), Error> {
+        let mut stored = self.stored.lock().unwrap();
+        for (path, value) in secrets {
+            let hash = hash(&value);
+            if stored.get(&path) == Some(&hash) {
+                continue;
+            }
+            let data = value.to_string();
+            let written = keyring::Entry::new(&self.service, &path).and_then(|entry| entry.set_password(&data));
+            wipe(data);
+            written?;
+            stored.insert(path, hash);
+        }
+        Ok(())
+    }
+
+    /// Replaces every placeholder in a loaded config file with the secret from the keyring.
+    pub(crate) fn resolve(&self, value: &mut Value) -> Result<(), Error> {
+        self.resolve_at(value, String::new())
+    }
+
+    fn resolve_at(&self, value: &mut Value, path: String) -> Result<(), Error> {
+        match value {
+            Value::String(string) if string == PLACEHOLDER => {
+                let data = keyring::Entry::new(&self.service, &path).and_then(|entry| entry.get_password())
+                    .map_err(|err| Error::load(format!("secret {} is not in the keyring: {}", path, err)))?;
+                let secret = serde_json::from_str(&data);
+                wipe(data);
+                *value = secret?;
+                self.stored.lock().unwrap().insert(path, hash(value));
+            }
+            Value::Object(map) => {
+                for (key, value) in map {
+                    self.resolve_at(value, join(&path, key))?;
+                }
+            }
+            Value::Array(items) => {
+                for (index, value) in items.iter_mut().enumerate() {
+                    self.resolve_at(value, join(&path, &index.to_string()))?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Clone for Keyring {
+    fn clone(&self) -> Self {
+        Self { service: self.service.clone(), stored: Mutex::new(self.stored.lock().unwrap().clone()) }
+    }
+}
+
+/// Replaces the markers of the secrets with the placeholder and collects the secrets by their dotted path.
+fn replace_markers(value: &mut Value, path: String, collected: &[Value], secrets: &mut Vec<(String, Value)>) {
+    match value {
+        Value::String(string) => {
+            let secret = string.strip_prefix(MARKER).and_then(|index| index.parse::<usize>().ok()).and_then(|index| collected.get(index));
+            if let Some(secret) = secret {
+                secrets.push((path, secret.clone()));
+                *value = Value::String(PLACEHOLDER.to_string());
+            }
+        }
+        Value::Object(map) => {
+            for (key, value) in map {
+                replace_markers(value, join(&path, key), collected, secrets);
+            }
+        }
+        Value::Array(items) => {
+            for (index, value) in items.iter_mut().enumerate() {
+                replace_markers(value, join(&path, &index.to_string()), collected, secrets);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) }
+}
+
+fn hash(value: &Value) -> u64 {
+    let data = value.to_string();
+    let hash = fingerprint(&data);
+    wipe(data);
+    hash
+}
@@ -0,0 +1,45 @@
+/// Runtime documentation of the fields of a config type, used by [`crate::Config::describe`].
+/// Nested config types usually delegate the part of the path after their own field name to the nested type.
+/// With the `derive` feature, `#[derive(Opzioni)]` implements this trait from the doc comments of the fields.
+///
+/// # Example
+/// ```
+/// use opzioni::{Config, Describe};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct Server {
+///   port: u16,
+/// }
+///
+/// impl Describe for Server {
+///   fn describe(path: &str) -> Option<&'static str> {
+///     match path {
+///       "port" => Some("The port the server listens on"),
+///       _ => None,
+///     }
+///   }
+/// }
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   server: Server,
+/// }
+///
+/// impl Describe for MyConfig {
+///   fn describe(path: &str) -> Option<&'static str> {
+///     match path.split_once('.') {
+///       None if path == "server" => Some("Settings of the HTTP server"),
+///       Some(("server", rest)) => Server::describe(rest),
+///       _ => None,
+///     }
+///   }
+/// }
+///
+/// assert_eq!(Config::<MyConfig>::describe("server.port"), Some("The port the server listens on"));
+/// assert_eq!(Config::<MyConfig>::describe("server.host"), None);
+/// ```
+pub trait Describe {
+    /// Returns the description of the field at the given dotted path, or `None` if the field does not exist or has no description.
+    fn describe(path: &str) -> Option<&'static str>;
+}
@@ -0,0 +1,159 @@
+use std::fmt::{Display, Formatter};
+
+use serde_json::Value;
+
+/// A single problem found in a config file by [`crate::ConfigBuilder::diagnose`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The dotted path of the affected key. Empty if the problem concerns the whole file or could not be attributed to a key.
+    pub path: String,
+    /// What is wrong.
+    pub kind: DiagnosticKind,
+    /// The 1-based line of the problem, if the format reports it.
+    pub line: Option<usize>,
+    /// The 1-based column of the problem, if the format reports it.
+    pub column: Option<usize>,
+}
+
+/// The kinds of problems reported by [`Diagnostic`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// The key is required by the config type but missing in the file.
+    MissingKey,
+    /// The key exists in the file but not in the config type.
+    UnknownKey,
+    /// The value of the key has the wrong type.
+    InvalidType {
+        /// The type the config type expects, for example `number`.
+        expected: String,
+        /// The type found in the file.
+        found: String,
+    },
+    /// The file could not be parsed or deserialized. Contains the message of the parser.
+    Invalid(String),
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        match &self.kind {
+            DiagnosticKind::MissingKey => write!(f, "missing key"),
+            DiagnosticKind::UnknownKey => write!(f, "unknown key"),
+            DiagnosticKind::InvalidType { expected, found } => write!(f, "expected {}, found {}", expected, found),
+            DiagnosticKind::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// A syntax error in a file of a format which opzioni parses itself, at the 1-based line and column where it occurred.
+#[cfg(any(feature = "kdl", feature = "env-file", feature = "properties"))]
+#[derive(Debug)]
+pub(crate) struct ParseError {
+    pub(crate) message: String,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+#[cfg(any(feature = "kdl", feature = "env-file", feature = "properties"))]
+impl From<ParseError> for crate::Error {
+    fn from(err: ParseError) -> Self {
+        crate::Error::parse_at(format!("{} at line {} column {}", err.message, err.line, err.column), err.line, err.column)
+    }
+}
+
+#[cfg(any(feature = "kdl", feature = "env-file", feature = "properties"))]
+impl From<ParseError> for Diagnostic {
+    fn from(err: ParseError) -> Self {
+        Diagnostic::invalid(err.message, Some((err.line, err.column)))
+    }
+}
+
+impl Diagnostic {
+    pub(crate) fn invalid(message: String, location: Option<(usize, usize)>) -> Self {
+        Self {
+            path: String::new(),
+            kind: DiagnosticKind::Invalid(message),
+            line: location.map(|(line, _)| line),
+            column: location.map(|(_, column)| column),
+        }
+    }
+
+    fn at(path: String, kind: DiagnosticKind) -> Self {
+        Self {
+            path,
+            kind,
+            line: None,
+            column: None,
+        }
+    }
+}
+
+/// Compares the generic representation of a config file with the serialized default config and reports missing keys, unknown keys and type mismatches.
+/// Missing keys are only reported if `deserialized` is false, because keys with a serde default may be left out of valid files.
+pub(crate) fn compare(value: &Value, reference: &Value, deserialized: bool) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    collect(value, reference, "", deserialized, &mut diagnostics);
+    diagnostics
+}
+
+fn collect(value: &Value, reference: &Value, path: &str, deserialized: bool, diagnostics: &mut Vec<Diagnostic>) {
+    match (value, reference) {
+        (_, Value::Null) => {}
+        (Value::Object(value), Value::Object(reference)) => {
+            let join = |key: &str| if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+            if !deserialized {
+                for key in reference.keys().filter(|key| !value.contains_key(*key)) {
+                    diagnostics.push(Diagnostic::at(join(key), DiagnosticKind::MissingKey));
+                }
+            }
+            // Empty maps in the default config usually belong to map types, which accept any key.
+            if reference.is_empty() {
+                return;
+            }
+            for (key, child) in value {
+                match reference.get(key) {
+                    Some(expected) => collect(child, expected, &join(key), deserialized, diagnostics),
+                    None => diagnostics.push(Diagnostic::at(join(key), DiagnosticKind::UnknownKey)),
+                }
+            }
+        }
+        (value, reference) if type_name(value) != type_name(reference) => {
+            diagnostics.push(Diagnostic::at(path.to_string(), DiagnosticKind::InvalidType {
+                expected: type_name(reference).to_string(),
+                found: type_name(value).to_string(),
+            }));
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "map",
+    }
+}
+
+/// Builds the report for a config file from its generic representation and the error which occurred while deserializing it into `T`, if any.
+/// If the file could not even be parsed into a generic value, the parse error is the only diagnostic.
+pub(crate) fn report<T>(value: Result<Value, Diagnostic>, error: Option<Diagnostic>) -> Vec<Diagnostic>
+    where T: serde::Serialize + Default {
+    match value {
+        Ok(value) => {
+            let reference = serde_json::to_value(T::default()).unwrap_or(Value::Null);
+            let mut diagnostics = compare(&value, &reference, error.is_none());
+            diagnostics.extend(error);
+            diagnostics
+        }
+        Err(diagnostic) => vec![diagnostic],
+    }
+}
@@ -0,0 +1,80 @@
+use serde_json::{Map, Value};
+
+use crate::Error;
+
+/// A value which differs between two states of a config, as found by [`diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// The dotted path of the value, like `server.port`. Arrays are compared as a whole, so the path never points into one.
+    pub path: String,
+    /// The value in the old config, or `None` if the key was added.
+    pub old: Option<Value>,
+    /// The value in the new config, or `None` if the key was removed.
+    pub new: Option<Value>,
+}
+
+impl Change {
+    /// Returns whether the key was added, that is it only exists in the new config.
+    pub fn is_added(&self) -> bool {
+        self.old.is_none()
+    }
+
+    /// Returns whether the key was removed, that is it only exists in the old config.
+    pub fn is_removed(&self) -> bool {
+        self.new.is_none()
+    }
+}
+
+/// Compares two states of a config and returns every value which differs, ordered by path.
+/// The configs are compared in their serialized form, so the paths use the names of the keys in the config file, including renames with `#[serde(rename)]`.
+/// A map which only exists in one of the configs is reported as one change of the whole map.
+/// Values of [`crate::Secret`] fields are compared and returned in plain text, so mask them before writing changes to a log.
+///
+/// # Example
+/// ```
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Server {
+///   host: String,
+///   port: u16,
+/// }
+///
+/// let old = Server { host: "localhost".to_string(), port: 8080 };
+/// let new = Server { host: "localhost".to_string(), port: 9090 };
+/// let changes = opzioni::diff(&old, &new).unwrap();
+/// assert_eq!(changes.len(), 1);
+/// assert_eq!(changes[0].path, "port");
+/// assert_eq!(changes[0].old, Some(8080.into()));
+/// assert_eq!(changes[0].new, Some(9090.into()));
+/// ```
+pub fn diff<T>(old: &T, new: &T) -> Result<Vec<Change>, Error>
+    where T: serde::Serialize + ?Sized {
+    let old = serde_json::to_value(old).map_err(Error::serialize)?;
+    let new = serde_json::to_value(new).map_err(Error::serialize)?;
+    let mut changes = Vec::new();
+    compare(old, new, String::new(), &mut changes);
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(changes)
+}
+
+fn compare(old: Value, new: Value, path: String, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => compare_maps(old, new, &path, changes),
+        (old, new) if old != new => changes.push(Change { path, old: Some(old), new: Some(new) }),
+        _ => {}
+    }
+}
+
+fn compare_maps(mut old: Map<String, Value>, new: Map<String, Value>, prefix: &str, changes: &mut Vec<Change>) {
+    let join = |key: &str| if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+    for (key, new) in new {
+        match old.remove(&key) {
+            Some(old) => compare(old, new, join(&key), changes),
+            None => changes.push(Change { path: join(&key), old: None, new: Some(new) }),
+        }
+    }
+    for (key, old) in old {
+        changes.push(Change { path: join(&key), old: Some(old), new: None });
+    }
+}
@@ -0,0 +1,86 @@
+use crate::Error;
+
+/// Returns the error for a config which is not a list and so cannot be saved as a stream of documents.
+fn not_a_list(format: &str) -> Error {
+    Error::serialize(format!("{} files hold a list of records, the config has to be a list like `Vec<T>`", format))
+}
+
+/// Parses newline delimited JSON, where every non-empty line is a record.
+#[cfg(feature = "json")]
+pub(crate) fn parse_ndjson<V>(data: &str) -> Result<V, Error>
+    where
+        V: serde::de::DeserializeOwned,
+{
+    let records = ndjson_value(data).map_err(|(line, err)| Error::parse(format!("{} in the record on line {}", err, line)))?;
+    Ok(serde_json::from_value(records)?)
+}
+
+/// Parses newline delimited JSON into an array of its records. Fails with the 1-based line of the first invalid record.
+#[cfg(feature = "json")]
+pub(crate) fn ndjson_value(data: &str) -> Result<serde_json::Value, (usize, serde_json::Error)> {
+    let records = data
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| serde_json::from_str(line).map_err(|err| (index + 1, err)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(serde_json::Value::Array(records))
+}
+
+/// Writes every record of a list on its own line. An empty list is written as an empty file.
+#[cfg(feature = "json")]
+pub(crate) fn write_ndjson<V>(value: &V) -> Result<Vec<u8>, Error>
+    where
+        V: serde::Serialize,
+{
+    let serde_json::Value::Array(records) = serde_json::to_value(value)? else {
+        return Err(not_a_list("NDJSON"));
+    };
+    let mut data = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut data, &record)?;
+        data.push(b'\n');
+    }
+    Ok(data)
+}
+
+/// Parses a YAML stream, where every document separated by `---` is a record. A stream with a single empty document is an empty list.
+#[cfg(feature = "yaml")]
+pub(crate) fn parse_yaml<V>(data: &[u8]) -> Result<V, Error>
+    where
+        V: serde::de::DeserializeOwned,
+{
+    Ok(serde_yaml::from_value(yaml_value(data)?)?)
+}
+
+/// Parses a YAML stream into a sequence of its documents.
+#[cfg(feature = "yaml")]
+pub(crate) fn yaml_value(data: &[u8]) -> Result<serde_yaml::Value, Error> {
+    use serde::Deserialize;
+
+    let mut documents = serde_yaml::Deserializer::from_slice(data).map(serde_yaml::Value::deserialize).collect::<Result<Vec<_>, _>>()?;
+    if documents.len() == 1 && documents[0].is_null() {
+        documents.clear();
+    }
+    Ok(serde_yaml::Value::Sequence(documents))
+}
+
+/// Writes every record of a list as its own YAML document. An empty list is written as a comment, so the file is not empty.
+#[cfg(feature = "yaml")]
+pub(crate) fn write_yaml<V>(value: &V) -> Result<Vec<u8>, Error>
+    where
+        V: serde::Serialize,
+{
+    let serde_yaml::Value::Sequence(records) = serde_yaml::to_value(value)? else {
+        return Err(not_a_list("YAML stream"));
+    };
+    if records.is_empty() {
+        return Ok(b"# no documents\n".to_vec());
+    }
+    let mut data = String::new();
+    for record in records {
+        data.push_str("---\n");
+        data.push_str(&serde_yaml::to_string(&record)?);
+    }
+    Ok(data.into_bytes())
+}
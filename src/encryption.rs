@@ -0,0 +1,103 @@
+use std::path::Path;
+
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use aes_gcm::aead::{Aead, AeadCore, OsRng};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::{Error, Format};
+use crate::manager::ConfigManager;
+
+/// Starts every encrypted config. Data without it is rejected, unless plain text is allowed to encrypt existing configs with their next save.
+const MAGIC: &str = "opzioni-encrypted:v1\n";
+
+/// What [`Error::IntegrityError`] expects when an encrypted config is read as plain text.
+const ENCRYPTED: &str = "encrypted";
+
+/// What [`Error::IntegrityError`] finds when an encrypted config is read as plain text.
+pub(crate) const PLAINTEXT: &str = "plain text";
+
+/// The length of the random nonce of AES-GCM, which is stored in front of the ciphertext.
+const NONCE_LEN: usize = 12;
+
+/// Returns whether the given data is an encrypted config.
+pub(crate) fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC.as_bytes())
+}
+
+/// Encrypts and decrypts configs with AES-256-GCM.
+#[derive(Clone)]
+pub(crate) struct Encryption {
+    cipher: Aes256Gcm,
+}
+
+impl Encryption {
+    pub(crate) fn new(key: &[u8; 32]) -> Self {
+        Self { cipher: Aes256Gcm::new(key.into()) }
+    }
+
+    /// Encrypts the serialized config with a new random nonce. The result is the magic header followed by the base64 encoded nonce and ciphertext, so it can be stored wherever text can.
+    pub(crate) fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher.encrypt(&nonce, data)
+            .map_err(|_| Error::serialize("the config could not be encrypted"))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(format!("{}{}\n", MAGIC, STANDARD.encode(sealed)).into_bytes())
+    }
+
+    /// Decrypts an encrypted config. Data without the magic header is returned unchanged if `plaintext` is set and fails with [`Error::IntegrityError`] otherwise, because anyone could have written it.
+    pub(crate) fn decrypt(&self, data: Vec<u8>, plaintext: bool) -> Result<Vec<u8>, Error> {
+        let Some(encoded) = data.strip_prefix(MAGIC.as_bytes()) else {
+            if plaintext {
+                return Ok(data);
+            }
+            crate::secret::wipe(data);
+            return Err(Error::IntegrityError { path: None, format: None, expected: ENCRYPTED.to_string(), actual: PLAINTEXT.to_string() });
+        };
+        let encoded = encoded.iter().copied().filter(|byte| !byte.is_ascii_whitespace()).collect::<Vec<_>>();
+        let sealed = STANDARD.decode(encoded).map_err(|err| Error::load(format!("the encrypted config is malformed: {}", err)))?;
+        if sealed.len() < NONCE_LEN {
+            return Err(Error::load("the encrypted config is truncated"));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::load("the config could not be decrypted, the key is wrong or the data was modified"))
+    }
+}
+
+impl std::fmt::Debug for Encryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Encryption(AES-256-GCM)")
+    }
+}
+
+/// Decrypts the config read by another manager and encrypts the config before it writes it.
+pub(crate) struct Encrypted<'a, T> {
+    pub(crate) inner: Box<dyn ConfigManager<T> + 'a>,
+    pub(crate) encryption: Encryption,
+    /// Whether configs which are not encrypted yet are read as they are, see [`crate::ConfigBuilder::allow_plaintext_migration`].
+    pub(crate) plaintext: bool,
+}
+
+impl<T> ConfigManager<T> for Encrypted<'_, T>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    fn format(&self) -> Format {
+        self.inner.format()
+    }
+
+    fn path(&self) -> &Path {
+        self.inner.path()
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Error> {
+        self.encryption.decrypt(self.inner.read()?, self.plaintext)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), Error> {
+        let sealed = self.encryption.encrypt(data)?;
+        self.inner.write(&sealed)
+    }
+}
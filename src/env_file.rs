@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::diagnostics::ParseError;
+use crate::manager::line_column;
+use crate::flat::{flatten, insert, Entry};
+use crate::Error;
+
+/// The separator of nested keys, so `SERVER__PORT` sets the `port` field of the `server` struct.
+const SEPARATOR: &str = "__";
+
+/// Parses a `.env` file. Keys are lowercased, so `MAX_CONNECTIONS` sets the `max_connections` field, and nested at `__`.
+/// Values may be quoted with double quotes, which understand escapes like `\n`, or with single quotes, which are taken literally. Lines may start with `export`.
+pub(crate) fn parse(data: &str) -> Result<Entry, ParseError> {
+    let error = |message: &str, offset: usize| {
+        let (line, column) = line_column(data, offset);
+        ParseError { message: message.to_string(), line, column }
+    };
+    // The offset of a part of the file, for the location of errors.
+    let offset = |part: &str| part.as_ptr() as usize - data.as_ptr() as usize;
+    let mut root = BTreeMap::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (line, next) = rest.split_once('\n').unwrap_or((rest, ""));
+        rest = next;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(error("expected `KEY=value`", offset(line)));
+        };
+        let key = key.trim_end();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(error("invalid key", offset(key)));
+        }
+        let value = value.trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                // A quoted value may continue on the following lines, so it is read from the rest of the file.
+                let quoted = offset(value);
+                let body = &data[quoted + 1..];
+                let Some(end) = closing_quote(body, quote) else {
+                    return Err(error("the quoted value is not closed", quoted));
+                };
+                let after = &body[end + 1..];
+                let (trailing, next) = after.split_once('\n').unwrap_or((after, ""));
+                let trailing = trailing.trim();
+                if !trailing.is_empty() && !trailing.starts_with('#') {
+                    return Err(error("unexpected characters after the quoted value", quoted + end + 2));
+                }
+                rest = next;
+                match quote {
+                    '"' => unescape(&body[..end]),
+                    _ => body[..end].to_string(),
+                }
+            }
+            _ => match value.find(" #") {
+                Some(comment) => value[..comment].trim_end().to_string(),
+                None => value.to_string(),
+            },
+        };
+        insert(&mut root, &key.to_lowercase(), SEPARATOR, value).map_err(|message| error(&message, offset(key)))?;
+    }
+    Ok(Entry::Table(root))
+}
+
+/// Returns the byte offset of the quote closing a value, skipping escaped double quotes.
+fn closing_quote(body: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in body.char_indices() {
+        match c {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            c if c == quote && !escaped => return Some(i),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some(c @ ('"' | '\\' | '$')) => unescaped.push(c),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Writes the generic representation of a config as a `.env` file, with uppercased keys and nested keys joined by `__`.
+/// Lists of values are written separated by commas. Lists of lists or tables cannot be written.
+pub(crate) fn to_string(value: &Value) -> Result<String, Error> {
+    let mut out = String::new();
+    for (key, value) in flatten(value, SEPARATOR, "a .env file")? {
+        write_value(&mut out, &key.to_uppercase(), &value);
+    }
+    Ok(out)
+}
+
+fn write_value(out: &mut String, key: &str, value: &str) {
+    out.push_str(key);
+    out.push('=');
+    if value.chars().all(|c| c.is_ascii_alphanumeric() || "_-.,:/@+".contains(c)) {
+        out.push_str(value);
+    } else {
+        out.push('"');
+        for c in value.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '$' => out.push_str("\\$"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+    out.push('\n');
+}
@@ -0,0 +1,158 @@
+use std::io::{BufRead, BufReader};
+use std::sync::Mutex;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{Backend, Error};
+
+/// A [`Backend`] keeping the config in a key of etcd, so the instances of a clustered service share one config.
+/// It talks to the JSON gateway of the etcd v3 API, which etcd serves on its client port, so no gRPC stack is needed.
+/// With the `tokio` feature, [`crate::Config::watch_backend`] uses an etcd watch on the key to reload the config as soon as another instance saves it.
+///
+/// # Example
+/// ```no_run
+/// use opzioni::{Config, Etcd, Format};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// let etcd = Etcd::new("http://127.0.0.1:2379", "/myapp/config");
+/// let config = Config::<MyConfig>::configure().create_if_missing().load_from_backend(etcd, Format::Json).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Etcd {
+    endpoint: String,
+    key: String,
+    token: Option<String>,
+    revision: Mutex<i64>,
+}
+
+impl Etcd {
+    /// Creates a backend for the given key of the etcd server at `endpoint`, like `http://127.0.0.1:2379`.
+    pub fn new(endpoint: &str, key: &str) -> Self {
+        Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            key: key.to_string(),
+            token: None,
+            revision: Mutex::new(0),
+        }
+    }
+
+    /// Sends the given authentication token with every request, for etcd servers with authentication enabled.
+    /// The token is returned by the `/v3/auth/authenticate` endpoint of etcd.
+    pub fn token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    fn post(&self, endpoint: &str, body: serde_json::Value) -> Result<ureq::Response, Error> {
+        let mut request = ureq::post(&format!("{}/v3/{}", self.endpoint, endpoint));
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", token);
+        }
+        Ok(request.send_json(body)?)
+    }
+
+    /// Remembers the revision of etcd the backend has seen, so a watch only reports later changes.
+    fn seen(&self, header: &Header) {
+        if let Ok(revision) = header.revision.parse() {
+            *self.revision.lock().unwrap() = revision;
+        }
+    }
+}
+
+impl Backend for Etcd {
+    fn read(&self) -> Result<Option<String>, Error> {
+        let response: RangeResponse = self.post("kv/range", json!({ "key": STANDARD.encode(&self.key) }))?.into_json()?;
+        self.seen(&response.header);
+        match response.kvs.into_iter().next() {
+            Some(kv) => {
+                let value = STANDARD.decode(kv.value).map_err(Error::parse)?;
+                String::from_utf8(value).map(Some).map_err(Error::parse)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn write(&self, data: &str) -> Result<(), Error> {
+        let response: PutResponse = self.post("kv/put", json!({ "key": STANDARD.encode(&self.key), "value": STANDARD.encode(data) }))?.into_json()?;
+        self.seen(&response.header);
+        Ok(())
+    }
+
+    fn wait_for_change(&self) -> Result<(), Error> {
+        let start = *self.revision.lock().unwrap() + 1;
+        let response = self.post("watch", json!({ "create_request": { "key": STANDARD.encode(&self.key), "start_revision": start.to_string() } }))?;
+        // The gateway streams one JSON message per line for as long as the watch is open.
+        for line in BufReader::new(response.into_reader()).lines() {
+            let message: WatchMessage = serde_json::from_str(&line?)?;
+            if let Some(error) = message.error {
+                return Err(Error::load(error.message));
+            }
+            if let Some(result) = message.result {
+                if result.canceled || !result.events.is_empty() {
+                    self.seen(&result.header);
+                    return Ok(());
+                }
+            }
+        }
+        Err(Error::load("the etcd watch was closed"))
+    }
+}
+
+// etcd encodes 64 bit integers as strings and leaves out fields with default values.
+
+#[derive(Deserialize, Default)]
+struct Header {
+    #[serde(default)]
+    revision: String,
+}
+
+#[derive(Deserialize)]
+struct KeyValue {
+    #[serde(default)]
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct RangeResponse {
+    #[serde(default)]
+    header: Header,
+    #[serde(default)]
+    kvs: Vec<KeyValue>,
+}
+
+#[derive(Deserialize)]
+struct PutResponse {
+    #[serde(default)]
+    header: Header,
+}
+
+#[derive(Deserialize)]
+struct WatchMessage {
+    result: Option<WatchResult>,
+    error: Option<WatchError>,
+}
+
+#[derive(Deserialize)]
+struct WatchResult {
+    #[serde(default)]
+    header: Header,
+    #[serde(default)]
+    canceled: bool,
+    #[serde(default)]
+    events: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct WatchError {
+    #[serde(default)]
+    message: String,
+}
@@ -0,0 +1,189 @@
+#[cfg(any(feature = "toml", feature = "yaml"))]
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::{Describe, Error, Format};
+
+/// Writes the default config to the given file, with the descriptions of its fields as comments, see [`crate::Config::write_example`].
+pub(crate) fn write<T>(path: &Path) -> Result<(), Error>
+    where T: serde::Serialize + Default + Describe {
+    let format = Format::from_path(path).map_err(|err| err.at(path, None))?;
+    let data = format.serialize(&T::default()).map_err(|err| err.at(path, Some(format)))?;
+    let data = match std::str::from_utf8(&data) {
+        Ok(text) => annotate(format, text, T::describe).map(String::into_bytes).unwrap_or(data),
+        Err(_) => data,
+    };
+    std::fs::write(path, data).map_err(|err| Error::from(err).at(path, Some(format)))
+}
+
+/// Puts the descriptions of the keys above them as comments, for the formats which have comments. Returns `None` for the other formats.
+#[allow(unused_variables)]
+fn annotate(format: Format, text: &str, describe: fn(&str) -> Option<&'static str>) -> Option<String> {
+    match format {
+        #[cfg(feature = "toml")]
+        Format::Toml => Some(toml(text, describe)),
+        #[cfg(feature = "yaml")]
+        Format::Yaml => Some(yaml(text, describe)),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// Annotates TOML written by the toml serializer, which puts keys at the start of their line and nested structs into `[table]` sections.
+#[cfg(feature = "toml")]
+fn toml(text: &str, describe: fn(&str) -> Option<&'static str>) -> String {
+    let mut annotated = Comments::new(describe);
+    let mut table = String::new();
+    let mut multiline = false;
+    for line in text.lines() {
+        // Lines of multi-line strings and arrays are indented or inside quotes, so they never look like keys.
+        let path = match line.strip_prefix('[') {
+            _ if multiline || line.starts_with(char::is_whitespace) => None,
+            Some(header) => {
+                table = unquote_path(header.trim_start_matches('[').trim_end().trim_end_matches(']'));
+                Some(table.clone())
+            }
+            None => line.split_once('=').map(|(key, _)| join(&table, &unquote(key.trim()))),
+        };
+        if let Some(path) = path {
+            annotated.describe(&path, "");
+        }
+        multiline ^= line.matches("\"\"\"").count() % 2 == 1 || line.matches("'''").count() % 2 == 1;
+        annotated.line(line);
+    }
+    annotated.text
+}
+
+/// Annotates YAML written by serde_yaml, which indents the keys of nested maps and puts the elements of sequences at the indentation of their key.
+/// The keys of maps inside sequences are not annotated, because the same description would be repeated for every element.
+#[cfg(feature = "yaml")]
+fn yaml(text: &str, describe: fn(&str) -> Option<&'static str>) -> String {
+    let mut annotated = Comments::new(describe);
+    let mut keys: Vec<(usize, String)> = Vec::new();
+    let mut sequence: Option<usize> = None;
+    let mut block: Option<usize> = None;
+    for line in text.lines() {
+        let content = line.trim_start();
+        let indent = line.len() - content.len();
+        if let Some(key_indent) = block {
+            if indent > key_indent || content.is_empty() {
+                annotated.line(line);
+                continue;
+            }
+            block = None;
+        }
+        if let Some(sequence_indent) = sequence {
+            if indent > sequence_indent || (indent == sequence_indent && content.starts_with('-')) {
+                annotated.line(line);
+                continue;
+            }
+            sequence = None;
+        }
+        if content.starts_with('-') {
+            sequence = Some(indent);
+            annotated.line(line);
+            continue;
+        }
+        if let Some((key, value)) = split_yaml_key(content) {
+            while keys.last().is_some_and(|(key_indent, _)| *key_indent >= indent) {
+                keys.pop();
+            }
+            let parent = keys.iter().map(|(_, key)| key.as_str()).collect::<Vec<_>>().join(".");
+            annotated.describe(&join(&parent, &key), &line[..indent]);
+            if value.starts_with('|') || value.starts_with('>') {
+                block = Some(indent);
+            }
+            keys.push((indent, key));
+        }
+        annotated.line(line);
+    }
+    annotated.text
+}
+
+/// Splits a line of a YAML map into its unquoted key and its value.
+#[cfg(feature = "yaml")]
+fn split_yaml_key(content: &str) -> Option<(String, &str)> {
+    let (key, rest) = match content.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let end = content[1..].find(quote)? + 1;
+            (unquote(&content[..=end]), &content[end + 1..])
+        }
+        _ => {
+            let end = content.find(": ").or_else(|| content.ends_with(':').then(|| content.len() - 1))?;
+            (content[..end].to_string(), &content[end..])
+        }
+    };
+    Some((key, rest.strip_prefix(':')?.trim_start()))
+}
+
+/// Collects the annotated lines and remembers which keys were described, so keys repeated in arrays of tables are only described once.
+#[cfg(any(feature = "toml", feature = "yaml"))]
+struct Comments {
+    describe: fn(&str) -> Option<&'static str>,
+    described: HashSet<String>,
+    text: String,
+}
+
+#[cfg(any(feature = "toml", feature = "yaml"))]
+impl Comments {
+    fn new(describe: fn(&str) -> Option<&'static str>) -> Self {
+        Self { describe, described: HashSet::new(), text: String::new() }
+    }
+
+    /// Adds the description of the key at the given path as comment lines with the given indentation.
+    fn describe(&mut self, path: &str, indent: &str) {
+        let Some(description) = (self.describe)(path) else {
+            return;
+        };
+        if !self.described.insert(path.to_string()) {
+            return;
+        }
+        for line in description.lines() {
+            self.text.push_str(indent);
+            self.text.push('#');
+            if !line.is_empty() {
+                self.text.push(' ');
+                self.text.push_str(line);
+            }
+            self.text.push('\n');
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        self.text.push_str(line);
+        self.text.push('\n');
+    }
+}
+
+#[cfg(any(feature = "toml", feature = "yaml"))]
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) }
+}
+
+/// Removes the quotes around a key, like `"my key"`.
+#[cfg(any(feature = "toml", feature = "yaml"))]
+fn unquote(key: &str) -> String {
+    let quoted = key.len() >= 2 && ((key.starts_with('"') && key.ends_with('"')) || (key.starts_with('\'') && key.ends_with('\'')));
+    match quoted {
+        true => key[1..key.len() - 1].to_string(),
+        false => key.to_string(),
+    }
+}
+
+/// Removes the quotes around the keys of a dotted path, like `servers."eu west"`.
+#[cfg(feature = "toml")]
+fn unquote_path(path: &str) -> String {
+    let mut keys = Vec::new();
+    let mut key = String::new();
+    let mut quote = None;
+    for c in path.chars() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('.', None) => keys.push(std::mem::take(&mut key).trim().to_string()),
+            (c, _) => key.push(c),
+        }
+    }
+    keys.push(key.trim().to_string());
+    keys.join(".")
+}
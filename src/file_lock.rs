@@ -0,0 +1,65 @@
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use fs2::FileExt;
+
+use crate::Error;
+
+/// An exclusive advisory lock on a config file, returned by [`crate::Config::lock_exclusive`].
+/// While the lock is held, other processes using opzioni can neither load nor save the config file, so a read-modify-write cycle like reload, change and save cannot be interleaved with theirs.
+/// The config which returned the lock can still be reloaded and saved. The lock is released when it is dropped.
+#[derive(Debug)]
+pub struct FileLock<'a> {
+    file: File,
+    held: &'a AtomicBool,
+}
+
+impl<'a> FileLock<'a> {
+    pub(crate) fn acquire(path: &Path, held: &'a AtomicBool) -> Result<Self, Error> {
+        if held.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            return Err(Error::AlreadyLocked);
+        }
+        match acquire(path, true) {
+            Ok(file) => Ok(Self { file, held }),
+            Err(err) => {
+                held.store(false, Ordering::Release);
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Drop for FileLock<'_> {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+        self.held.store(false, Ordering::Release);
+    }
+}
+
+/// Returns the path of the lock file belonging to the given config file, like `config.toml.lock`.
+/// A separate file is locked, because saving may replace the config file itself.
+fn lock_path(path: &Path) -> PathBuf {
+    let mut lock = path.as_os_str().to_owned();
+    lock.push(".lock");
+    lock.into()
+}
+
+/// Blocks until the lock on the given config file is acquired. The lock is released when the returned file is closed.
+pub(crate) fn acquire(path: &Path, exclusive: bool) -> Result<File, Error> {
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(lock_path(path))?;
+    if exclusive {
+        FileExt::lock_exclusive(&file)?;
+    } else {
+        FileExt::lock_shared(&file)?;
+    }
+    Ok(file)
+}
+
+/// Acquires the lock on the given config file unless the config already holds it through a [`FileLock`].
+pub(crate) fn acquire_unless_held(path: &Path, exclusive: bool, held: &AtomicBool) -> Result<Option<File>, Error> {
+    if held.load(Ordering::Acquire) {
+        return Ok(None);
+    }
+    acquire(path, exclusive).map(Some)
+}
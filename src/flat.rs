@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+
+use serde::de::value::{Error as DeError, MapDeserializer, SeqDeserializer};
+use serde::de::{Error as _, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::Value;
+
+use crate::diagnostics::{report, Diagnostic, ParseError};
+use crate::Error;
+
+/// The values of a file of flat `key=value` pairs, like a `.env` or a `.properties` file, nested by splitting the keys at a separator.
+/// The values are kept as strings and parsed into the types the config asks for while deserializing.
+#[derive(Debug, Clone)]
+pub(crate) enum Entry {
+    Value(String),
+    Table(BTreeMap<String, Entry>),
+}
+
+/// Inserts a value at a key which is split into nested tables at the separator. Later values replace earlier ones.
+/// Returns an error message if a key is set both as a value and as a table.
+pub(crate) fn insert(table: &mut BTreeMap<String, Entry>, key: &str, separator: &str, value: String) -> Result<(), String> {
+    match key.split_once(separator) {
+        Some((head, rest)) => match table.entry(head.to_string()).or_insert_with(|| Entry::Table(BTreeMap::new())) {
+            Entry::Table(table) => insert(table, rest, separator, value),
+            Entry::Value(_) => Err(format!("`{}` is set both as a value and as a table", head)),
+        },
+        None => match table.insert(key.to_string(), Entry::Value(value)) {
+            Some(Entry::Table(_)) => Err(format!("`{}` is set both as a value and as a table", key)),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Flattens the generic representation of a config into keys joined with the separator and their values as strings, the reverse of [`insert`].
+/// Lists of values are joined with commas. Lists of lists or tables cannot be flattened. `file` names the kind of file in error messages.
+pub(crate) fn flatten(value: &Value, separator: &str, file: &str) -> Result<Vec<(String, String)>, Error> {
+    let Value::Object(object) = value else {
+        return Err(Error::serialize(format!("{} can only hold tables", file)));
+    };
+    let mut pairs = Vec::new();
+    flatten_table(&mut pairs, "", object, separator, file)?;
+    Ok(pairs)
+}
+
+fn flatten_table(pairs: &mut Vec<(String, String)>, prefix: &str, table: &serde_json::Map<String, Value>, separator: &str, file: &str) -> Result<(), Error> {
+    for (key, value) in table {
+        let key = format!("{}{}", prefix, key);
+        let value = match value {
+            Value::Object(table) => {
+                flatten_table(pairs, &format!("{}{}", key, separator), table, separator, file)?;
+                continue;
+            }
+            Value::Array(items) => items.iter().map(|item| match item {
+                Value::String(item) if !item.contains(',') => Ok(item.clone()),
+                Value::Bool(_) | Value::Number(_) => Ok(item.to_string()),
+                _ => Err(Error::serialize(format!("the list `{}` cannot be written to {}, only lists of numbers, booleans and strings without commas can", key, file))),
+            }).collect::<Result<Vec<_>, _>>()?.join(","),
+            Value::Null => String::new(),
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        pairs.push((key, value));
+    }
+    Ok(())
+}
+
+/// Reports everything which is wrong with a parsed file as a config of type `T`.
+pub(crate) fn diagnose<T>(entry: Result<Entry, ParseError>) -> Vec<Diagnostic>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    use serde::Deserialize;
+
+    match entry {
+        Ok(entry) => {
+            let invalid = |err: DeError| Diagnostic::invalid(err.to_string(), None);
+            report::<T>(Value::deserialize(entry.clone()).map_err(invalid), T::deserialize(entry).err().map(invalid))
+        }
+        Err(err) => vec![err.into()],
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for Entry {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, DeError>
+                where V: Visitor<'de> {
+                match self {
+                    Entry::Value(value) => match value.trim().parse() {
+                        Ok(parsed) => visitor.$visit(parsed),
+                        Err(_) => Err(DeError::invalid_value(serde::de::Unexpected::Str(&value), &visitor)),
+                    },
+                    table => table.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+/// Deserializes the string values into the types the config asks for. Where the config accepts any type, like [`Value`], numbers and booleans are recognized and everything else stays a string.
+impl<'de> serde::Deserializer<'de> for Entry {
+    type Error = DeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, DeError>
+        where V: Visitor<'de> {
+        match self {
+            Entry::Value(value) => {
+                if let Ok(value) = value.parse::<bool>() {
+                    visitor.visit_bool(value)
+                } else if let Ok(value) = value.parse::<i64>() {
+                    visitor.visit_i64(value)
+                } else if let Ok(value) = value.parse::<u64>() {
+                    visitor.visit_u64(value)
+                } else if value.contains(|c: char| c.is_ascii_digit()) && value.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c)) && value.parse::<f64>().is_ok() {
+                    visitor.visit_f64(value.parse().unwrap_or_default())
+                } else {
+                    visitor.visit_string(value)
+                }
+            }
+            Entry::Table(table) => visitor.visit_map(MapDeserializer::new(table.into_iter())),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, DeError>
+        where V: Visitor<'de> {
+        match self {
+            Entry::Value(value) => visitor.visit_string(value),
+            table => table.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, DeError>
+        where V: Visitor<'de> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, DeError>
+        where V: Visitor<'de> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, DeError>
+        where V: Visitor<'de> {
+        match self {
+            Entry::Value(value) if value.is_empty() => visitor.visit_none(),
+            entry => visitor.visit_some(entry),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, DeError>
+        where V: Visitor<'de> {
+        match self {
+            Entry::Value(value) => {
+                let items = value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(|item| Entry::Value(item.to_string()));
+                visitor.visit_seq(SeqDeserializer::new(items))
+            }
+            table => table.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, DeError>
+        where V: Visitor<'de> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value, DeError>
+        where V: Visitor<'de> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, DeError>
+        where V: Visitor<'de> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, DeError>
+        where V: Visitor<'de> {
+        match self {
+            Entry::Value(value) => visitor.visit_enum(value.into_deserializer()),
+            table => table.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct map struct identifier ignored_any
+    }
+}
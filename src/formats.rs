@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::{Arc, RwLock};
+
+use crate::{Error, Value};
+
+/// A file format which is not built into opzioni, added at runtime with [`FormatRegistry::register`].
+/// A loader converts between the bytes of a file and the generic representation of a config, which opzioni then converts into the config type.
+///
+/// # Example
+/// ```
+/// use opzioni::{Config, Error, FormatLoader, FormatRegistry, Value};
+/// use serde::{Serialize, Deserialize};
+///
+/// /// `key value` pairs, one per line.
+/// #[derive(Debug)]
+/// struct Conf;
+///
+/// impl FormatLoader for Conf {
+///     fn parse(&self, data: &[u8]) -> Result<Value, Error> {
+///         let text = std::str::from_utf8(data).map_err(Error::parse)?;
+///         let pairs = text.lines().filter_map(|line| line.split_once(' '));
+///         Ok(Value::Object(pairs.map(|(key, value)| (key.to_string(), value.parse().unwrap_or_else(|_| Value::from(value)))).collect()))
+///     }
+///
+///     fn serialize(&self, value: &Value) -> Result<Vec<u8>, Error> {
+///         let pairs = value.as_object().ok_or_else(|| Error::serialize("not a table"))?;
+///         Ok(pairs.iter().map(|(key, value)| format!("{} {}\n", key, value.as_str().map(String::from).unwrap_or(value.to_string()))).collect::<String>().into_bytes())
+///     }
+/// }
+///
+/// #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// FormatRegistry::register("conf", Conf);
+///
+/// let path = std::env::temp_dir().join("opzioni-registry.conf");
+/// std::fs::write(&path, "name John\nage 42\n").unwrap();
+/// let config = Config::<MyConfig>::configure().load(&path).unwrap();
+/// assert!(format!("{:?}", config).contains("age: 42"));
+/// ```
+pub trait FormatLoader: Debug + Send + Sync {
+    /// Parses the data of a file into the generic representation of a config.
+    fn parse(&self, data: &[u8]) -> Result<Value, Error>;
+
+    /// Serializes the generic representation of a config into the data of a file.
+    fn serialize(&self, value: &Value) -> Result<Vec<u8>, Error>;
+}
+
+/// The formats registered with [`FormatRegistry::register`], by extension.
+static LOADERS: RwLock<BTreeMap<&'static str, Arc<dyn FormatLoader>>> = RwLock::new(BTreeMap::new());
+
+/// The formats added at runtime, so applications and other crates can support file formats without a feature of opzioni.
+/// The registered extensions are consulted before the built-in ones when detecting the format of a file, so a registered loader may also replace a built-in format.
+/// Files of a registered format are loaded like every other file and use [`crate::Format::Custom`] with their extension.
+#[derive(Debug)]
+pub struct FormatRegistry;
+
+impl FormatRegistry {
+    /// Registers a loader for files with the given extension, without the leading dot. Like the built-in extensions it is matched regardless of its case.
+    /// A loader registered earlier for the same extension is replaced.
+    pub fn register(extension: &str, loader: impl FormatLoader + 'static) {
+        let extension = extension.to_ascii_lowercase();
+        let mut loaders = LOADERS.write().unwrap_or_else(|err| err.into_inner());
+        // The extension is kept by `Format::Custom`, which is `Copy`, so it is leaked once per extension.
+        let extension = match loaders.keys().find(|&&registered| registered == extension) {
+            Some(&registered) => registered,
+            None => Box::leak(extension.into_boxed_str()),
+        };
+        loaders.insert(extension, Arc::new(loader));
+    }
+
+    /// Removes the loader registered for the given extension. Returns whether there was one.
+    pub fn unregister(extension: &str) -> bool {
+        LOADERS.write().unwrap_or_else(|err| err.into_inner()).remove(extension.to_ascii_lowercase().as_str()).is_some()
+    }
+
+    /// Returns whether a loader is registered for the given extension.
+    pub fn contains(extension: &str) -> bool {
+        find(extension).is_some()
+    }
+}
+
+/// Returns the registered extension and its loader.
+pub(crate) fn find(extension: &str) -> Option<(&'static str, Arc<dyn FormatLoader>)> {
+    let loaders = LOADERS.read().unwrap_or_else(|err| err.into_inner());
+    loaders.get_key_value(extension.to_ascii_lowercase().as_str()).map(|(&extension, loader)| (extension, loader.clone()))
+}
+
+/// Returns the loader registered for an extension, or the error for an unknown extension.
+pub(crate) fn loader(extension: &str) -> Result<Arc<dyn FormatLoader>, Error> {
+    find(extension).map(|(_, loader)| loader).ok_or_else(|| Error::UnknownFileExtension { path: None, extension: Some(extension.to_string()) })
+}
+
+/// Returns the registered extensions, in alphabetical order.
+pub(crate) fn extensions() -> Vec<&'static str> {
+    LOADERS.read().unwrap_or_else(|err| err.into_inner()).keys().copied().collect()
+}
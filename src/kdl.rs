@@ -0,0 +1,535 @@
+use std::collections::HashSet;
+
+use serde_json::{Map, Number, Value};
+
+use crate::Error;
+use crate::diagnostics::ParseError;
+
+/// Parses a KDL document into the generic representation of a config.
+/// Every node becomes a key of an object. A node with a single argument holds that value, a node with several arguments or an `(array)` annotation holds an array, and a node with properties or children holds an object.
+/// Children which are all named `-` are the items of an array, and nodes which are repeated are collected into an array.
+pub(crate) fn parse(data: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser { data, pos: 0 };
+    let nodes = parser.nodes(false)?;
+    let mut object = Map::new();
+    parser.insert(&mut object, nodes)?;
+    Ok(Value::Object(object))
+}
+
+/// Writes the generic representation of a config as a KDL document, the reverse of [`parse`].
+/// Only objects can be written, because a document is a list of nodes. Keys are written in alphabetical order.
+pub(crate) fn to_string(value: &Value) -> Result<String, Error> {
+    let Value::Object(object) = value else {
+        return Err(Error::serialize("KDL documents can only hold objects"));
+    };
+    let mut out = String::new();
+    for (key, value) in object {
+        write_node(&mut out, 0, key, value);
+    }
+    Ok(out)
+}
+
+struct Node {
+    name: String,
+    annotation: Option<String>,
+    args: Vec<Value>,
+    props: Map<String, Value>,
+    children: Option<Vec<Node>>,
+    position: usize,
+}
+
+struct Parser<'a> {
+    data: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.data[self.pos..].chars().next()
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        self.data[self.pos..].starts_with(prefix)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, message: &str, position: usize) -> ParseError {
+        let (line, column) = crate::manager::line_column(self.data, position);
+        ParseError { message: message.to_string(), line, column }
+    }
+
+    /// Adds the values of the nodes to the object.
+    fn insert(&self, object: &mut Map<String, Value>, nodes: Vec<Node>) -> Result<(), ParseError> {
+        let mut repeated = HashSet::new();
+        for node in nodes {
+            let name = node.name.clone();
+            let value = self.convert(node)?;
+            match object.get_mut(&name) {
+                Some(Value::Array(items)) if repeated.contains(&name) => items.push(value),
+                Some(existing) => {
+                    *existing = Value::Array(vec![existing.take(), value]);
+                    repeated.insert(name);
+                }
+                None => {
+                    object.insert(name, value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts a node into the value it holds.
+    fn convert(&self, node: Node) -> Result<Value, ParseError> {
+        let Node { annotation, mut args, mut props, children, position, .. } = node;
+        let items = children.as_ref().is_some_and(|children| !children.is_empty() && children.iter().all(|child| child.name == "-"));
+        if annotation.as_deref() == Some("array") || items {
+            if !props.is_empty() {
+                return Err(self.error("an array cannot have properties", position));
+            }
+            for child in children.unwrap_or_default() {
+                args.push(self.convert(child)?);
+            }
+            return Ok(Value::Array(args));
+        }
+        match (args.len(), props.is_empty() && children.is_none()) {
+            (0, true) => Ok(Value::Null),
+            (1, true) => Ok(args.remove(0)),
+            (_, true) => Ok(Value::Array(args)),
+            (0, false) => {
+                self.insert(&mut props, children.unwrap_or_default())?;
+                Ok(Value::Object(props))
+            }
+            _ => Err(self.error("a node cannot have arguments together with properties or children", position)),
+        }
+    }
+
+    /// Parses the nodes of the document or, if `nested`, of a children block up to its closing brace.
+    fn nodes(&mut self, nested: bool) -> Result<Vec<Node>, ParseError> {
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_lines()?;
+            match self.peek() {
+                None if nested => return Err(self.error("the children block is not closed", self.pos)),
+                None => return Ok(nodes),
+                Some('}') if nested => {
+                    self.bump();
+                    return Ok(nodes);
+                }
+                Some('}') => return Err(self.error("unexpected `}`", self.pos)),
+                Some(';') => {
+                    self.bump();
+                }
+                Some(_) => {
+                    let ignored = self.slashdash()?;
+                    let node = self.node()?;
+                    if !ignored {
+                        nodes.push(node);
+                    }
+                }
+            }
+        }
+    }
+
+    fn node(&mut self) -> Result<Node, ParseError> {
+        let position = self.pos;
+        let annotation = self.annotation()?;
+        let name = match self.peek() {
+            Some('"') => self.string()?,
+            Some('r') if self.raw_string_start() => self.raw_string()?,
+            _ => self.identifier()?,
+        };
+        let mut node = Node { name, annotation, args: Vec::new(), props: Map::new(), children: None, position };
+        loop {
+            let spaced = self.skip_spaces()?;
+            match self.peek() {
+                None | Some('}') => return Ok(node),
+                Some(';') => {
+                    self.bump();
+                    return Ok(node);
+                }
+                Some(c) if is_newline(c) || self.starts_with("//") => return Ok(node),
+                Some('{') => {
+                    self.bump();
+                    node.children = Some(self.nodes(true)?);
+                    self.skip_spaces()?;
+                    return match self.peek() {
+                        None | Some('}') => Ok(node),
+                        Some(';') => {
+                            self.bump();
+                            Ok(node)
+                        }
+                        Some(c) if is_newline(c) || self.starts_with("//") => Ok(node),
+                        Some(_) => Err(self.error("expected the end of the node after its children", self.pos)),
+                    };
+                }
+                Some(_) if !spaced => return Err(self.error("expected whitespace before the argument", self.pos)),
+                Some(_) => {
+                    let ignored = self.slashdash()?;
+                    if ignored && self.peek() == Some('{') {
+                        self.bump();
+                        self.nodes(true)?;
+                    } else {
+                        self.entry(&mut node, ignored)?;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parses an argument or a property of a node.
+    fn entry(&mut self, node: &mut Node, ignored: bool) -> Result<(), ParseError> {
+        let position = self.pos;
+        self.annotation()?;
+        let token = match self.peek() {
+            Some(c) if self.number_start(c) => Token::Value(self.number()?),
+            Some('"') => Token::Name(self.string()?),
+            Some('r') if self.raw_string_start() => Token::Name(self.raw_string()?),
+            _ => Token::Keyword(self.identifier()?),
+        };
+        if self.peek() == Some('=') {
+            let key = match token {
+                Token::Name(key) | Token::Keyword(key) => key,
+                Token::Value(_) => return Err(self.error("a property name cannot be a number", position)),
+            };
+            self.bump();
+            self.annotation()?;
+            let value = self.value()?;
+            if !ignored && node.props.insert(key, value).is_some() {
+                return Err(self.error("the node has this property twice", position));
+            }
+        } else {
+            let value = match token {
+                Token::Value(value) => value,
+                Token::Name(name) => Value::String(name),
+                Token::Keyword(keyword) => self.keyword(&keyword, position)?,
+            };
+            if !ignored {
+                node.args.push(value);
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        let position = self.pos;
+        match self.peek() {
+            Some(c) if self.number_start(c) => self.number(),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('r') if self.raw_string_start() => Ok(Value::String(self.raw_string()?)),
+            _ => {
+                let keyword = self.identifier()?;
+                self.keyword(&keyword, position)
+            }
+        }
+    }
+
+    fn keyword(&self, keyword: &str, position: usize) -> Result<Value, ParseError> {
+        match keyword {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            "null" => Ok(Value::Null),
+            _ => Err(self.error("expected a value, strings have to be quoted", position)),
+        }
+    }
+
+    /// Parses a type annotation like `(array)`, which only has a meaning in front of nodes.
+    fn annotation(&mut self) -> Result<Option<String>, ParseError> {
+        if self.peek() != Some('(') {
+            return Ok(None);
+        }
+        self.bump();
+        let annotation = match self.peek() {
+            Some('"') => self.string()?,
+            Some('r') if self.raw_string_start() => self.raw_string()?,
+            _ => self.identifier()?,
+        };
+        if self.bump() != Some(')') {
+            return Err(self.error("the type annotation is not closed", self.pos));
+        }
+        Ok(Some(annotation))
+    }
+
+    fn identifier(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !is_identifier_char(c) {
+                break;
+            }
+            self.bump();
+        }
+        if self.pos == start {
+            return Err(self.error("expected a name or a value", start));
+        }
+        Ok(self.data[start..self.pos].to_string())
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.bump();
+        let mut string = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error("the string is not closed", start)),
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let escape = self.pos;
+                    let c = match self.bump() {
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some(c @ ('\\' | '/' | '"')) => c,
+                        Some('u') if self.peek() == Some('{') => {
+                            self.bump();
+                            let digits = self.pos;
+                            while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                                self.bump();
+                            }
+                            let code = u32::from_str_radix(&self.data[digits..self.pos], 16).ok().and_then(char::from_u32);
+                            match (code, self.bump()) {
+                                (Some(c), Some('}')) => c,
+                                _ => return Err(self.error("invalid unicode escape", escape)),
+                            }
+                        }
+                        _ => return Err(self.error("invalid escape", escape)),
+                    };
+                    string.push(c);
+                }
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    fn raw_string_start(&self) -> bool {
+        self.data[self.pos + 1..].trim_start_matches('#').starts_with('"')
+    }
+
+    fn raw_string(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.bump();
+        let mut hashes = 0;
+        while self.peek() == Some('#') {
+            self.bump();
+            hashes += 1;
+        }
+        self.bump();
+        let end = format!("\"{}", "#".repeat(hashes));
+        match self.data[self.pos..].find(&end) {
+            Some(length) => {
+                let string = self.data[self.pos..self.pos + length].to_string();
+                self.pos += length + end.len();
+                Ok(string)
+            }
+            None => Err(self.error("the string is not closed", start)),
+        }
+    }
+
+    fn number_start(&self, c: char) -> bool {
+        c.is_ascii_digit() || (matches!(c, '+' | '-') && self.data[self.pos + 1..].starts_with(|c: char| c.is_ascii_digit()))
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '+' | '-')) {
+            self.bump();
+        }
+        let number = self.data[start..self.pos].replace('_', "");
+        let (negative, digits) = match number.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, number.strip_prefix('+').unwrap_or(&number)),
+        };
+        let radix = match digits.get(..2) {
+            Some("0x") => Some(16),
+            Some("0o") => Some(8),
+            Some("0b") => Some(2),
+            _ => None,
+        };
+        let value = match radix {
+            Some(radix) => i64::from_str_radix(&digits[2..], radix).ok().map(|value| if negative { -value } else { value }).map(Number::from),
+            None if digits.contains(['.', 'e', 'E']) => number.parse::<f64>().ok().and_then(Number::from_f64),
+            None => number.parse::<i64>().map(Number::from).ok().or_else(|| number.trim_start_matches('+').parse::<u64>().ok().map(Number::from)),
+        };
+        value.map(Value::Number).ok_or_else(|| self.error("invalid number", start))
+    }
+
+    /// Consumes a `/-`, which comments out the following node, argument, property or children block.
+    fn slashdash(&mut self) -> Result<bool, ParseError> {
+        if !self.starts_with("/-") {
+            return Ok(false);
+        }
+        self.pos += 2;
+        self.skip_spaces()?;
+        Ok(true)
+    }
+
+    /// Skips whitespace, block comments and escaped line breaks within a node. Returns whether anything was skipped.
+    fn skip_spaces(&mut self) -> Result<bool, ParseError> {
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() && !is_newline(c) || c == '\u{feff}' => {
+                    self.bump();
+                }
+                Some('/') if self.starts_with("/*") => self.block_comment()?,
+                Some('\\') => {
+                    let escape = self.pos;
+                    self.bump();
+                    while self.peek().is_some_and(|c| c.is_whitespace() && !is_newline(c)) {
+                        self.bump();
+                    }
+                    if self.starts_with("//") {
+                        self.line_comment();
+                    }
+                    match self.bump() {
+                        Some(c) if is_newline(c) => {}
+                        None => {}
+                        Some(_) => return Err(self.error("expected a line break after `\\`", escape)),
+                    }
+                }
+                _ => return Ok(self.pos > start),
+            }
+        }
+    }
+
+    /// Skips whitespace, comments and line breaks between nodes.
+    fn skip_lines(&mut self) -> Result<(), ParseError> {
+        loop {
+            self.skip_spaces()?;
+            match self.peek() {
+                Some(c) if is_newline(c) => {
+                    self.bump();
+                }
+                Some('/') if self.starts_with("//") => self.line_comment(),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn line_comment(&mut self) {
+        while self.peek().is_some_and(|c| !is_newline(c)) {
+            self.bump();
+        }
+    }
+
+    fn block_comment(&mut self) -> Result<(), ParseError> {
+        let start = self.pos;
+        let mut depth = 0;
+        loop {
+            if self.starts_with("/*") {
+                self.pos += 2;
+                depth += 1;
+            } else if self.starts_with("*/") {
+                self.pos += 2;
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+            } else if self.bump().is_none() {
+                return Err(self.error("the comment is not closed", start));
+            }
+        }
+    }
+}
+
+enum Token {
+    Value(Value),
+    Name(String),
+    Keyword(String),
+}
+
+fn is_newline(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\u{85}' | '\u{c}' | '\u{2028}' | '\u{2029}')
+}
+
+fn is_identifier_char(c: char) -> bool {
+    !c.is_whitespace() && !c.is_control() && !matches!(c, '\\' | '/' | '(' | ')' | '{' | '}' | '<' | '>' | ';' | '[' | ']' | '=' | ',' | '"' | '\u{feff}')
+}
+
+fn write_node(out: &mut String, depth: usize, name: &str, value: &Value) {
+    let indent = "    ".repeat(depth);
+    out.push_str(&indent);
+    if value.is_array() {
+        out.push_str("(array)");
+    }
+    write_name(out, name);
+    match value {
+        Value::Array(items) if items.iter().all(is_scalar) => {
+            for item in items {
+                out.push(' ');
+                write_scalar(out, item);
+            }
+            out.push('\n');
+        }
+        Value::Array(items) => {
+            out.push_str(" {\n");
+            for item in items {
+                write_node(out, depth + 1, "-", item);
+            }
+            out.push_str(&indent);
+            out.push_str("}\n");
+        }
+        Value::Object(object) if object.is_empty() => out.push_str(" {}\n"),
+        Value::Object(object) => {
+            out.push_str(" {\n");
+            for (key, value) in object {
+                write_node(out, depth + 1, key, value);
+            }
+            out.push_str(&indent);
+            out.push_str("}\n");
+        }
+        scalar => {
+            out.push(' ');
+            write_scalar(out, scalar);
+            out.push('\n');
+        }
+    }
+}
+
+fn is_scalar(value: &Value) -> bool {
+    !value.is_array() && !value.is_object()
+}
+
+/// Writes a node name, quoting it if it is no valid identifier.
+fn write_name(out: &mut String, name: &str) {
+    let mut chars = name.chars();
+    let quoted = match (chars.next(), chars.next()) {
+        (None, _) => true,
+        (Some(first), _) if first.is_ascii_digit() => true,
+        (Some('+' | '-'), Some(second)) => second.is_ascii_digit(),
+        _ => false,
+    };
+    if quoted || !name.chars().all(is_identifier_char) || matches!(name, "true" | "false" | "null") {
+        write_string(out, name);
+    } else {
+        out.push_str(name);
+    }
+}
+
+fn write_scalar(out: &mut String, value: &Value) {
+    match value {
+        Value::String(string) => write_string(out, string),
+        value => out.push_str(&value.to_string()),
+    }
+}
+
+fn write_string(out: &mut String, string: &str) {
+    out.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
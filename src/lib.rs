@@ -2,87 +2,749 @@
 //! It is designed to be easy to use and to provide a good user experience.
 //! It uses serde for serialization and deserialization.
 //! The currently supported formats are JSON, TOML and YAML.
+//!
+//! # Features
+//! The typed [`Config`] with its builder, the [`Clock`] and the [`Secret`] wrapper are always available. They only depend on serde and use the std `RwLock`.
+//! Every build needs at least one format feature, because a config without a format can neither be loaded nor saved.
+//! Everything else is behind a cargo feature. Most subsystems which work on the generic representation of a config, like migrations, includes, profiles, section files and environment overrides, also need `json`, as listed below:
+//!
+//! - `json`, `toml`, `yaml`: the supported file formats. `all` enables all of them and is the default.
+//! - `msgpack`: the MessagePack binary format, for compact configs which are generated and read by programs. It is not part of `all`.
+//! - `cbor`: the CBOR binary format, for embedded and IoT deployments which already use it. It is not part of `all`.
+//! - `bincode`: the bincode binary format, for local state which needs the fastest loading and saving. It is selected with [`ConfigBuilder::format`] instead of a file extension and is not part of `all`.
+//! - `hcl`: the HashiCorp configuration language, so tools around Terraform and other HashiCorp products can load their existing `.hcl` and `.tf` files. It is not part of `all`.
+//! - `kdl`: the KDL document language used by CLI tools like zellij, for `.kdl` files. It is not part of `all` and enables `json`.
+//! - `env-file`: loads and saves `.env` files, for simple flat configs. Keys are lowercased to match the fields and `__` separates the keys of nested structs, so `SERVER__PORT=8080` sets `server.port`. It is not part of `all` and enables `json`.
+//! - `properties`: loads and saves Java `.properties` files, for services migrated from the JVM. Dotted keys like `server.port` set the fields of nested structs. It is not part of `all` and enables `json`.
+//! - `plist`: Apple property lists, so macOS apps can keep their configs in `.plist` files. XML and binary property lists are loaded, binary ones are saved with `Format::BinaryPlist`. It is not part of `all`.
+//! - `tokio`: makes [`Config`] use the tokio `RwLock`, turns saving async and adds autosaving in the background. The blocking config stays available as [`blocking::Config`] and the async one is also exported as [`sync::Config`], so crates using either flavor can be mixed in one build.
+//! - `async-std`, `smol`: make [`Config`] async like `tokio` does, for applications on the async-std or smol runtime. They use the `RwLock` of async-lock and spawn the background tasks on their runtime. If several of them are enabled, tokio is preferred over async-std and async-std over smol.
+//! - `rcu`: adds [`RcuConfig`], loaded with `ConfigBuilder::load_rcu`, whose readers take cheap `Arc` snapshots of the config instead of locking it, for read-heavy services. Writers publish new versions.
+//! - `miette`: implements `miette::Diagnostic` for [`Error`], so parse errors are reported with the line of the config file they occurred in. See [`Error::render`] for the same without miette.
+//! - `tracing`: logs loading and saving with the tracing crate. Logged configs mask every [`Secret`] and every field marked with `#[opzioni(secret)]`.
+//! - `json` also enables the features which work on the generic representation of a config file, like [`Migrations`], strict parsing with `deny_unknown_fields`, completing files with `merge_defaults`, controlling how files are merged with [`MergeStrategy`], expanding environment variables with `expand_env`, overriding fields with the environment variables named after their serialized keys with `env_prefix` and [`EnvOptions`], composing files with `allow_includes`, keeping sections in their own files with `map_section`, selecting a `profile`, sorting keys with `SaveOptions::sort_keys` and comparing configs with [`diff`]. It also enables [`FormatRegistry`], which adds file formats at runtime.
+//! - `gzip`, `zstd`: loads and saves compressed config files, detected from a second extension like `config.json.gz` or `config.toml.zst`.
+//! - `toml-edit`: keeps comments, formatting and key order of existing TOML files when saving, only the changed values are replaced.
+//! - `yaml-order`: keeps the key order of existing YAML files when saving, so diffs after a save stay minimal. Comments and anchors are still lost.
+//! - `file-lock`: locks config files while loading and saving, so several processes sharing a config file do not overwrite each other's changes. See [`Config::lock_exclusive`].
+//! - `etcd`: adds the [`Etcd`] backend, which keeps a config in a key of etcd so the instances of a clustered service share it. See [`Backend`] for keeping configs in other stores.
+//! - `consul`: adds the [`Consul`] backend, which keeps a config in the Consul KV store and coordinates the instances sharing it with session locks.
+//! - `registry`: adds the `Registry` backend on Windows, which keeps a config in a key of the Windows registry, with fields as values and nested structs as subkeys. It enables `properties` and does nothing on other platforms.
+//! - `sqlite`: adds the [`Sqlite`] backend, which keeps a config in a table of a SQLite database and rejects saves over changes of other connections.
+//! - `object-storage`: adds the [`ObjectStorage`] backend, which keeps a config in an object of Amazon S3, Google Cloud Storage or an S3 compatible store and rejects saves over changes of other instances using the ETag of the object.
+//! - `derive`: adds `#[derive(Opzioni)]`, which implements [`Describe`] and [`Opzioni`] from attributes on the fields of a config struct.
+//! - `keyring`: keeps [`Secret`] fields in the credential store of the operating system instead of the config file, see [`ConfigBuilder::use_keyring`].
+//! - `encryption`: encrypts config files with AES-256-GCM, so tokens stored in them are protected at rest. See [`ConfigBuilder::with_encryption_key`].
+//! - `schemars`: exports the JSON Schema of config types which implement `schemars::JsonSchema`, so editors can validate and complete config files. See [`Config::json_schema`] and [`ConfigBuilder::write_schema`], and check files against the schema with [`validate_file`]. It enables `json`.
+//! - `clap`: overrides fields of a config with the arguments given on the command line, on top of the config file and the environment variables. See [`ConfigBuilder::with_cli_overrides`]. It enables `json`.
+//! - `figment`: implements `figment::Provider` for [`Config`], and adds figment providers as layers over the config file with [`ConfigBuilder::merge_provider`], so projects using figment can move to opzioni step by step.
+//! - `config-rs`: builds a [`Config`] from a `config::Config` of the config-rs crate with [`ConfigBuilder::load_from_config_rs`], so projects using config-rs can keep their sources while moving to opzioni.
+//! - `vault`: resolves references like `vault:secret/myapp/db#password` in config files with the secrets of a HashiCorp Vault server while loading, see [`Vault`]. Saving writes the references back, so the secrets are never written to the file. It enables `json`.
+//! - `checksum`: keeps a SHA-256 checksum next to config files and verifies it on load, so files corrupted or truncated by flaky storage are detected. See [`ConfigBuilder::with_checksum`].
+//! - `zeroize`: wipes [`Secret`] values and serialized config buffers from memory.
+//!
+//! The smallest build enables a single format, like `opzioni = { version = "3", default-features = false, features = ["toml"] }`. It compiles without `json`, but leaves out the subsystems which need it.
+//! The minimum supported Rust version is 1.71.
+//!
+//! # Dynamic configs
+//! Applications which do not know the layout of their config at compile time can use [`Value`] as the config type. It holds any config file regardless of its format and behaves like every other config type when loading, saving and locking.
+//! The values are accessed with [`Config::get_value`] and [`Config::set_value`]. The default of a [`Value`] config is null, so [`ConfigBuilder::create_if_missing`] only works with formats which can represent it, like JSON and YAML.
+//!
+//! ```
+//! use opzioni::{Config, Value};
+//! use std::path::Path;
+//!
+//! let config = Config::<Value>::configure().load(Path::new("testconfig.json")).unwrap();
+//! assert!(format!("{:?}", config).contains(r#"String("John")"#));
+//! ```
 #![deny(missing_docs)]
 
 use std::{
     fmt::Display,
+    path::{Path, PathBuf},
 };
 
 mod manager;
 mod config;
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+mod runtime;
+mod clock;
+mod secret;
+mod describe;
+mod example;
+mod attributes;
+mod search;
+mod backend;
+#[cfg(feature = "json")]
+mod migrations;
+#[cfg(feature = "json")]
+mod value;
+#[cfg(feature = "json")]
+mod diagnostics;
+#[cfg(feature = "json")]
+mod diff;
+#[cfg(any(feature = "json", feature = "yaml"))]
+mod documents;
+#[cfg(feature = "json")]
+mod formats;
+#[cfg(feature = "json")]
+mod split;
+#[cfg(any(feature = "toml-edit", feature = "yaml-order"))]
+mod preserve;
+#[cfg(feature = "file-lock")]
+mod file_lock;
+#[cfg(feature = "etcd")]
+mod etcd;
+#[cfg(feature = "consul")]
+mod consul;
+#[cfg(feature = "vault")]
+mod vault;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "object-storage")]
+mod object_storage;
+#[cfg(feature = "keyring")]
+mod credentials;
+#[cfg(feature = "encryption")]
+mod encryption;
+#[cfg(feature = "checksum")]
+mod checksum;
+#[cfg(feature = "schemars")]
+mod schema;
+#[cfg(feature = "clap")]
+mod cli;
+#[cfg(feature = "figment")]
+mod provider;
+#[cfg(feature = "kdl")]
+mod kdl;
+#[cfg(feature = "env-file")]
+mod env_file;
+#[cfg(feature = "properties")]
+mod properties;
+#[cfg(any(feature = "env-file", feature = "properties"))]
+mod flat;
+#[cfg(feature = "rcu")]
+mod rcu;
+#[cfg(feature = "miette")]
+mod report;
+#[cfg(all(windows, feature = "registry"))]
+mod registry;
+
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use manager::{Format, SaveOptions};
+pub use search::SearchPaths;
+pub use backend::Backend;
+pub use describe::Describe;
+pub use attributes::Opzioni;
+/// Derives [`Describe`] and [`Opzioni`] for a config struct from the attributes on its fields. See [`Opzioni`] for the supported attributes.
+///
+/// # Example
+/// ```
+/// use opzioni::{Config, Opzioni, Secret};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, Clone, Opzioni)]
+/// struct Server {
+///   /// The port the server listens on
+///   #[opzioni(default = "8080", env = "MYAPP_PORT", min = 1)]
+///   port: u16,
+///   #[opzioni(secret)]
+///   token: Secret<String>,
+/// }
+///
+/// #[derive(Serialize, Deserialize, Default, Clone, Debug, Opzioni)]
+/// struct MyConfig {
+///   /// Settings of the HTTP server
+///   #[opzioni(nested)]
+///   server: Server,
+/// }
+///
+/// assert_eq!(Server::default().port, 8080);
+/// assert_eq!(Config::<MyConfig>::describe("server.port"), Some("The port the server listens on"));
+/// assert!(MyConfig::default().check().is_ok());
+/// assert_eq!(MyConfig::secrets(), vec!["server.token"]);
+/// ```
+#[cfg(feature = "derive")]
+pub use opzioni_derive::Opzioni;
+pub use secret::{Secret, SecretValue};
+#[cfg(feature = "file-lock")]
+pub use file_lock::FileLock;
+#[cfg(feature = "etcd")]
+pub use etcd::Etcd;
+#[cfg(feature = "consul")]
+pub use consul::{Consul, ConsulLock};
+#[cfg(feature = "vault")]
+pub use vault::Vault;
+#[cfg(feature = "sqlite")]
+pub use sqlite::Sqlite;
+#[cfg(feature = "object-storage")]
+pub use object_storage::ObjectStorage;
+#[cfg(feature = "rcu")]
+pub use rcu::RcuConfig;
+#[cfg(all(windows, feature = "registry"))]
+pub use registry::Registry;
+#[cfg(feature = "json")]
+pub use migrations::Migrations;
+#[cfg(feature = "json")]
+pub use diagnostics::{Diagnostic, DiagnosticKind};
+#[cfg(feature = "json")]
+pub use diff::{diff, Change};
+#[cfg(feature = "schemars")]
+pub use schema::validate_file;
+#[cfg(feature = "json")]
+pub use formats::{FormatLoader, FormatRegistry};
+#[cfg(feature = "json")]
+pub use value::{EnvCase, EnvOptions, MergeStrategy, UnsetVariable};
+/// The format-agnostic representation of a config file, see [Dynamic configs](crate#dynamic-configs).
+#[cfg(feature = "json")]
+pub use serde_json::Value;
 
 #[cfg(feature = "tracing")]
 #[macro_use]
 extern crate tracing;
 
-#[cfg(not(feature = "tokio"))]
-type Lock<T> = std::sync::RwLock<T>;
-
-#[cfg(feature = "tokio")]
-type Lock<T> = tokio::sync::RwLock<T>;
-
 /// The Error enum contains all possible errors that can occur while loading or saving a config file.
+/// New variants may be added in minor releases, so matches on it need a wildcard arm.
+/// The errors which occur with a config file carry its path and its format, which are also part of their message. See [`Error::path`] and [`Error::format`].
+/// The errors of parsers, serializers and the file system are kept as the [`std::error::Error::source`] of the error.
+///
+/// # Example
+/// ```
+/// use opzioni::{blocking::Config, Error, Format};
+/// use serde::{Serialize, Deserialize};
+/// use std::error::Error as _;
+///
+/// #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// let path = std::env::temp_dir().join("opzioni-error.json");
+/// std::fs::write(&path, r#"{ "name": "John", "age": "old" }"#).unwrap();
+/// let err = Config::<MyConfig>::configure().load(&path).unwrap_err();
+/// assert!(matches!(err, Error::ParseError { .. }));
+/// assert_eq!(err.path(), Some(path.as_path()));
+/// assert_eq!(err.format(), Some(Format::Json));
+/// assert!(err.to_string().contains("opzioni-error.json (JSON): invalid type"));
+/// assert!(err.source().unwrap().to_string().contains("invalid type"));
+/// ```
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
-    /// This error occurs when the config file could not be loaded. It contains an optional error message.
-    ConfigLoadError(Option<String>),
-    /// This error occurs when the file extension of the config file is not supported. It contains an optional error message.
-    UnknownFileExtension(Option<String>),
-    /// This error occurs when serializing or deserializing the config fails. It contains an optional error message.
-    SerializationError(Option<String>),
+    /// This error occurs when the config file could not be loaded, for example because an include or a profile is missing. It contains an optional error message.
+    ConfigLoadError {
+        /// The path of the config file.
+        path: Option<PathBuf>,
+        /// The format of the config file.
+        format: Option<Format>,
+        /// What went wrong.
+        message: Option<String>,
+    },
+    /// This error occurs when the file extension of the config file is not supported.
+    UnknownFileExtension {
+        /// The path of the config file.
+        path: Option<PathBuf>,
+        /// The extension, or `None` if the file has none.
+        extension: Option<String>,
+    },
+    /// This error occurs when the config file or the data of a backend cannot be parsed into the config. It contains the error of the parser and where it occurred, if the parser reports it.
+    /// [`Error::render`] shows the line of the file with the error.
+    ParseError {
+        /// The path of the config file.
+        path: Option<PathBuf>,
+        /// The format of the config file.
+        format: Option<Format>,
+        /// The 1-based line of the error.
+        line: Option<usize>,
+        /// The 1-based column of the error.
+        column: Option<usize>,
+        /// The line of the config file with the error, without its line break.
+        snippet: Option<String>,
+        /// The error of the parser.
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// This error occurs when the config cannot be written in its format, for example because the format cannot hold one of its values. It contains the error of the serializer.
+    SerializeError {
+        /// The path of the config file.
+        path: Option<PathBuf>,
+        /// The format of the config file.
+        format: Option<Format>,
+        /// The error of the serializer.
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// This error occurs when the config file or a backend cannot be read or written. It contains the underlying error.
+    IoError {
+        /// The path of the config file.
+        path: Option<PathBuf>,
+        /// The format of the config file.
+        format: Option<Format>,
+        /// The error of the file system or the backend.
+        source: std::io::Error,
+    },
+    /// This error occurs when a config is saved while a save of the same config is already in progress, for example from a hook triggered by the first save.
+    ReentrantSave,
+    /// This error occurs when the validator set with [`ConfigBuilder::validate`] rejects the config while loading or saving. It contains the message of the validator.
+    ValidationError {
+        /// The path of the config file.
+        path: Option<PathBuf>,
+        /// The format of the config file.
+        format: Option<Format>,
+        /// The message of the validator.
+        message: String,
+    },
+    /// This error occurs when the lock of a config was poisoned by a panicking thread and `PoisonPolicy::Error` is set. Only the std version of [`Config`] has poisoned locks.
+    Poisoned,
+    /// This error occurs when [`Config::lock_exclusive`] is called while the same config already holds the lock.
+    #[cfg(feature = "file-lock")]
+    AlreadyLocked,
+    /// This error occurs when [`ConfigBuilder::detect_conflicts`] is set and the config file was changed by someone else since it was loaded or saved.
+    /// Backends which check for changes themselves, like the `Sqlite` backend, also return it, regardless of [`ConfigBuilder::detect_conflicts`].
+    Conflict {
+        /// The path of the config file.
+        path: Option<PathBuf>,
+        /// The format of the config file.
+        format: Option<Format>,
+    },
+    /// This error occurs when a config without a file or [`Backend`], like one created with [`Config::in_memory`], is saved, reloaded or locked. Use [`Config::attach`] to give it a file.
+    NoBackingFile,
+    /// This error occurs when the config file is empty. Set [`ConfigBuilder::treat_empty_as_missing`] to handle empty files like missing ones instead.
+    EmptyFile {
+        /// The path of the config file.
+        path: Option<PathBuf>,
+        /// The format of the config file.
+        format: Option<Format>,
+    },
+    /// This error occurs when [`ConfigBuilder::with_checksum`] is set and the config file does not match the checksum saved with it, because it was corrupted, truncated or changed without opzioni.
+    /// It also occurs when [`ConfigBuilder::with_encryption_key`] is set and the config file is not encrypted, unless [`ConfigBuilder::allow_plaintext_migration`] is set.
+    #[cfg(any(feature = "checksum", feature = "encryption"))]
+    IntegrityError {
+        /// The path of the config file.
+        path: Option<PathBuf>,
+        /// The format of the config file.
+        format: Option<Format>,
+        /// The SHA-256 checksum saved with the file, in hex, or `encrypted` for a file which is not encrypted.
+        expected: String,
+        /// The SHA-256 checksum of the file, in hex, or `plain text` for a file which is not encrypted.
+        actual: String,
+    },
+    /// This error occurs when an old config file could not be upgraded by the registered [`Migrations`]. It contains an error message.
+    #[cfg(feature = "json")]
+    MigrationError {
+        /// The path of the config file.
+        path: Option<PathBuf>,
+        /// The format of the config file.
+        format: Option<Format>,
+        /// What went wrong.
+        message: String,
+    },
+    /// This error occurs when [`ConfigBuilder::deny_unknown_fields`] is set and the config file contains keys the config type does not know.
+    #[cfg(feature = "json")]
+    UnknownFields {
+        /// The path of the config file.
+        path: Option<PathBuf>,
+        /// The format of the config file.
+        format: Option<Format>,
+        /// The dotted paths of the unknown keys.
+        keys: Vec<String>,
+    },
+    /// This error occurs when a dotted path like `server.port` does not lead to a value of the config. It contains an error message.
+    #[cfg(feature = "json")]
+    InvalidPath(String),
+}
+
+impl Error {
+    /// Creates a [`Error::ConfigLoadError`] with a message, for example in a [`Backend`]. opzioni adds the path and the format of the config file when the error reaches it.
+    pub fn load(message: impl Into<String>) -> Self {
+        Error::ConfigLoadError { path: None, format: None, message: Some(message.into()) }
+    }
+
+    /// Creates a [`Error::ParseError`], for example in a custom format or a [`Backend`]. opzioni adds the path and the format of the config file when the error reaches it.
+    pub fn parse(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Error::ParseError { path: None, format: None, line: None, column: None, snippet: None, source: source.into() }
+    }
+
+    /// Creates a [`Error::ParseError`] at the given 1-based line and column.
+    #[cfg(any(feature = "json", feature = "toml", feature = "yaml", feature = "kdl", feature = "env-file", feature = "properties"))]
+    pub(crate) fn parse_at(source: impl Into<Box<dyn std::error::Error + Send + Sync>>, line: usize, column: usize) -> Self {
+        Error::ParseError { path: None, format: None, line: Some(line), column: Some(column), snippet: None, source: source.into() }
+    }
+
+    /// Creates a [`Error::SerializeError`] like [`Error::parse`].
+    pub fn serialize(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Error::SerializeError { path: None, format: None, source: source.into() }
+    }
+
+    /// Creates a [`Error::IoError`] like [`Error::parse`].
+    pub fn io(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Error::IoError { path: None, format: None, source: std::io::Error::new(std::io::ErrorKind::Other, source) }
+    }
+
+    /// Returns the path of the config file the error occurred with, if it is known.
+    pub fn path(&self) -> Option<&Path> {
+        self.location().0.as_deref()
+    }
+
+    /// Returns the format of the config file the error occurred with, if it is known.
+    pub fn format(&self) -> Option<Format> {
+        self.location().1
+    }
+
+    /// Returns the path and the format of the errors which occur with a config file. The other errors have neither.
+    fn location(&self) -> (&Option<PathBuf>, Option<Format>) {
+        match self {
+            Error::ConfigLoadError { path, format, .. }
+            | Error::ParseError { path, format, .. }
+            | Error::SerializeError { path, format, .. }
+            | Error::IoError { path, format, .. }
+            | Error::ValidationError { path, format, .. }
+            | Error::Conflict { path, format }
+            | Error::EmptyFile { path, format } => (path, *format),
+            #[cfg(any(feature = "checksum", feature = "encryption"))]
+            Error::IntegrityError { path, format, .. } => (path, *format),
+            #[cfg(feature = "json")]
+            Error::MigrationError { path, format, .. } | Error::UnknownFields { path, format, .. } => (path, *format),
+            Error::UnknownFileExtension { path, .. } => (path, None),
+            _ => (&None, None),
+        }
+    }
+
+    /// Adds the path and the format of the config file to an error which does not know them yet. Configs without a file use an empty path, which is not added.
+    pub(crate) fn at(mut self, file: &Path, detected: Option<Format>) -> Self {
+        let (path, format) = match &mut self {
+            Error::ConfigLoadError { path, format, .. }
+            | Error::ParseError { path, format, .. }
+            | Error::SerializeError { path, format, .. }
+            | Error::IoError { path, format, .. }
+            | Error::ValidationError { path, format, .. }
+            | Error::Conflict { path, format }
+            | Error::EmptyFile { path, format } => (path, Some(format)),
+            #[cfg(any(feature = "checksum", feature = "encryption"))]
+            Error::IntegrityError { path, format, .. } => (path, Some(format)),
+            #[cfg(feature = "json")]
+            Error::MigrationError { path, format, .. } | Error::UnknownFields { path, format, .. } => (path, Some(format)),
+            Error::UnknownFileExtension { path, .. } => (path, None),
+            _ => return self,
+        };
+        if path.is_none() && !file.as_os_str().is_empty() {
+            *path = Some(file.to_path_buf());
+        }
+        if let Some(format) = format {
+            *format = format.or(detected);
+        }
+        self
+    }
+
+    /// Adds the line of the data a [`Error::ParseError`] points to, so [`Error::render`] can show it.
+    pub(crate) fn excerpt(mut self, data: &[u8]) -> Self {
+        if let Error::ParseError { line: Some(line), snippet: snippet @ None, .. } = &mut self {
+            *snippet = std::str::from_utf8(data).ok().zip(line.checked_sub(1)).and_then(|(text, index)| text.lines().nth(index)).map(str::to_string);
+        }
+        self
+    }
+
+    /// Renders the error with the line of the config file it points to and a caret under its column, like compilers show their errors.
+    /// Errors which do not point to a line are rendered like their [`Display`] output. The line is a copy of the file, so it is only shown here and not in the [`Display`] output, which ends up in logs more easily.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::blocking::Config;
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-render.toml");
+    /// std::fs::write(&path, "name = \"John\"\nage = \"old\"\n").unwrap();
+    /// let err = Config::<MyConfig>::configure().load(&path).unwrap_err();
+    /// let rendered = err.render();
+    /// assert!(rendered.ends_with(" --> opzioni-render.toml:2:7\n  |\n2 | age = \"old\"\n  |       ^\n".replace("opzioni-render.toml", &path.display().to_string()).as_str()));
+    /// ```
+    pub fn render(&self) -> String {
+        let Error::ParseError { path, line: Some(line), column, snippet: Some(snippet), .. } = self else {
+            return self.to_string();
+        };
+        let number = line.to_string();
+        let gutter = " ".repeat(number.len());
+        let mut rendered = format!("{}\n{}--> ", self, gutter);
+        if let Some(path) = path {
+            rendered.push_str(&format!("{}:", path.display()));
+        }
+        rendered.push_str(&number);
+        if let Some(column) = column {
+            rendered.push_str(&format!(":{}", column));
+        }
+        rendered.push_str(&format!("\n{} |\n{} | {}\n", gutter, number, snippet));
+        if let Some(column) = column {
+            // Tabs are kept, so the caret lines up with the text above it.
+            let indent = snippet.chars().take(column.saturating_sub(1)).map(|c| if c == '\t' { '\t' } else { ' ' }).collect::<String>();
+            rendered.push_str(&format!("{} | {}^\n", gutter, indent));
+        }
+        rendered
+    }
+
+    /// Returns the message of the parser of a [`Error::ParseError`].
+    /// Parsers which show the offending line themselves, like toml, only contribute their message, followed by the line and the column like the messages of the other parsers.
+    fn parser_message(&self) -> String {
+        match self {
+            #[cfg(feature = "toml")]
+            Error::ParseError { line: Some(line), column: Some(column), source, .. } if source.is::<toml::de::Error>() => {
+                let message = source.downcast_ref::<toml::de::Error>().map(toml::de::Error::message).unwrap_or_default();
+                format!("{} at line {} column {}", message.trim_end(), line, column)
+            }
+            Error::ParseError { source, .. } => source.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Returns where the error occurred for its message, like `config.json (JSON): `. It is empty if neither the path nor the format is known.
+    fn location_prefix(&self) -> String {
+        match self.location() {
+            (Some(path), Some(format)) => format!("{} ({}): ", path.display(), format),
+            (Some(path), None) => format!("{}: ", path.display()),
+            (None, Some(format)) => format!("{}: ", format),
+            (None, None) => String::new(),
+        }
+    }
+
+    /// Turns a [`Error::ParseError`] into a [`Error::SerializeError`], for the errors of libraries which use one error type in both directions, like serde_json.
+    pub(crate) fn serializing(self) -> Self {
+        match self {
+            Error::ParseError { path, format, source, .. } => Error::SerializeError { path, format, source },
+            err => err,
+        }
+    }
 }
 
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
-        Error::SerializationError(Some(err.to_string()))
+        Error::IoError { path: None, format: None, source: err }
     }
 }
 
 #[cfg(feature = "json")]
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
-        Error::SerializationError(Some(err.to_string()))
+        // Errors which do not come from parsing text, like the ones of `from_value`, have no line.
+        match (err.line(), err.column()) {
+            (0, _) => Error::parse(err),
+            (line, column) => Error::parse_at(err, line, column),
+        }
     }
 }
 
 #[cfg(feature = "toml")]
 impl From<toml::de::Error> for Error {
     fn from(err: toml::de::Error) -> Self {
-        Error::SerializationError(Some(err.to_string()))
+        Error::parse(err)
     }
 }
 
 #[cfg(feature = "toml")]
 impl From<toml::ser::Error> for Error {
     fn from(err: toml::ser::Error) -> Self {
-        Error::SerializationError(Some(err.to_string()))
+        Error::serialize(err)
     }
 }
 
 #[cfg(feature = "yaml")]
 impl From<serde_yaml::Error> for Error {
     fn from(err: serde_yaml::Error) -> Self {
-        Error::SerializationError(Some(err.to_string()))
+        match err.location() {
+            Some(location) => Error::parse_at(err, location.line(), location.column()),
+            None => Error::parse(err),
+        }
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl From<rmp_serde::decode::Error> for Error {
+    fn from(err: rmp_serde::decode::Error) -> Self {
+        Error::parse(err)
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl From<rmp_serde::encode::Error> for Error {
+    fn from(err: rmp_serde::encode::Error) -> Self {
+        Error::serialize(err)
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl From<ciborium::de::Error<std::io::Error>> for Error {
+    fn from(err: ciborium::de::Error<std::io::Error>) -> Self {
+        Error::parse(err)
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl From<ciborium::ser::Error<std::io::Error>> for Error {
+    fn from(err: ciborium::ser::Error<std::io::Error>) -> Self {
+        Error::serialize(err)
+    }
+}
+
+#[cfg(feature = "bincode")]
+impl From<bincode::Error> for Error {
+    fn from(err: bincode::Error) -> Self {
+        Error::parse(err)
+    }
+}
+
+#[cfg(any(feature = "env-file", feature = "properties"))]
+impl From<serde::de::value::Error> for Error {
+    fn from(err: serde::de::value::Error) -> Self {
+        Error::parse(err)
+    }
+}
+
+#[cfg(feature = "plist")]
+impl From<plist::Error> for Error {
+    fn from(err: plist::Error) -> Self {
+        Error::parse(err)
+    }
+}
+
+#[cfg(feature = "hcl")]
+impl From<hcl::Error> for Error {
+    fn from(err: hcl::Error) -> Self {
+        Error::parse(err)
+    }
+}
+
+#[cfg(any(feature = "etcd", feature = "consul", feature = "vault", feature = "object-storage"))]
+impl From<ureq::Error> for Error {
+    fn from(err: ureq::Error) -> Self {
+        Error::io(err)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::io(err)
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl From<keyring::Error> for Error {
+    fn from(err: keyring::Error) -> Self {
+        Error::io(err)
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let location = self.location_prefix();
+        match self {
+            Error::ConfigLoadError { message: Some(msg), .. } => write!(f, "opzioni::ConfigLoadError: {}{}", location, msg),
+            Error::ConfigLoadError { message: None, .. } => write!(f, "opzioni::ConfigLoadError: {}the config could not be loaded", location),
+            Error::UnknownFileExtension { extension: Some(extension), .. } => write!(f, "opzioni::UnknownFileExtension: {}no enabled format uses the extension {}", location, extension),
+            Error::UnknownFileExtension { extension: None, .. } => write!(f, "opzioni::UnknownFileExtension: {}the file has no extension", location),
+            Error::ParseError { .. } => write!(f, "opzioni::ParseError: {}{}", location, self.parser_message()),
+            Error::SerializeError { source, .. } => write!(f, "opzioni::SerializeError: {}{}", location, source),
+            Error::IoError { source, .. } => write!(f, "opzioni::IoError: {}{}", location, source),
+            Error::ReentrantSave => write!(f, "opzioni::ReentrantSave: the config is already being saved"),
+            Error::Poisoned => write!(f, "opzioni::Poisoned: a thread panicked while changing the config"),
+            Error::ValidationError { message, .. } => write!(f, "opzioni::ValidationError: {}{}", location, message),
+            #[cfg(feature = "file-lock")]
+            Error::AlreadyLocked => write!(f, "opzioni::AlreadyLocked: the config file is already locked by this config"),
+            Error::Conflict { .. } => write!(f, "opzioni::Conflict: {}the config file was changed since it was loaded", location),
+            Error::NoBackingFile => write!(f, "opzioni::NoBackingFile: the config has no file"),
+            Error::EmptyFile { .. } => write!(f, "opzioni::EmptyFile: {}the config file is empty", location),
+            #[cfg(feature = "encryption")]
+            Error::IntegrityError { expected, actual, .. } if actual == encryption::PLAINTEXT => write!(f, "opzioni::IntegrityError: {}the config file is {} instead of {}", location, actual, expected),
+            #[cfg(any(feature = "checksum", feature = "encryption"))]
+            Error::IntegrityError { expected, actual, .. } => write!(f, "opzioni::IntegrityError: {}the checksum of the config file is {} instead of {}", location, actual, expected),
+            #[cfg(feature = "json")]
+            Error::MigrationError { message, .. } => write!(f, "opzioni::MigrationError: {}{}", location, message),
+            #[cfg(feature = "json")]
+            Error::UnknownFields { keys, .. } => write!(f, "opzioni::UnknownFields: {}{}", location, keys.join(", ")),
+            #[cfg(feature = "json")]
+            Error::InvalidPath(msg) => write!(f, "opzioni::InvalidPath: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            Error::ConfigLoadError(Some(msg)) => write!(f, "opzioni::ConfigLoadError: {}", msg),
-            Error::ConfigLoadError(None) => write!(f, "opzioni::ConfigLoadError"),
-            Error::UnknownFileExtension(Some(msg)) => write!(f, "opzioni::UnknownFileExtension: {}", msg),
-            Error::UnknownFileExtension(None) => write!(f, "opzioni::UnknownFileExtension"),
-            Error::SerializationError(Some(msg)) => write!(f, "opzioni::SerializationError: {}", msg),
-            Error::SerializationError(None) => write!(f, "opzioni::SerializationError"),
+            Error::ParseError { source, .. } | Error::SerializeError { source, .. } => Some(source.as_ref()),
+            Error::IoError { source, .. } => Some(source),
+            _ => None,
         }
     }
 }
 
-/// See [`config::sync::Config`]
-#[cfg(feature = "tokio")]
-pub type Config<T> = config::sync::Config<T>;
+/// The blocking config on the std `RwLock`. It is part of every build, so a library using it keeps compiling when an application enables `tokio`, `async-std` or `smol`.
+/// [`Config`] stays an alias of the flavor selected by the features, for applications which only use one of them.
+///
+/// # Example
+/// ```
+/// use opzioni::blocking::Config;
+/// use serde::{Serialize, Deserialize};
+/// use std::path::Path;
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// // Blocking regardless of the features enabled by other crates in the build.
+/// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+/// assert_eq!(config.read().age, 42);
+/// ```
+pub mod blocking {
+    pub use crate::config::std::{Config, ConfigBuilder, ConfigRegistry, LoadOutcome, Section};
+}
+
+/// The async config, enabled by the `tokio`, `async-std` and `smol` features. It can be used next to [`crate::blocking`] in the same build.
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+pub mod sync {
+    pub use crate::config::sync::{Config, ConfigBuilder, ConfigRegistry, LoadOutcome, Section, TaskHandle};
+}
+
+/// The async config if `tokio`, `async-std` or `smol` is enabled, the blocking config otherwise. See [`sync::Config`] and [`blocking::Config`].
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+pub type Config<T> = sync::Config<T>;
+
+/// See [`Config`]
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+pub type ConfigBuilder<T> = sync::ConfigBuilder<T>;
+
+/// See [`Config`]
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+pub type LoadOutcome<T> = sync::LoadOutcome<T>;
+
+/// See [`Config::section`]
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+pub type Section<T, S> = sync::Section<T, S>;
+
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+pub use sync::{ConfigRegistry, TaskHandle};
+
+/// The async config if `tokio`, `async-std` or `smol` is enabled, the blocking config otherwise. See [`blocking::Config`].
+#[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+pub type Config<T> = blocking::Config<T>;
+
+/// See [`Config`]
+#[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+pub type ConfigBuilder<T> = blocking::ConfigBuilder<T>;
+
+/// See [`Config`]
+#[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+pub type LoadOutcome<T> = blocking::LoadOutcome<T>;
+
+/// See [`Config::section`]
+#[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+pub type Section<T, S> = blocking::Section<T, S>;
+
+#[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+pub use blocking::ConfigRegistry;
 
-/// See [`config::std::Config`]
-#[cfg(not(feature = "tokio"))]
-pub type Config<T> = config::std::Config<T>;
+pub use config::{PoisonPolicy, Recovery, Skipped};
@@ -1,152 +1,1032 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::Error;
 
-pub(crate) fn for_file<T>(path: &Path) -> Result<Box<dyn ConfigManager<T>>, Error>
-    where
-        T: serde::Serialize + serde::de::DeserializeOwned + Default,
-{
-    match path.extension() {
-        Some(ext) => match ext.to_str() {
+/// The file formats supported by opzioni. Which variants exist depends on the enabled features.
+/// Usually the format is detected from the file extension, see [`Format::from_path`].
+/// The enum is non-exhaustive, because enabling a format feature anywhere in the dependency graph adds variants, so matches on it need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Format {
+    /// JSON, used for files ending in `.json`.
+    #[cfg(feature = "json")]
+    Json,
+    /// Newline delimited JSON, used for files ending in `.ndjson` or `.jsonl`. Every line is a record of a list-shaped config like `Vec<T>`.
+    #[cfg(feature = "json")]
+    Ndjson,
+    /// TOML, used for files ending in `.toml`.
+    #[cfg(feature = "toml")]
+    Toml,
+    /// YAML, used for files ending in `.yaml` or `.yml`.
+    #[cfg(feature = "yaml")]
+    Yaml,
+    /// A stream of YAML documents separated by `---`, where every document is a record of a list-shaped config like `Vec<T>`.
+    /// It uses the extensions of [`Format::Yaml`], so it has to be selected with [`crate::ConfigBuilder::format`].
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{Config, Format};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+    /// struct User {
+    ///   name: String,
+    ///   age: u8,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-users.yaml");
+    /// std::fs::write(&path, "name: John\nage: 42\n---\nname: Jane\nage: 36\n").unwrap();
+    ///
+    /// let config = Config::<Vec<User>>::configure().format(Format::YamlDocuments).load(&path).unwrap();
+    /// assert!(format!("{:?}", config).contains("Jane"));
+    /// ```
+    #[cfg(feature = "yaml")]
+    YamlDocuments,
+    /// MessagePack, a compact binary format used for files ending in `.msgpack`. Fields are stored with their names, so the files are self-describing.
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    /// CBOR, a binary format standardized in RFC 8949, used for files ending in `.cbor`.
+    #[cfg(feature = "cbor")]
+    Cbor,
+    /// Bincode, the fastest format to load and save, for state which is never edited by hand.
+    /// It is not self-describing, so it has no file extension and has to be selected with [`crate::ConfigBuilder::format`]. For the same reason, the options which work on the generic representation of a config, like [`crate::ConfigBuilder::merge_defaults`], cannot read it.
+    #[cfg(feature = "bincode")]
+    Bincode,
+    /// HCL, the HashiCorp configuration language, used for files ending in `.hcl` or `.tf`.
+    /// Blocks are read as nested objects, which are saved as object attributes. Expressions, functions and templates are not evaluated, so configs should only use literal values.
+    #[cfg(feature = "hcl")]
+    Hcl,
+    /// KDL, used for files ending in `.kdl`.
+    /// A node with one argument holds a value, a node with several arguments or an `(array)` annotation holds an array and a node with children or properties holds an object. Children named `-` are the items of an array.
+    #[cfg(feature = "kdl")]
+    Kdl,
+    /// `.env` files of `KEY=value` pairs, used for files named `.env` or ending in `.env`.
+    /// Keys are lowercased to match the fields and split at `__` into the keys of nested structs. Lists are written as comma separated values.
+    /// The values are parsed into the types of the fields. The options which work on the generic representation of a config, like [`crate::ConfigBuilder::merge_defaults`], read values which look like numbers or booleans as such instead.
+    #[cfg(feature = "env-file")]
+    Dotenv,
+    /// Java `.properties` files, used for files ending in `.properties`.
+    /// Keys are split at dots into the keys of nested structs. Lists are written as comma separated values, and the values are parsed like the ones of [`Format::Dotenv`].
+    #[cfg(feature = "properties")]
+    Properties,
+    /// Apple property lists, used for files ending in `.plist`. Both XML and binary property lists are loaded, and configs are saved as XML.
+    /// Property lists cannot hold `None`, so optional fields which are not set are left out.
+    #[cfg(feature = "plist")]
+    Plist,
+    /// Binary Apple property lists. They are loaded like [`Format::Plist`], but saved in the binary format, so this format has to be selected with [`crate::ConfigBuilder::format`].
+    #[cfg(feature = "plist")]
+    BinaryPlist,
+    /// A format registered at runtime with [`crate::FormatRegistry::register`], identified by its extension.
+    #[cfg(feature = "json")]
+    Custom(&'static str),
+}
+
+impl Format {
+    /// Detects the format of a file from its extension. Extensions registered with [`crate::FormatRegistry`] are consulted before the built-in ones.
+    /// Extensions are matched regardless of their case, so `settings.JSON` and `Config.Toml` are found as well.
+    /// The extension of a compression enabled with the `gzip` or `zstd` feature is skipped, so `config.json.gz` is JSON.
+    /// Returns [`Error::UnknownFileExtension`] if the extension is missing or no enabled format uses it.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::Format;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(Format::from_path(Path::new("config.yml")).unwrap(), Format::Yaml);
+    /// assert!(Format::from_path(Path::new("config")).is_err());
+    ///
+    /// for (file, format) in [
+    ///     ("settings.json", Format::Json),
+    ///     ("settings.JSON", Format::Json),
+    ///     ("Config.Toml", Format::Toml),
+    ///     ("config.YAML", Format::Yaml),
+    ///     ("config.Yml", Format::Yaml),
+    /// ] {
+    ///     assert_eq!(Format::from_path(Path::new(file)).unwrap(), format);
+    /// }
+    /// ```
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let path = match Compression::from_path(path) {
+            Some(_) => Path::new(path.file_stem().unwrap_or_default()),
+            None => path,
+        };
+        let extension = path.extension().map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        #[cfg(feature = "json")]
+        if let Some((extension, _)) = extension.as_deref().and_then(crate::formats::find) {
+            return Ok(Format::Custom(extension));
+        }
+        #[cfg(feature = "env-file")]
+        if path.file_name().is_some_and(|name| name.eq_ignore_ascii_case(".env")) {
+            return Ok(Format::Dotenv);
+        }
+        match extension.as_deref() {
             #[cfg(feature = "json")]
-            Some("json") => Ok(Box::new(json::JsonLoader::new(path))),
+            Some("json") => Ok(Format::Json),
+            #[cfg(feature = "json")]
+            Some("ndjson") | Some("jsonl") => Ok(Format::Ndjson),
             #[cfg(feature = "toml")]
-            Some("toml") => Ok(Box::new(toml::TomlLoader::new(path))),
+            Some("toml") => Ok(Format::Toml),
             #[cfg(feature = "yaml")]
-            Some("yaml") | Some("yml") => Ok(Box::new(yaml::YamlLoader::new(path))),
-            Some(ext) => Err(Error::UnknownFileExtension(Some(
-                ext.to_string(),
-            ))),
-            None => Err(Error::UnknownFileExtension(None))
-        },
-        None => Err(Error::UnknownFileExtension(None)),
+            Some("yaml") | Some("yml") => Ok(Format::Yaml),
+            #[cfg(feature = "msgpack")]
+            Some("msgpack") => Ok(Format::Msgpack),
+            #[cfg(feature = "cbor")]
+            Some("cbor") => Ok(Format::Cbor),
+            #[cfg(feature = "hcl")]
+            Some("hcl") | Some("tf") => Ok(Format::Hcl),
+            #[cfg(feature = "kdl")]
+            Some("kdl") => Ok(Format::Kdl),
+            #[cfg(feature = "env-file")]
+            Some("env") => Ok(Format::Dotenv),
+            #[cfg(feature = "properties")]
+            Some("properties") => Ok(Format::Properties),
+            #[cfg(feature = "plist")]
+            Some("plist") => Ok(Format::Plist),
+            Some(_) => Err(Error::UnknownFileExtension { path: Some(path.to_path_buf()), extension: path.extension().map(|ext| ext.to_string_lossy().to_string()) }),
+            None => Err(Error::UnknownFileExtension { path: Some(path.to_path_buf()), extension: None }),
+        }
     }
 }
 
-pub(crate) trait ConfigManager<T>
-    where
-        T: serde::Serialize + serde::de::DeserializeOwned + Default,
-{
-    fn load(&self) -> Result<T, Error>;
-    fn save(&self, config: &T) -> Result<(), Error>;
+/// Writes the common name of the format, like `JSON` or `TOML`. Registered formats are named by their extension.
+impl std::fmt::Display for Format {
+    #[allow(unreachable_code)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            #[cfg(feature = "json")]
+            Format::Json => "JSON",
+            #[cfg(feature = "json")]
+            Format::Ndjson => "NDJSON",
+            #[cfg(feature = "toml")]
+            Format::Toml => "TOML",
+            #[cfg(feature = "yaml")]
+            Format::Yaml => "YAML",
+            #[cfg(feature = "yaml")]
+            Format::YamlDocuments => "YAML stream",
+            #[cfg(feature = "msgpack")]
+            Format::Msgpack => "MessagePack",
+            #[cfg(feature = "cbor")]
+            Format::Cbor => "CBOR",
+            #[cfg(feature = "bincode")]
+            Format::Bincode => "bincode",
+            #[cfg(feature = "hcl")]
+            Format::Hcl => "HCL",
+            #[cfg(feature = "kdl")]
+            Format::Kdl => "KDL",
+            #[cfg(feature = "env-file")]
+            Format::Dotenv => ".env",
+            #[cfg(feature = "properties")]
+            Format::Properties => "properties",
+            #[cfg(feature = "plist")]
+            Format::Plist => "property list",
+            #[cfg(feature = "plist")]
+            Format::BinaryPlist => "binary property list",
+            #[cfg(feature = "json")]
+            Format::Custom(extension) => extension,
+        };
+        f.write_str(name)
+    }
 }
 
-#[cfg(feature = "json")]
-mod json {
-    pub(crate) struct JsonLoader {
-        path: std::path::PathBuf,
+impl Format {
+    /// Parses data in this format. Errors which know their line are given the line of the data, see [`Error::render`].
+    pub(crate) fn parse<V>(self, data: &[u8]) -> Result<V, Error>
+        where
+            V: serde::de::DeserializeOwned,
+    {
+        self.decode(data).map_err(|err| err.excerpt(data))
     }
 
-    impl JsonLoader {
-        pub(crate) fn new(path: &std::path::Path) -> Self {
-            Self {
-                path: path.to_path_buf(),
+    #[allow(unused_variables)]
+    fn decode<V>(self, data: &[u8]) -> Result<V, Error>
+        where
+            V: serde::de::DeserializeOwned,
+    {
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json => Ok(serde_json::from_slice(data)?),
+            #[cfg(feature = "json")]
+            Format::Ndjson => crate::documents::parse_ndjson(text(data)?),
+            #[cfg(feature = "toml")]
+            Format::Toml => {
+                let data = text(data)?;
+                // TOML errors only know the byte offset where they occurred.
+                toml::from_str(data).map_err(|err| match err.span() {
+                    Some(span) => {
+                        let (line, column) = line_column(data, span.start);
+                        Error::parse_at(err, line, column)
+                    }
+                    None => err.into(),
+                })
             }
+            #[cfg(feature = "yaml")]
+            Format::Yaml => Ok(serde_yaml::from_slice(data)?),
+            #[cfg(feature = "yaml")]
+            Format::YamlDocuments => crate::documents::parse_yaml(data),
+            #[cfg(feature = "msgpack")]
+            Format::Msgpack => Ok(rmp_serde::from_slice(data)?),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => Ok(ciborium::from_reader(data)?),
+            #[cfg(feature = "bincode")]
+            Format::Bincode => Ok(bincode::deserialize(data)?),
+            #[cfg(feature = "hcl")]
+            Format::Hcl => Ok(hcl::from_str(text(data)?)?),
+            #[cfg(feature = "kdl")]
+            Format::Kdl => Ok(serde_json::from_value(crate::kdl::parse(text(data)?)?)?),
+            #[cfg(feature = "env-file")]
+            Format::Dotenv => Ok(V::deserialize(crate::env_file::parse(text(data)?)?)?),
+            #[cfg(feature = "properties")]
+            Format::Properties => Ok(V::deserialize(crate::properties::parse(text(data)?)?)?),
+            #[cfg(feature = "plist")]
+            Format::Plist | Format::BinaryPlist => Ok(plist::from_bytes(data)?),
+            #[cfg(feature = "json")]
+            Format::Custom(extension) => Ok(serde_json::from_value(crate::formats::loader(extension)?.parse(data)?)?),
         }
     }
 
-    impl<T> super::ConfigManager<T> for JsonLoader
+    /// Serializes a value in this format.
+    pub(crate) fn serialize<V>(self, value: &V) -> Result<Vec<u8>, Error>
         where
-            T: serde::Serialize + serde::de::DeserializeOwned + Default,
+            V: serde::Serialize,
     {
-        fn load(&self) -> Result<T, super::Error> {
-            #[cfg(feature = "tracing")]
-            trace!(file = ?self.path, "loading config");
-            let data = std::fs::read_to_string(&self.path)?;
-            let config: T = serde_json::from_str(&data)?;
-            #[cfg(feature = "tracing")]
-            debug!(file = ?self.path, config = data, "loaded config");
-            Ok(config)
-        }
+        // Libraries like serde_json use one error type for both directions, which converts into a parse error.
+        self.encode(value).map_err(Error::serializing)
+    }
 
-        fn save(&self, config: &T) -> Result<(), super::Error> {
-            #[cfg(feature = "tracing")]
-            trace!(file = ?self.path, "saving config");
-            let data = serde_json::to_string_pretty(config)?;
-            std::fs::write(&self.path, &data)?;
-            #[cfg(feature = "tracing")]
-            debug!(file = ?self.path, config = data, "saved config");
-            Ok(())
+    /// Serializes a value in this format, laid out as the options ask for.
+    pub(crate) fn serialize_with<V>(self, value: &V, options: &SaveOptions) -> Result<Vec<u8>, Error>
+        where
+            V: serde::Serialize,
+    {
+        #[cfg(feature = "json")]
+        if options.sort_keys && !self.is_positional() {
+            #[allow(unused_mut)]
+            let mut sorted = crate::value::sort_keys(serde_json::to_value(value).map_err(Error::serialize)?);
+            // TOML has no null, so fields which are `None` are left out like the TOML serializer leaves them out of structs.
+            #[cfg(feature = "toml")]
+            if self == Format::Toml {
+                crate::value::remove_nulls(&mut sorted);
+            }
+            return self.serialize_with(&sorted, &SaveOptions { sort_keys: false, ..options.clone() });
         }
+        let mut data = self.encode_with(value, options).map_err(Error::serializing)?;
+        if !self.is_binary() {
+            match options.trailing_newline {
+                Some(true) if !data.ends_with(b"\n") => data.push(b'\n'),
+                Some(false) => {
+                    while data.ends_with(b"\n") {
+                        data.pop();
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(data)
     }
-}
 
-#[cfg(feature = "toml")]
-mod toml {
-    pub(crate) struct TomlLoader {
-        path: std::path::PathBuf,
+    #[allow(unused_variables)]
+    fn encode_with<V>(self, value: &V, options: &SaveOptions) -> Result<Vec<u8>, Error>
+        where
+            V: serde::Serialize,
+    {
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json if !options.pretty => Ok(serde_json::to_vec(value)?),
+            #[cfg(feature = "json")]
+            Format::Json => {
+                let indent = b" ".repeat(options.indent);
+                let mut data = Vec::new();
+                let mut serializer = serde_json::Serializer::with_formatter(&mut data, serde_json::ser::PrettyFormatter::with_indent(&indent));
+                value.serialize(&mut serializer)?;
+                Ok(data)
+            }
+            #[cfg(feature = "toml")]
+            Format::Toml if !options.pretty => Ok(toml::to_string(value)?.into_bytes()),
+            _ => self.encode(value),
+        }
     }
 
-    impl TomlLoader {
-        pub(crate) fn new(path: &std::path::Path) -> Self {
-            Self {
-                path: path.to_path_buf(),
+    #[allow(unused_variables)]
+    fn encode<V>(self, value: &V) -> Result<Vec<u8>, Error>
+        where
+            V: serde::Serialize,
+    {
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json => Ok(serde_json::to_vec_pretty(value)?),
+            #[cfg(feature = "json")]
+            Format::Ndjson => crate::documents::write_ndjson(value),
+            #[cfg(feature = "toml")]
+            Format::Toml => Ok(toml::to_string_pretty(value)?.into_bytes()),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => Ok(serde_yaml::to_string(value)?.into_bytes()),
+            #[cfg(feature = "yaml")]
+            Format::YamlDocuments => crate::documents::write_yaml(value),
+            #[cfg(feature = "msgpack")]
+            Format::Msgpack => Ok(rmp_serde::to_vec_named(value)?),
+            #[cfg(feature = "cbor")]
+            Format::Cbor => {
+                let mut data = Vec::new();
+                ciborium::into_writer(value, &mut data)?;
+                Ok(data)
+            }
+            #[cfg(feature = "bincode")]
+            Format::Bincode => Ok(bincode::serialize(value)?),
+            #[cfg(feature = "hcl")]
+            Format::Hcl => Ok(hcl::to_vec(value)?),
+            #[cfg(feature = "kdl")]
+            Format::Kdl => Ok(crate::kdl::to_string(&serde_json::to_value(value)?)?.into_bytes()),
+            #[cfg(feature = "env-file")]
+            Format::Dotenv => Ok(crate::env_file::to_string(&serde_json::to_value(value)?)?.into_bytes()),
+            #[cfg(feature = "properties")]
+            Format::Properties => Ok(crate::properties::to_string(&serde_json::to_value(value)?)?.into_bytes()),
+            #[cfg(feature = "plist")]
+            Format::Plist => {
+                let mut data = Vec::new();
+                plist::to_writer_xml(&mut data, value)?;
+                Ok(data)
+            }
+            #[cfg(feature = "plist")]
+            Format::BinaryPlist => {
+                let mut data = Vec::new();
+                plist::to_writer_binary(&mut data, value)?;
+                Ok(data)
             }
+            #[cfg(feature = "json")]
+            Format::Custom(extension) => crate::formats::loader(extension)?.serialize(&serde_json::to_value(value)?),
         }
     }
 
-    impl<T> super::ConfigManager<T> for TomlLoader
+    /// Returns whether data in this format may be binary instead of UTF-8 text.
+    pub(crate) fn is_binary(self) -> bool {
+        match self {
+            #[cfg(feature = "msgpack")]
+            Format::Msgpack => true,
+            #[cfg(feature = "cbor")]
+            Format::Cbor => true,
+            #[cfg(feature = "bincode")]
+            Format::Bincode => true,
+            #[cfg(feature = "plist")]
+            Format::Plist | Format::BinaryPlist => true,
+            #[cfg(feature = "json")]
+            Format::Custom(_) => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Returns whether this format writes the fields of structs by their position instead of their name, so structs cannot be written as maps.
+    #[cfg(feature = "json")]
+    fn is_positional(self) -> bool {
+        match self {
+            #[cfg(feature = "bincode")]
+            Format::Bincode => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Checks data in this format and reports everything which is wrong with it as a config of type `T`.
+    #[cfg(feature = "json")]
+    pub(crate) fn diagnose<T>(self, data: &[u8]) -> Vec<crate::Diagnostic>
         where
             T: serde::Serialize + serde::de::DeserializeOwned + Default,
     {
-        fn load(&self) -> Result<T, super::Error> {
-            #[cfg(feature = "tracing")]
-            trace!(file = ?self.path, "loading config");
-            let data = std::fs::read_to_string(&self.path)?;
-            let config: T = toml::from_str(&data)?;
-            #[cfg(feature = "tracing")]
-            debug!(file = ?self.path, config = data, "loaded config");
-            Ok(config)
+        if !self.is_binary() {
+            if let Err(err) = std::str::from_utf8(data) {
+                return vec![crate::Diagnostic::invalid(err.to_string(), None)];
+            }
+        }
+        match self {
+            Format::Json => {
+                let invalid = |err: serde_json::Error| crate::Diagnostic::invalid(err.to_string(), Some((err.line(), err.column())));
+                crate::diagnostics::report::<T>(
+                    serde_json::from_slice(data).map_err(invalid),
+                    serde_json::from_slice::<T>(data).err().map(invalid),
+                )
+            }
+            #[cfg(feature = "toml")]
+            Format::Toml => {
+                let data = std::str::from_utf8(data).unwrap_or_default();
+                let invalid = |err: toml::de::Error| crate::Diagnostic::invalid(
+                    err.message().to_string(),
+                    err.span().map(|span| line_column(data, span.start)),
+                );
+                crate::diagnostics::report::<T>(
+                    toml::from_str(data).map_err(invalid),
+                    toml::from_str::<T>(data).err().map(invalid),
+                )
+            }
+            #[cfg(feature = "yaml")]
+            Format::Yaml => {
+                let invalid = |err: serde_yaml::Error| crate::Diagnostic::invalid(
+                    err.to_string(),
+                    err.location().map(|location| (location.line(), location.column())),
+                );
+                crate::diagnostics::report::<T>(
+                    serde_yaml::from_slice(data).map_err(invalid),
+                    serde_yaml::from_slice::<T>(data).err().map(invalid),
+                )
+            }
+            Format::Ndjson => {
+                let data = std::str::from_utf8(data).unwrap_or_default();
+                match crate::documents::ndjson_value(data) {
+                    Ok(records) => {
+                        let error = serde_json::from_value::<T>(records.clone()).err();
+                        crate::diagnostics::report::<T>(Ok(records), error.map(|err| crate::Diagnostic::invalid(err.to_string(), None)))
+                    }
+                    Err((line, err)) => vec![crate::Diagnostic::invalid(err.to_string(), Some((line, err.column())))],
+                }
+            }
+            #[cfg(feature = "yaml")]
+            Format::YamlDocuments => {
+                let invalid = |err: Error| crate::Diagnostic::invalid(match err {
+                    Error::ParseError { source, .. } => source.to_string(),
+                    err => err.to_string(),
+                }, None);
+                crate::diagnostics::report::<T>(
+                    crate::documents::parse_yaml(data).map_err(invalid),
+                    crate::documents::parse_yaml::<T>(data).err().map(invalid),
+                )
+            }
+            #[cfg(feature = "msgpack")]
+            Format::Msgpack => {
+                let invalid = |err: rmp_serde::decode::Error| crate::Diagnostic::invalid(err.to_string(), None);
+                crate::diagnostics::report::<T>(
+                    rmp_serde::from_slice(data).map_err(invalid),
+                    rmp_serde::from_slice::<T>(data).err().map(invalid),
+                )
+            }
+            #[cfg(feature = "cbor")]
+            Format::Cbor => {
+                let invalid = |err: ciborium::de::Error<std::io::Error>| crate::Diagnostic::invalid(err.to_string(), None);
+                crate::diagnostics::report::<T>(
+                    ciborium::from_reader(data).map_err(invalid),
+                    ciborium::from_reader::<T, _>(data).err().map(invalid),
+                )
+            }
+            #[cfg(feature = "bincode")]
+            Format::Bincode => {
+                // The generic representation cannot be read from bincode, so only the config type is checked.
+                let invalid = |err: bincode::Error| crate::Diagnostic::invalid(err.to_string(), None);
+                bincode::deserialize::<T>(data).err().map(invalid).into_iter().collect()
+            }
+            #[cfg(feature = "hcl")]
+            Format::Hcl => {
+                let data = std::str::from_utf8(data).unwrap_or_default();
+                let invalid = |err: hcl::Error| match err {
+                    hcl::Error::Parse(err) => crate::Diagnostic::invalid(
+                        err.message().to_string(),
+                        Some((err.location().line(), err.location().column())),
+                    ),
+                    err => crate::Diagnostic::invalid(err.to_string(), None),
+                };
+                crate::diagnostics::report::<T>(
+                    hcl::from_str(data).map_err(invalid),
+                    hcl::from_str::<T>(data).err().map(invalid),
+                )
+            }
+            #[cfg(feature = "kdl")]
+            Format::Kdl => match crate::kdl::parse(std::str::from_utf8(data).unwrap_or_default()) {
+                Ok(value) => {
+                    let error = serde_json::from_value::<T>(value.clone()).err();
+                    crate::diagnostics::report::<T>(Ok(value), error.map(|err| crate::Diagnostic::invalid(err.to_string(), None)))
+                }
+                Err(err) => vec![err.into()],
+            },
+            #[cfg(feature = "env-file")]
+            Format::Dotenv => crate::flat::diagnose::<T>(crate::env_file::parse(std::str::from_utf8(data).unwrap_or_default())),
+            #[cfg(feature = "properties")]
+            Format::Properties => crate::flat::diagnose::<T>(crate::properties::parse(std::str::from_utf8(data).unwrap_or_default())),
+            #[cfg(feature = "plist")]
+            Format::Plist | Format::BinaryPlist => {
+                let invalid = |err: plist::Error| crate::Diagnostic::invalid(err.to_string(), None);
+                crate::diagnostics::report::<T>(
+                    plist::from_bytes(data).map_err(invalid),
+                    plist::from_bytes::<T>(data).err().map(invalid),
+                )
+            }
+            Format::Custom(extension) => match crate::formats::loader(extension).and_then(|loader| loader.parse(data)) {
+                Ok(value) => {
+                    let error = serde_json::from_value::<T>(value.clone()).err();
+                    crate::diagnostics::report::<T>(Ok(value), error.map(|err| crate::Diagnostic::invalid(err.to_string(), None)))
+                }
+                Err(err) => vec![crate::Diagnostic::invalid(err.to_string(), None)],
+            },
+        }
+    }
+}
+
+/// Returns the data of a text format as a string.
+pub(crate) fn text(data: &[u8]) -> Result<&str, Error> {
+    std::str::from_utf8(data).map_err(Error::parse)
+}
+
+/// How configs are laid out when they are saved, set with [`crate::ConfigBuilder::save_options`].
+/// The options only apply to the formats which support them, others are written as usual.
+///
+/// # Example
+/// ```
+/// use opzioni::{Format, SaveOptions};
+///
+/// let options = SaveOptions::new().indent(4).trailing_newline(true);
+/// assert_ne!(options, SaveOptions::default());
+/// assert_eq!(SaveOptions::new().compact(), SaveOptions::new().pretty(false));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveOptions {
+    pretty: bool,
+    indent: usize,
+    trailing_newline: Option<bool>,
+    sort_keys: bool,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self { pretty: true, indent: 2, trailing_newline: None, sort_keys: false }
+    }
+}
+
+impl SaveOptions {
+    /// Creates the default options, which write JSON and TOML pretty printed like a save without options does.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether configs are pretty printed. Compact JSON is written on one line, compact TOML writes arrays on one line instead of one element per line.
+    /// TOML writes arrays of tables as `[[table]]` sections either way and YAML is always written in block style. Defaults to `true`.
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Writes configs compact, like `pretty(false)`.
+    pub fn compact(self) -> Self {
+        self.pretty(false)
+    }
+
+    /// Sets the number of spaces pretty printed JSON is indented with. Defaults to 2.
+    /// TOML does not indent nested tables and serde_yaml always indents with two spaces, so other formats are not affected.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Sets whether text formats end with a newline. By default every format ends like its serializer writes it, which is without a newline for JSON and with one for TOML and YAML.
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = Some(trailing_newline);
+        self
+    }
+
+    /// Sets whether the keys of maps and the fields of structs are written in alphabetical order, so repeated saves of an equal config write identical files.
+    /// Without it struct fields keep their declaration order, but maps like `HashMap` are written in the order they iterate in, which changes between runs.
+    /// bincode writes structs by position and is not affected. With the `toml-edit` or `yaml-order` feature the keys of an existing file keep their order. Defaults to `false`.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, SaveOptions};
+    /// use serde::{Serialize, Deserialize};
+    /// use std::collections::HashMap;
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   ports: HashMap<String, u16>,
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("opzioni-sort-keys.json");
+    /// std::fs::write(&path, r#"{ "name": "John", "ports": {} }"#).unwrap();
+    /// let config = Config::<MyConfig>::configure().save_options(SaveOptions::new().compact().sort_keys(true)).load(&path).unwrap();
+    /// config.get().write().unwrap().ports.insert("https".to_string(), 443);
+    /// config.get().write().unwrap().ports.insert("http".to_string(), 80);
+    /// config.save().unwrap();
+    /// assert_eq!(std::fs::read_to_string(&path).unwrap(), r#"{"name":"John","ports":{"http":80,"https":443}}"#);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+}
+
+/// Converts a byte offset into `data` into a 1-based line and column.
+#[cfg(any(feature = "toml", feature = "kdl", feature = "env-file", feature = "properties"))]
+pub(crate) fn line_column(data: &str, offset: usize) -> (usize, usize) {
+    let before = &data[..offset.min(data.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+    (line, column)
+}
+
+/// The compressions of config files, detected from the extension following the one of the format, like `config.json.gz`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Compression {
+    /// gzip, used for files ending in `.gz`.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Zstandard, used for files ending in `.zst`.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression of a file from its extension. Returns `None` if the file is not compressed with an enabled compression.
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            #[cfg(feature = "gzip")]
+            "gz" => Some(Compression::Gzip),
+            #[cfg(feature = "zstd")]
+            "zst" => Some(Compression::Zstd),
+            _ => None,
         }
+    }
 
-        fn save(&self, config: &T) -> Result<(), super::Error> {
-            #[cfg(feature = "tracing")]
-            trace!(file = ?self.path, "saving config");
-            let data = toml::to_string_pretty(config)?;
-            std::fs::write(&self.path, &data)?;
-            #[cfg(feature = "tracing")]
-            debug!(file = ?self.path, config = data, "saved config");
-            Ok(())
+    #[allow(unused_variables)]
+    fn compress(self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Write;
+
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(zstd::encode_all(data, 0)?),
         }
     }
+
+    #[allow(unused_variables)]
+    fn decompress(self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Read;
+
+                let mut decompressed = Vec::new();
+                flate2::read::GzDecoder::new(data).read_to_end(&mut decompressed)?;
+                Ok(decompressed)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(zstd::decode_all(data)?),
+        }
+    }
+}
+
+/// The file extensions of the enabled formats, in the order files are searched for. Extensions registered with [`crate::FormatRegistry`] are searched after them, see [`extensions`].
+pub(crate) const EXTENSIONS: &[&str] = &[
+    #[cfg(feature = "json")]
+    "json",
+    #[cfg(feature = "toml")]
+    "toml",
+    #[cfg(feature = "yaml")]
+    "yaml",
+    #[cfg(feature = "yaml")]
+    "yml",
+    #[cfg(feature = "msgpack")]
+    "msgpack",
+    #[cfg(feature = "cbor")]
+    "cbor",
+    #[cfg(feature = "hcl")]
+    "hcl",
+    #[cfg(feature = "hcl")]
+    "tf",
+    #[cfg(feature = "kdl")]
+    "kdl",
+    #[cfg(feature = "env-file")]
+    "env",
+    #[cfg(feature = "properties")]
+    "properties",
+    #[cfg(feature = "plist")]
+    "plist",
+    #[cfg(feature = "json")]
+    "ndjson",
+    #[cfg(feature = "json")]
+    "jsonl",
+];
+
+/// Returns the extensions of the enabled and the registered formats, in the order files are searched for.
+pub(crate) fn extensions() -> Vec<&'static str> {
+    #[allow(unused_mut)]
+    let mut extensions = EXTENSIONS.to_vec();
+    #[cfg(feature = "json")]
+    extensions.extend(crate::formats::extensions().into_iter().filter(|ext| !EXTENSIONS.contains(ext)));
+    extensions
+}
+
+#[cfg(feature = "json")]
+pub(crate) fn for_file<T>(path: &Path) -> Result<Box<dyn ConfigManager<T>>, Error>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    Ok(for_format(Format::from_path(path)?, path))
+}
+
+pub(crate) fn for_format<T>(format: Format, path: &Path) -> Box<dyn ConfigManager<T>>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    Box::new(FileLoader {
+        format,
+        compression: Compression::from_path(path),
+        path: path.to_path_buf(),
+    })
+}
+
+/// Wraps the manager of a config file so the file is created with the given permissions, see [`crate::ConfigBuilder::file_mode`].
+pub(crate) fn with_mode<'a, T>(loader: Box<dyn ConfigManager<T> + 'a>, mode: Option<u32>) -> Box<dyn ConfigManager<T> + 'a>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default + 'a,
+{
+    match mode {
+        Some(mode) => Box::new(Restricted { inner: loader, mode }),
+        None => loader,
+    }
 }
 
-#[cfg(feature = "yaml")]
-mod yaml {
-    pub(crate) struct YamlLoader {
-        path: std::path::PathBuf,
+/// Wraps the manager of a config so it serializes the config with the given options, see [`crate::ConfigBuilder::save_options`].
+pub(crate) fn with_options<'a, T>(loader: Box<dyn ConfigManager<T> + 'a>, options: &SaveOptions) -> Box<dyn ConfigManager<T> + 'a>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default + 'a,
+{
+    match *options == SaveOptions::default() {
+        true => loader,
+        false => Box::new(Formatted { inner: loader, options: options.clone() }),
+    }
+}
+
+/// Returns a manager for config data which is already in memory, for example because it was read from a [`std::io::Read`]. It cannot be written.
+pub(crate) fn for_data<T>(format: Format, data: Vec<u8>) -> Box<dyn ConfigManager<T>>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    Box::new(DataLoader { format, data })
+}
+
+/// Reads and writes the serialized form of a config. The parsing is shared, implementors only move the data.
+pub(crate) trait ConfigManager<T>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    /// Returns the format of the config data.
+    fn format(&self) -> Format;
+    /// Returns the path of the config file.
+    fn path(&self) -> &Path;
+    /// Reads the serialized config.
+    fn read(&self) -> Result<Vec<u8>, Error>;
+    /// Writes the serialized config.
+    fn write(&self, data: &[u8]) -> Result<(), Error>;
+
+    fn load(&self) -> Result<T, Error> {
+        let data = self.read()?;
+        let config = self.format().parse(&data);
+        crate::secret::wipe(data);
+        config
     }
 
-    impl YamlLoader {
-        pub(crate) fn new(path: &std::path::Path) -> Self {
-            Self {
-                path: path.to_path_buf(),
+    /// Loads the config into a generic value instead of `T`, so it can be inspected or transformed before deserializing.
+    #[cfg(feature = "json")]
+    fn load_value(&self) -> Result<serde_json::Value, Error> {
+        let data = self.read()?;
+        let value = self.format().parse(&data);
+        crate::secret::wipe(data);
+        value
+    }
+
+    /// Checks the config and reports everything which is wrong with it.
+    #[cfg(feature = "json")]
+    fn diagnose(&self) -> Result<Vec<crate::Diagnostic>, Error> {
+        let data = self.read()?;
+        let diagnostics = self.format().diagnose::<T>(&data);
+        crate::secret::wipe(data);
+        Ok(diagnostics)
+    }
+
+    fn serialize(&self, config: &T) -> Result<Vec<u8>, Error> {
+        self.format().serialize(config)
+    }
+
+    /// Serializes a generic value in the format of the config.
+    #[cfg(feature = "json")]
+    fn serialize_value(&self, value: &serde_json::Value) -> Result<Vec<u8>, Error> {
+        self.format().serialize(value)
+    }
+}
+
+/// Manages a config file on disk. Compressed files are decompressed on read and compressed on write.
+struct FileLoader {
+    format: Format,
+    compression: Option<Compression>,
+    path: PathBuf,
+}
+
+impl FileLoader {
+    fn read_file(&self) -> Result<Vec<u8>, Error> {
+        let data = std::fs::read(&self.path)?;
+        match self.compression {
+            Some(compression) => {
+                let decompressed = compression.decompress(&data);
+                crate::secret::wipe(data);
+                decompressed
             }
+            None => Ok(data),
         }
     }
 
-    impl<T> super::ConfigManager<T> for YamlLoader
-        where
-            T: serde::Serialize + serde::de::DeserializeOwned + Default,
-    {
-        fn load(&self) -> Result<T, super::Error> {
-            #[cfg(feature = "tracing")]
-            trace!(file = ?self.path, "loading config");
-            let data = std::fs::read_to_string(&self.path)?;
-            let config: T = serde_yaml::from_str(&data)?;
-            #[cfg(feature = "tracing")]
-            debug!(file = ?self.path, config = data, "loaded config");
-            Ok(config)
-        }
-
-        fn save(&self, config: &T) -> Result<(), super::Error> {
-            #[cfg(feature = "tracing")]
-            trace!(file = ?self.path, "saving config");
-            let data = serde_yaml::to_string(config)?;
-            std::fs::write(&self.path, &data)?;
-            #[cfg(feature = "tracing")]
-            debug!(file = ?self.path, config = data, "saved config");
-            Ok(())
+    fn write_file(&self, data: &[u8]) -> Result<(), Error> {
+        match self.compression {
+            Some(compression) => std::fs::write(&self.path, compression.compress(data)?)?,
+            None => std::fs::write(&self.path, data)?,
+        }
+        Ok(())
+    }
+
+    /// Merges the serialized config into the existing file, if a feature for keeping the existing file is enabled for the format.
+    /// Returns `None` if the file should simply be overwritten.
+    #[allow(unused_variables)]
+    fn preserve(&self, data: &[u8]) -> Option<Vec<u8>> {
+        #[allow(unreachable_patterns)]
+        let preserve: Option<fn(&str, &str) -> Option<String>> = match self.format {
+            #[cfg(feature = "toml-edit")]
+            Format::Toml => Some(crate::preserve::toml),
+            #[cfg(feature = "yaml-order")]
+            Format::Yaml => Some(crate::preserve::yaml),
+            _ => None,
+        };
+        let preserve = preserve?;
+        // Encrypted configs have no structure to merge into.
+        #[cfg(feature = "encryption")]
+        if crate::encryption::is_encrypted(data) {
+            return None;
         }
+        let existing = self.read_file().ok()?;
+        let preserved = text(&existing).ok().zip(text(data).ok()).and_then(|(existing, data)| preserve(existing, data));
+        crate::secret::wipe(existing);
+        preserved.map(String::into_bytes)
+    }
+}
+
+impl<T> ConfigManager<T> for FileLoader
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    fn format(&self) -> Format {
+        self.format
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Error> {
+        #[cfg(feature = "tracing")]
+        trace!(file = ?self.path, "loading config");
+        let data = self.read_file().map_err(|err| err.at(&self.path, Some(self.format)))?;
+        #[cfg(feature = "tracing")]
+        trace!(file = ?self.path, bytes = data.len(), "read config file");
+        Ok(data)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), Error> {
+        #[cfg(feature = "tracing")]
+        trace!(file = ?self.path, "saving config");
+        match self.preserve(data) {
+            Some(preserved) => {
+                let written = self.write_file(&preserved);
+                crate::secret::wipe(preserved);
+                written.map_err(|err| err.at(&self.path, Some(self.format)))?;
+            }
+            None => self.write_file(data).map_err(|err| err.at(&self.path, Some(self.format)))?,
+        }
+        #[cfg(feature = "tracing")]
+        trace!(file = ?self.path, bytes = data.len(), "wrote config file");
+        Ok(())
+    }
+}
+
+/// Manages a config file whose permissions are set before it is written, so its content is never readable by others, not even briefly.
+struct Restricted<'a, T> {
+    inner: Box<dyn ConfigManager<T> + 'a>,
+    mode: u32,
+}
+
+impl<T> ConfigManager<T> for Restricted<'_, T>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    fn format(&self) -> Format {
+        self.inner.format()
+    }
+
+    fn path(&self) -> &Path {
+        self.inner.path()
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Error> {
+        self.inner.read()
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), Error> {
+        restrict(self.inner.path(), self.mode).map_err(|err| Error::from(err).at(self.inner.path(), Some(self.inner.format())))?;
+        self.inner.write(data)
+    }
+}
+
+/// Creates the file with the given permissions if it does not exist yet and applies them to an existing file.
+#[cfg(unix)]
+fn restrict(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    std::fs::OpenOptions::new().write(true).create(true).truncate(false).mode(mode).open(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+/// Windows has no permission bits, the file keeps the access control list it inherits from its directory.
+#[cfg(not(unix))]
+fn restrict(_path: &Path, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Returns the permissions of an existing file.
+#[cfg(all(unix, feature = "json"))]
+pub(crate) fn mode_of(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path).ok().map(|metadata| metadata.permissions().mode() & 0o7777)
+}
+
+/// Returns the permissions of an existing file.
+#[cfg(all(not(unix), feature = "json"))]
+pub(crate) fn mode_of(_path: &Path) -> Option<u32> {
+    None
+}
+
+/// Manages a config whose serialized form is laid out with the save options.
+struct Formatted<'a, T> {
+    inner: Box<dyn ConfigManager<T> + 'a>,
+    options: SaveOptions,
+}
+
+impl<T> ConfigManager<T> for Formatted<'_, T>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    fn format(&self) -> Format {
+        self.inner.format()
+    }
+
+    fn path(&self) -> &Path {
+        self.inner.path()
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Error> {
+        self.inner.read()
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), Error> {
+        self.inner.write(data)
+    }
+
+    fn serialize(&self, config: &T) -> Result<Vec<u8>, Error> {
+        self.format().serialize_with(config, &self.options)
+    }
+
+    #[cfg(feature = "json")]
+    fn serialize_value(&self, value: &serde_json::Value) -> Result<Vec<u8>, Error> {
+        self.format().serialize_with(value, &self.options)
+    }
+}
+
+/// Manages config data which is already in memory. The data is wiped when the manager is dropped.
+struct DataLoader {
+    format: Format,
+    data: Vec<u8>,
+}
+
+impl<T> ConfigManager<T> for DataLoader
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    fn format(&self) -> Format {
+        self.format
+    }
+
+    fn path(&self) -> &Path {
+        Path::new("")
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.data.clone())
+    }
+
+    fn write(&self, _data: &[u8]) -> Result<(), Error> {
+        Err(Error::NoBackingFile)
+    }
+}
+
+impl Drop for DataLoader {
+    fn drop(&mut self) {
+        crate::secret::wipe(std::mem::take(&mut self.data));
     }
 }
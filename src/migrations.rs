@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::Error;
+
+type Migration = Arc<dyn Fn(Value) -> Result<Value, String> + Send + Sync>;
+
+/// An ordered set of migrations which upgrade old config files to the current layout of `T`.
+/// The version of a config file is read from a numeric field at the top level of the file. A file without the field has version 0.
+/// Every migration upgrades a config file by exactly one version, working on the generic [`serde_json::Value`] representation of the file regardless of its format.
+/// After the last migration the version field is set to the current version and the value is deserialized into `T`.
+/// Migrated configs are considered dirty (see [`crate::Config::is_dirty`]), so the upgraded file is written back with the next save.
+///
+/// Pass the migrations to the builder with [`crate::ConfigBuilder::with_migrations`].
+///
+/// # Example
+/// ```
+/// use opzioni::{Config, Migrations};
+/// use serde::{Serialize, Deserialize};
+/// use std::path::Path;
+///
+/// #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+/// struct MyConfig {
+///   version: u64,
+///   full_name: String,
+///   age: u8,
+/// }
+///
+/// // testconfig.json has no version field and still calls full_name "name".
+/// let migrations = Migrations::<MyConfig>::new("version", 1)
+///     .add(0, |mut value| {
+///         let name = value["name"].take();
+///         value["full_name"] = name;
+///         Ok(value)
+///     });
+/// let config = Config::<MyConfig>::configure().with_migrations(migrations).load(Path::new("testconfig.json")).unwrap();
+/// assert!(format!("{:?}", config).contains(r#"MyConfig { version: 1, full_name: "John", age: 42 }"#));
+/// ```
+pub struct Migrations<T> {
+    field: String,
+    current: u64,
+    steps: BTreeMap<u64, Migration>,
+    _p: PhantomData<fn() -> T>,
+}
+
+impl<T> Migrations<T> {
+    /// Creates an empty set of migrations. `field` is the name of the version field and `current` the version `T` corresponds to.
+    pub fn new(field: &str, current: u64) -> Self {
+        Self {
+            field: field.to_string(),
+            current,
+            steps: BTreeMap::new(),
+            _p: PhantomData,
+        }
+    }
+
+    /// Registers the migration which upgrades a config file from version `from` to version `from + 1`.
+    /// If the migration returns an error, loading fails with [`Error::MigrationError`].
+    pub fn add<F>(mut self, from: u64, migration: F) -> Self
+        where F: Fn(Value) -> Result<Value, String> + Send + Sync + 'static {
+        self.steps.insert(from, Arc::new(migration));
+        self
+    }
+
+    /// Upgrades the given value to the current version. Returns the upgraded value and whether any migration ran.
+    pub(crate) fn apply(&self, mut value: Value) -> Result<(Value, bool), Error> {
+        let mut version = match value.get(&self.field) {
+            None | Some(Value::Null) => 0,
+            Some(version) => version.as_u64().ok_or_else(|| {
+                Error::MigrationError { path: None, format: None, message: format!("the version field {} is not a positive number", self.field) }
+            })?,
+        };
+        if version > self.current {
+            return Err(Error::MigrationError { path: None, format: None, message: format!(
+                "the config file has version {}, but the newest known version is {}",
+                version, self.current
+            ) });
+        }
+        let migrated = version < self.current;
+        while version < self.current {
+            let migration = self.steps.get(&version).ok_or_else(|| {
+                Error::MigrationError { path: None, format: None, message: format!("no migration from version {} registered", version) }
+            })?;
+            #[cfg(feature = "tracing")]
+            debug!(from = version, to = version + 1, "migrating config");
+            value = migration(value).map_err(|message| Error::MigrationError { path: None, format: None, message })?;
+            version += 1;
+        }
+        if migrated {
+            match value.as_object_mut() {
+                Some(object) => {
+                    object.insert(self.field.clone(), Value::from(version));
+                }
+                None => return Err(Error::MigrationError { path: None, format: None, message: "the migrated config is not a map".to_string() }),
+            }
+        }
+        Ok((value, migrated))
+    }
+}
+
+impl<T> Clone for Migrations<T> {
+    fn clone(&self) -> Self {
+        Self {
+            field: self.field.clone(),
+            current: self.current,
+            steps: self.steps.clone(),
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<T> Debug for Migrations<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Migrations")
+            .field("field", &self.field)
+            .field("current", &self.current)
+            .field("steps", &self.steps.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
@@ -0,0 +1,93 @@
+#[cfg(feature = "toml-edit")]
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+/// Patches the existing TOML document with the values of the newly serialized one and returns the patched document.
+/// Only changed values are replaced, keeping their comments and formatting. Keys which are missing in `data` are removed and new keys are appended.
+/// Returns `None` if one of the documents cannot be parsed, in which case the file should simply be overwritten.
+#[cfg(feature = "toml-edit")]
+pub(crate) fn toml(existing: &str, data: &str) -> Option<String> {
+    let mut document = existing.parse::<DocumentMut>().ok()?;
+    let new = data.parse::<DocumentMut>().ok()?;
+    patch_table(document.as_table_mut(), new.as_table());
+    Some(document.to_string())
+}
+
+#[cfg(feature = "toml-edit")]
+fn patch_table(old: &mut Table, new: &Table) {
+    old.retain(|key, _| new.contains_key(key));
+    for (key, item) in new.iter() {
+        match (old.get_mut(key), item) {
+            (Some(Item::Table(old)), Item::Table(new)) => patch_table(old, new),
+            (Some(Item::ArrayOfTables(old)), Item::ArrayOfTables(new)) if old.len() == new.len() => {
+                for (old, new) in old.iter_mut().zip(new.iter()) {
+                    patch_table(old, new);
+                }
+            }
+            (Some(Item::Value(old)), Item::Value(new)) => {
+                if !same(old, new) {
+                    let decor = old.decor().clone();
+                    *old = new.clone();
+                    *old.decor_mut() = decor;
+                }
+            }
+            _ => {
+                old.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+/// Compares two values ignoring their formatting, so for example `'a'` and `"a"` are the same.
+#[cfg(feature = "toml-edit")]
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Integer(a), Value::Integer(b)) => a.value() == b.value(),
+        (Value::Float(a), Value::Float(b)) => a.value() == b.value(),
+        (Value::Boolean(a), Value::Boolean(b)) => a.value() == b.value(),
+        (Value::Datetime(a), Value::Datetime(b)) => a.value() == b.value(),
+        (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same(a, b)),
+        (Value::InlineTable(a), Value::InlineTable(b)) => {
+            a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| same(a, b)))
+        }
+        _ => false,
+    }
+}
+
+/// Reorders the keys of the newly serialized YAML document to match the existing one and returns the reordered document.
+/// Keys which are new are appended in the order they were serialized. Comments and anchors cannot be kept, because serde_yaml drops them while parsing.
+/// Returns `None` if one of the documents cannot be parsed, in which case the file should simply be overwritten.
+#[cfg(feature = "yaml-order")]
+pub(crate) fn yaml(existing: &str, data: &str) -> Option<String> {
+    let existing: serde_yaml::Value = serde_yaml::from_str(existing).ok()?;
+    let new: serde_yaml::Value = serde_yaml::from_str(data).ok()?;
+    serde_yaml::to_string(&reorder(new, &existing)).ok()
+}
+
+#[cfg(feature = "yaml-order")]
+fn reorder(new: serde_yaml::Value, old: &serde_yaml::Value) -> serde_yaml::Value {
+    use serde_yaml::{Mapping, Value};
+
+    match (new, old) {
+        (Value::Mapping(mut new), Value::Mapping(old)) => {
+            let mut ordered = Mapping::with_capacity(new.len());
+            for (key, old) in old {
+                if let Some(value) = new.remove(key) {
+                    ordered.insert(key.clone(), reorder(value, old));
+                }
+            }
+            ordered.extend(new);
+            Value::Mapping(ordered)
+        }
+        (Value::Sequence(new), Value::Sequence(old)) => Value::Sequence(
+            new.into_iter()
+                .enumerate()
+                .map(|(i, value)| match old.get(i) {
+                    Some(old) => reorder(value, old),
+                    None => value,
+                })
+                .collect(),
+        ),
+        (new, _) => new,
+    }
+}
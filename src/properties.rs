@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::diagnostics::ParseError;
+use crate::manager::line_column;
+use crate::flat::{flatten, insert, Entry};
+use crate::Error;
+
+/// The separator of nested keys, so `server.port` sets the `port` field of the `server` struct.
+const SEPARATOR: &str = ".";
+
+/// Parses a `.properties` file like `java.util.Properties` does. Keys are split at dots into the keys of nested structs.
+/// Lines starting with `#` or `!` are comments, keys are separated from their values by `=`, `:` or whitespace, and a backslash at the end of a line continues the value on the next line.
+pub(crate) fn parse(data: &str) -> Result<Entry, ParseError> {
+    let mut root = BTreeMap::new();
+    let mut lines = data.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+        let mut logical = line.to_string();
+        while continues(&logical) {
+            logical.pop();
+            match lines.next() {
+                Some((_, next)) => logical.push_str(next.trim_start()),
+                None => break,
+            }
+        }
+        let (key, value) = split(&logical);
+        let position = data.lines().take(index).map(|line| line.len() + 1).sum::<usize>();
+        let error = |message: String| {
+            let (line, column) = line_column(data, position);
+            ParseError { message, line, column }
+        };
+        let key = unescape(key).map_err(&error)?;
+        let value = unescape(value).map_err(&error)?;
+        insert(&mut root, &key, SEPARATOR, value).map_err(error)?;
+    }
+    Ok(Entry::Table(root))
+}
+
+/// Returns whether the line ends with an odd number of backslashes, which continues it on the next line.
+fn continues(line: &str) -> bool {
+    line.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+/// Splits a line at the first unescaped `=`, `:` or whitespace into its still escaped key and value.
+fn split(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' => escaped = !escaped,
+            '=' | ':' if !escaped => return (&line[..i], line[i + 1..].trim_start()),
+            c if c.is_whitespace() && !escaped => {
+                let value = line[i..].trim_start();
+                let value = value.strip_prefix(['=', ':']).unwrap_or(value);
+                return (&line[..i], value.trim_start());
+            }
+            _ => escaped = false,
+        }
+    }
+    (line, "")
+}
+
+fn unescape(escaped: &str) -> Result<String, String> {
+    let mut unescaped = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('f') => unescaped.push('\u{c}'),
+            Some('u') => {
+                let code = |chars: &mut std::str::Chars| {
+                    let digits = chars.by_ref().take(4).collect::<String>();
+                    match u16::from_str_radix(&digits, 16) {
+                        Ok(code) if digits.len() == 4 => Ok(code),
+                        _ => Err(format!("invalid unicode escape `\\u{}`", digits)),
+                    }
+                };
+                let high = code(&mut chars)?;
+                let c = match char::from_u32(high as u32) {
+                    Some(c) => c,
+                    // A character outside the basic multilingual plane is escaped as a surrogate pair.
+                    None if chars.as_str().starts_with("\\u") => {
+                        chars.nth(1);
+                        let low = code(&mut chars)?;
+                        char::decode_utf16([high, low]).next().and_then(Result::ok).ok_or("invalid surrogate pair")?
+                    }
+                    None => return Err("invalid surrogate pair".to_string()),
+                };
+                unescaped.push(c);
+            }
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    Ok(unescaped)
+}
+
+/// Writes the generic representation of a config as a `.properties` file, with nested keys joined by dots.
+/// Lists of values are written separated by commas. Characters outside of ASCII are escaped, so the file can be read as ISO 8859-1 like `java.util.Properties` does by default.
+pub(crate) fn to_string(value: &Value) -> Result<String, Error> {
+    Ok(write(flatten(value, SEPARATOR, "a .properties file")?))
+}
+
+/// Writes dotted keys and their values as a `.properties` file.
+pub(crate) fn write(pairs: impl IntoIterator<Item = (String, String)>) -> String {
+    let mut out = String::new();
+    for (key, value) in pairs {
+        escape(&mut out, &key, true);
+        out.push('=');
+        escape(&mut out, &value, false);
+        out.push('\n');
+    }
+    out
+}
+
+fn escape(out: &mut String, text: &str, key: bool) {
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\u{c}' => out.push_str("\\f"),
+            '=' | ':' | '#' | '!' if key => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' ' if key || i == 0 => out.push_str("\\ "),
+            c if c.is_ascii() && !c.is_ascii_control() => out.push(c),
+            c => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    out.push_str(&format!("\\u{:04x}", unit));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use figment::providers::Serialized;
+use figment::value::{Dict, Map};
+use figment::{Figment, Metadata, Profile, Provider};
+
+use crate::Error;
+
+/// Provides the current values of a config to figment, so it can be merged with other providers. See [`crate::ConfigBuilder::merge_provider`] for the other way around.
+///
+/// # Example
+/// ```
+/// use opzioni::blocking::Config;
+/// use figment::{Figment, providers::Serialized};
+/// use serde::{Serialize, Deserialize};
+/// use std::path::Path;
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// let config = Config::<MyConfig>::configure().load(Path::new("testconfig.json")).unwrap();
+/// let figment = Figment::from(&config).merge(Serialized::default("age", 43));
+/// let merged: MyConfig = figment.extract().unwrap();
+/// assert_eq!(merged.name, "John");
+/// assert_eq!(merged.age, 43);
+/// ```
+impl<T> Provider for crate::blocking::Config<T>
+    where T: serde::Serialize + serde::de::DeserializeOwned + Default + Clone {
+    fn metadata(&self) -> Metadata {
+        metadata(self.path().as_deref())
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
+        Serialized::defaults(&*self.read()).data()
+    }
+}
+
+/// Provides the current values of an async config to figment, like the blocking config does.
+/// figment reads providers synchronously, so the data is read without waiting for the lock and fails while the config is locked for writing.
+#[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+impl<T> Provider for crate::sync::Config<T>
+    where T: serde::Serialize + serde::de::DeserializeOwned + Default + Clone + Send + Sync {
+    fn metadata(&self) -> Metadata {
+        metadata(self.path().as_deref())
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
+        match self.try_read() {
+            Some(config) => Serialized::defaults(&*config).data(),
+            None => Err(figment::Error::from("the config is locked for writing".to_string())),
+        }
+    }
+}
+
+/// Names configs in the errors of figment, with the path of their file if they have one.
+fn metadata(path: Option<&std::path::Path>) -> Metadata {
+    match path {
+        Some(path) => Metadata::from("opzioni config", path),
+        None => Metadata::named("opzioni config"),
+    }
+}
+
+/// Merges the values of the providers added with [`crate::ConfigBuilder::merge_provider`] over the config.
+pub(crate) fn merge<T>(config: &mut T, providers: &Figment) -> Result<(), Error>
+    where T: serde::Serialize + serde::de::DeserializeOwned {
+    *config = Figment::from(Serialized::defaults(&*config)).merge(providers.clone()).extract().map_err(|err| Error::load(err.to_string()))?;
+    Ok(())
+}
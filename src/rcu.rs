@@ -0,0 +1,128 @@
+use std::sync::Arc;
+#[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+use std::sync::PoisonError;
+
+use arc_swap::ArcSwap;
+
+use crate::{Config, Error};
+
+/// A config for read-heavy services, where readers take cheap snapshots instead of locking.
+/// Every read returns an `Arc` of the current version, which stays valid while writers publish new versions, so readers never wait for writers and never block them.
+/// Writers build a new version from a copy of the current one, which is validated before it is published.
+/// It is loaded with [`crate::ConfigBuilder::load_rcu`]. A [`Config`] kept inside keeps the file, the settings of the builder and the background tasks. Saving writes the current version through it and reloading publishes the reloaded version.
+/// Cloning the config is cheap and all clones share the published version.
+///
+/// # Example
+/// ```
+/// use opzioni::Config;
+/// use serde::{Serialize, Deserialize};
+/// use std::path::Path;
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// let config = Config::<MyConfig>::configure().load_rcu(Path::new("testconfig.json")).unwrap();
+/// let snapshot = config.load();
+/// config.update(|draft| draft.age += 1).unwrap();
+/// assert_eq!(snapshot.age, 42);
+/// assert_eq!(config.load().age, 43);
+/// ```
+#[derive(Clone)]
+pub struct RcuConfig<T>
+    where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone + Send + Sync {
+    config: Config<T>,
+    current: Arc<ArcSwap<T>>,
+}
+
+impl<T> RcuConfig<T>
+    where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone + Send + Sync {
+    /// Publishes the value of a config which was just loaded as the first version.
+    /// The value is taken from the config without locking it, which needs the only handle to the config. Fails with [`Error::ConfigLoadError`] if there are others.
+    pub(crate) fn new(mut config: Config<T>) -> Result<Self, Error> {
+        let inner = Arc::get_mut(&mut config.inner).ok_or_else(|| Error::load("the config was shared before it was published"))?;
+        #[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+        let first = inner.config.get_mut().unwrap_or_else(PoisonError::into_inner).clone();
+        #[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+        let first = inner.config.get_mut().clone();
+        Ok(Self { config, current: Arc::new(ArcSwap::from_pointee(first)) })
+    }
+
+    /// Returns the current version of the config. It does not change when a new version is published, load the config again to see it.
+    pub fn load(&self) -> Arc<T> {
+        self.current.load_full()
+    }
+
+    /// Publishes a new version of the config, after checking it with the checks and the validator of the builder.
+    pub fn publish(&self, config: T) -> Result<(), Error> {
+        self.config.inner.settings.validate(&config)?;
+        self.current.store(Arc::new(config));
+        Ok(())
+    }
+
+    /// Publishes a new version of the config, which is changed by `update` from a copy of the current one and validated like in [`RcuConfig::publish`].
+    /// If another writer published a version in the meantime, `update` is called again with a copy of that one, so no change is lost.
+    pub fn update<F>(&self, mut update: F) -> Result<(), Error>
+        where F: FnMut(&mut T) {
+        let mut current = self.current.load_full();
+        loop {
+            let mut draft = T::clone(&current);
+            update(&mut draft);
+            self.config.inner.settings.validate(&draft)?;
+            let previous = self.current.compare_and_swap(&current, Arc::new(draft));
+            if Arc::ptr_eq(&previous, &current) {
+                return Ok(());
+            }
+            current = arc_swap::Guard::into_inner(previous);
+        }
+    }
+
+    /// Returns the [`Config`] which keeps the file of the config. Its lock only holds the version which was saved or reloaded last.
+    pub fn config(&self) -> &Config<T> {
+        &self.config
+    }
+
+    /// Saves the current version like [`Config::save`].
+    #[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+    pub fn save(&self) -> Result<(), Error> {
+        self.config.replace(T::clone(&self.load()));
+        self.config.save()
+    }
+
+    /// Saves the current version like [`Config::save`].
+    #[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+    pub async fn save(&self) -> Result<(), Error>
+        where T: 'static {
+        self.config.replace(T::clone(&self.load())).await;
+        self.config.save().await
+    }
+
+    /// Reloads the config like [`Config::reload`] and publishes the reloaded version if the file changed. Returns whether it did.
+    #[cfg(not(any(feature = "tokio", feature = "async-std", feature = "smol")))]
+    pub fn reload(&self) -> Result<bool, Error> {
+        let changed = self.config.reload()?;
+        if changed {
+            self.current.store(Arc::new(self.config.snapshot()));
+        }
+        Ok(changed)
+    }
+
+    /// Reloads the config like [`Config::reload`] and publishes the reloaded version if the file changed. Returns whether it did.
+    #[cfg(any(feature = "tokio", feature = "async-std", feature = "smol"))]
+    pub async fn reload(&self) -> Result<bool, Error> {
+        let changed = self.config.reload().await?;
+        if changed {
+            self.current.store(Arc::new(self.config.snapshot().await));
+        }
+        Ok(changed)
+    }
+}
+
+impl<T> std::fmt::Debug for RcuConfig<T>
+    where T: serde::ser::Serialize + serde::de::DeserializeOwned + Default + Clone + Send + Sync + std::fmt::Debug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RcuConfig").field("current", &self.load()).field("path", &self.config.path()).finish()
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+
+use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, REG_DWORD, REG_EXPAND_SZ, REG_MULTI_SZ, REG_QWORD, REG_SZ};
+use winreg::types::FromRegValue;
+use winreg::{HKEY, RegKey};
+
+use crate::flat::Entry;
+use crate::{Backend, Error, properties};
+
+/// A [`Backend`] keeping the config in a key of the Windows registry, where native Windows apps keep their settings.
+/// Every field becomes a value of the key and nested structs become subkeys. Numbers are stored as `REG_DWORD` or `REG_QWORD` and everything else as `REG_SZ`, with lists separated by commas.
+/// The registry does not know the types of the fields, so the config is exchanged as a `.properties` file and has to be loaded with [`crate::Format::Properties`], which parses the values into the types of the fields.
+/// Values and subkeys which are not part of the config anymore are removed when saving.
+///
+/// # Example
+/// ```no_run
+/// use opzioni::{Config, Format, Registry};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// let registry = Registry::current_user(r"Software\MyApp");
+/// let config = Config::<MyConfig>::configure().create_if_missing().load_from_backend(registry, Format::Properties).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Registry {
+    root: HKEY,
+    path: String,
+}
+
+impl Registry {
+    /// Uses the key at the given path below `HKEY_CURRENT_USER`, for settings of the current user.
+    pub fn current_user(path: &str) -> Self {
+        Self { root: HKEY_CURRENT_USER, path: path.to_string() }
+    }
+
+    /// Uses the key at the given path below `HKEY_LOCAL_MACHINE`, for settings shared by all users. Saving usually needs administrator rights.
+    pub fn local_machine(path: &str) -> Self {
+        Self { root: HKEY_LOCAL_MACHINE, path: path.to_string() }
+    }
+}
+
+impl Backend for Registry {
+    fn read(&self) -> Result<Option<String>, Error> {
+        let key = match RegKey::predef(self.root).open_subkey(&self.path) {
+            Ok(key) => key,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let mut pairs = Vec::new();
+        collect(&key, "", &mut pairs)?;
+        Ok(Some(properties::write(pairs)))
+    }
+
+    fn write(&self, data: &str) -> Result<(), Error> {
+        let Entry::Table(table) = properties::parse(data)? else {
+            return Ok(());
+        };
+        let (key, _) = RegKey::predef(self.root).create_subkey(&self.path)?;
+        store(&key, table)
+    }
+}
+
+/// Collects the values of the key and its subkeys as dotted keys. Values which cannot be part of a config, like binary ones, are skipped.
+fn collect(key: &RegKey, prefix: &str, pairs: &mut Vec<(String, String)>) -> Result<(), Error> {
+    for value in key.enum_values() {
+        let (name, value) = value?;
+        if name.is_empty() {
+            continue;
+        }
+        let value = match value.vtype {
+            REG_SZ | REG_EXPAND_SZ => String::from_reg_value(&value)?,
+            REG_MULTI_SZ => Vec::<String>::from_reg_value(&value)?.join(","),
+            REG_DWORD => u32::from_reg_value(&value)?.to_string(),
+            REG_QWORD => u64::from_reg_value(&value)?.to_string(),
+            _ => continue,
+        };
+        pairs.push((format!("{}{}", prefix, name), value));
+    }
+    for name in key.enum_keys() {
+        let name = name?;
+        collect(&key.open_subkey(&name)?, &format!("{}{}.", prefix, name), pairs)?;
+    }
+    Ok(())
+}
+
+/// Stores the values of the table in the key and removes the values and subkeys which are not part of it.
+fn store(key: &RegKey, table: BTreeMap<String, Entry>) -> Result<(), Error> {
+    let values = key.enum_values().map(|value| value.map(|(name, _)| name)).collect::<Result<Vec<_>, _>>()?;
+    for name in values {
+        if !name.is_empty() && !matches!(table.get(&name), Some(Entry::Value(_))) {
+            key.delete_value(&name)?;
+        }
+    }
+    let subkeys = key.enum_keys().collect::<Result<Vec<_>, _>>()?;
+    for name in subkeys {
+        if !matches!(table.get(&name), Some(Entry::Table(_))) {
+            key.delete_subkey_all(&name)?;
+        }
+    }
+    for (name, entry) in table {
+        match entry {
+            // Numbers are only stored as such if they are written the same way when read again, so a string like `007` keeps its zeros.
+            Entry::Value(value) => match (value.parse::<u32>(), value.parse::<u64>()) {
+                (Ok(number), _) if number.to_string() == value => key.set_value(&name, &number)?,
+                (_, Ok(number)) if number.to_string() == value => key.set_value(&name, &number)?,
+                _ => key.set_value(&name, &value)?,
+            },
+            Entry::Table(table) => {
+                let (subkey, _) = key.create_subkey(&name)?;
+                store(&subkey, table)?;
+            }
+        }
+    }
+    Ok(())
+}
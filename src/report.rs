@@ -0,0 +1,122 @@
+use std::fmt::Display;
+
+use miette::{Diagnostic, LabeledSpan, MietteError, MietteSpanContents, SourceCode, SourceSpan, SpanContents};
+
+use crate::Error;
+
+/// Reports errors with miette. Parse errors point to the line of the config file they occurred in, which miette shows with the path of the file.
+/// Only the line with the error is kept, see [`Error::ParseError`], so the report shows no lines around it.
+///
+/// # Example
+/// ```
+/// use opzioni::blocking::Config;
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, Default, Clone, Debug)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// let path = std::env::temp_dir().join("opzioni-miette.yaml");
+/// std::fs::write(&path, "name: John\nage: old\n").unwrap();
+/// let err = Config::<MyConfig>::configure().load(&path).unwrap_err();
+///
+/// let mut report = String::new();
+/// miette::NarratableReportHandler::new().render_report(&mut report, &err).unwrap();
+/// assert!(report.contains("snippet line 2: age: old"));
+/// assert!(report.contains("label at line 2, column 6: here"));
+/// ```
+impl Diagnostic for Error {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        let code = match self {
+            Error::ConfigLoadError { .. } => "opzioni::ConfigLoadError",
+            Error::UnknownFileExtension { .. } => "opzioni::UnknownFileExtension",
+            Error::ParseError { .. } => "opzioni::ParseError",
+            Error::SerializeError { .. } => "opzioni::SerializeError",
+            Error::IoError { .. } => "opzioni::IoError",
+            Error::ReentrantSave => "opzioni::ReentrantSave",
+            Error::ValidationError { .. } => "opzioni::ValidationError",
+            Error::Poisoned => "opzioni::Poisoned",
+            #[cfg(feature = "file-lock")]
+            Error::AlreadyLocked => "opzioni::AlreadyLocked",
+            Error::Conflict { .. } => "opzioni::Conflict",
+            Error::NoBackingFile => "opzioni::NoBackingFile",
+            Error::EmptyFile { .. } => "opzioni::EmptyFile",
+            #[cfg(any(feature = "checksum", feature = "encryption"))]
+            Error::IntegrityError { .. } => "opzioni::IntegrityError",
+            #[cfg(feature = "json")]
+            Error::MigrationError { .. } => "opzioni::MigrationError",
+            #[cfg(feature = "json")]
+            Error::UnknownFields { .. } => "opzioni::UnknownFields",
+            #[cfg(feature = "json")]
+            Error::InvalidPath(_) => "opzioni::InvalidPath",
+        };
+        Some(Box::new(code))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        let help = match self {
+            Error::UnknownFileExtension { .. } => "enable the feature of the format or select it with `ConfigBuilder::format`",
+            Error::Conflict { .. } => "reload the config to see the changes, or overwrite them with `Config::force_save`",
+            Error::NoBackingFile => "give the config a file with `Config::attach`",
+            Error::EmptyFile { .. } => "set `ConfigBuilder::treat_empty_as_missing` to handle empty files like missing ones",
+            #[cfg(feature = "encryption")]
+            Error::IntegrityError { actual, .. } if actual == crate::encryption::PLAINTEXT => "restore the encrypted file from a backup, or load it once with `ConfigBuilder::allow_plaintext_migration` to encrypt it",
+            #[cfg(feature = "checksum")]
+            Error::IntegrityError { .. } => "restore the file from a backup, or delete its `.sha256` file to accept it as it is",
+            #[cfg(feature = "json")]
+            Error::UnknownFields { .. } => "remove the keys from the file, or add them to the config type",
+            _ => return None,
+        };
+        Some(Box::new(help))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.span().map(|_| self as &dyn SourceCode)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let span = self.span()?;
+        Some(Box::new(std::iter::once(LabeledSpan::new_with_span(Some("here".to_string()), span))))
+    }
+}
+
+/// The line kept in a [`Error::ParseError`] is the source code of its report. Its offsets are the ones within the line.
+impl SourceCode for Error {
+    fn read_span<'a>(&'a self, span: &SourceSpan, _before: usize, _after: usize) -> Result<Box<dyn SpanContents<'a> + 'a>, MietteError> {
+        let Error::ParseError { path, line: Some(line), snippet: Some(snippet), .. } = self else {
+            return Err(MietteError::OutOfBounds);
+        };
+        if span.offset() + span.len() > snippet.len() {
+            return Err(MietteError::OutOfBounds);
+        }
+        let whole = SourceSpan::new(0.into(), snippet.len());
+        let contents = match path {
+            Some(path) => MietteSpanContents::new_named(path.display().to_string(), snippet.as_bytes(), whole, line - 1, 0, 1),
+            None => MietteSpanContents::new(snippet.as_bytes(), whole, line - 1, 0, 1),
+        };
+        Ok(Box::new(contents))
+    }
+}
+
+impl Error {
+    /// Returns the span of the character a [`Error::ParseError`] points to within its line, or of the whole line if it has no column.
+    fn span(&self) -> Option<SourceSpan> {
+        let Error::ParseError { line: Some(line), column, snippet: Some(snippet), .. } = self else {
+            return None;
+        };
+        if *line == 0 {
+            return None;
+        }
+        let Some(column) = column else {
+            return Some(SourceSpan::new(0.into(), snippet.len()));
+        };
+        // Columns count characters, the span counts bytes.
+        let mut chars = snippet.char_indices().skip(column.saturating_sub(1));
+        Some(match chars.next() {
+            Some((offset, c)) => SourceSpan::new(offset.into(), c.len_utf8()),
+            None => SourceSpan::new(snippet.len().into(), 0),
+        })
+    }
+}
@@ -0,0 +1,203 @@
+use std::future::Future;
+use std::time::Duration;
+
+use crate::Error;
+
+// The async config runs on tokio if the `tokio` feature is enabled, otherwise on async-std or smol, in this order.
+// async-std and smol share the `RwLock` of async-lock and only differ in how tasks are spawned and timers are created.
+
+#[cfg(feature = "tokio")]
+pub(crate) use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cfg(not(feature = "tokio"))]
+pub(crate) use async_lock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Locks for reading if no writer holds the lock.
+#[cfg(feature = "tokio")]
+pub(crate) fn try_read<T>(lock: &RwLock<T>) -> Option<RwLockReadGuard<'_, T>> {
+    lock.try_read().ok()
+}
+
+/// Locks for reading if no writer holds the lock.
+#[cfg(not(feature = "tokio"))]
+pub(crate) fn try_read<T>(lock: &RwLock<T>) -> Option<RwLockReadGuard<'_, T>> {
+    lock.try_read()
+}
+
+/// Locks for writing if no reader or writer holds the lock.
+#[cfg(feature = "tokio")]
+pub(crate) fn try_write<T>(lock: &RwLock<T>) -> Option<RwLockWriteGuard<'_, T>> {
+    lock.try_write().ok()
+}
+
+/// Locks for writing if no reader or writer holds the lock.
+#[cfg(not(feature = "tokio"))]
+pub(crate) fn try_write<T>(lock: &RwLock<T>) -> Option<RwLockWriteGuard<'_, T>> {
+    lock.try_write()
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+pub(crate) async fn sleep(duration: Duration) {
+    async_std::task::sleep(duration).await;
+}
+
+#[cfg(all(feature = "smol", not(any(feature = "tokio", feature = "async-std"))))]
+pub(crate) async fn sleep(duration: Duration) {
+    smol::Timer::after(duration).await;
+}
+
+/// Waits at most `duration` for the future. Returns `None` if the time ran out.
+#[cfg(feature = "tokio")]
+pub(crate) async fn timeout<F>(duration: Duration, future: F) -> Option<F::Output>
+    where F: Future {
+    tokio::time::timeout(duration, future).await.ok()
+}
+
+/// Waits at most `duration` for the future. Returns `None` if the time ran out.
+#[cfg(not(feature = "tokio"))]
+pub(crate) async fn timeout<F>(duration: Duration, future: F) -> Option<F::Output>
+    where F: Future {
+    use futures_lite::FutureExt;
+
+    async { Some(future.await) }.or(async {
+        sleep(duration).await;
+        None
+    }).await
+}
+
+/// Runs blocking work on the thread pool of the runtime, so it does not block other tasks.
+#[cfg(feature = "tokio")]
+pub(crate) async fn unblock<F, R>(work: F) -> Result<R, Error>
+    where F: FnOnce() -> R + Send + 'static, R: Send + 'static {
+    tokio::task::spawn_blocking(work).await.map_err(|err| Error::load(err.to_string()))
+}
+
+/// Runs blocking work on the thread pool of the runtime, so it does not block other tasks.
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+pub(crate) async fn unblock<F, R>(work: F) -> Result<R, Error>
+    where F: FnOnce() -> R + Send + 'static, R: Send + 'static {
+    Ok(async_std::task::spawn_blocking(work).await)
+}
+
+/// Runs blocking work on the thread pool of the runtime, so it does not block other tasks.
+#[cfg(all(feature = "smol", not(any(feature = "tokio", feature = "async-std"))))]
+pub(crate) async fn unblock<F, R>(work: F) -> Result<R, Error>
+    where F: FnOnce() -> R + Send + 'static, R: Send + 'static {
+    Ok(smol::unblock(work).await)
+}
+
+/// Runs a future to completion on the current thread, parking it while the future waits.
+/// The futures of the config only wait for its lock, which is released by other threads or tasks, so they do not need the reactor of a runtime.
+pub(crate) fn block_on<F>(future: F) -> F::Output
+    where F: Future {
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct Unpark(std::thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+/// A background task, which keeps running when it is dropped.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub(crate) struct Task(tokio::task::JoinHandle<()>);
+
+#[cfg(feature = "tokio")]
+impl Task {
+    pub(crate) fn spawn<F>(future: F) -> Self
+        where F: Future<Output = ()> + Send + 'static {
+        Self(tokio::spawn(future))
+    }
+
+    pub(crate) fn stop(&self) {
+        self.0.abort();
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+}
+
+/// A background task, which keeps running when it is dropped.
+/// async-std and smol can only cancel tasks asynchronously, so the task is raced against a signal which [`Task::stop`] sets instead.
+#[cfg(not(feature = "tokio"))]
+#[derive(Debug)]
+pub(crate) struct Task(std::sync::Arc<Signal>);
+
+#[cfg(not(feature = "tokio"))]
+#[derive(Debug, Default)]
+pub(crate) struct Signal {
+    stopped: std::sync::atomic::AtomicBool,
+    finished: std::sync::atomic::AtomicBool,
+    waker: std::sync::Mutex<Option<std::task::Waker>>,
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Task {
+    pub(crate) fn spawn<F>(future: F) -> Self
+        where F: Future<Output = ()> + Send + 'static {
+        use futures_lite::FutureExt;
+        use std::sync::atomic::Ordering;
+        use std::task::Poll;
+
+        let signal = std::sync::Arc::new(Signal::default());
+        let task = signal.clone();
+        let stopped = futures_lite::future::poll_fn(move |cx| {
+            *task.waker.lock().unwrap_or_else(std::sync::PoisonError::into_inner) = Some(cx.waker().clone());
+            match task.stopped.load(Ordering::Acquire) {
+                true => Poll::Ready(()),
+                false => Poll::Pending,
+            }
+        });
+        let finished = signal.clone();
+        detach(async move {
+            future.or(stopped).await;
+            finished.finished.store(true, Ordering::Release);
+        });
+        Self(signal)
+    }
+
+    pub(crate) fn stop(&self) {
+        self.0.stopped.store(true, std::sync::atomic::Ordering::Release);
+        if let Some(waker) = self.0.waker.lock().unwrap_or_else(std::sync::PoisonError::into_inner).take() {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.0.finished.load(std::sync::atomic::Ordering::Acquire)
+    }
+}
+
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+fn detach<F>(future: F)
+    where F: Future<Output = ()> + Send + 'static {
+    // Dropping the handle of an async-std task detaches it.
+    async_std::task::spawn(future);
+}
+
+#[cfg(all(feature = "smol", not(any(feature = "tokio", feature = "async-std"))))]
+fn detach<F>(future: F)
+    where F: Future<Output = ()> + Send + 'static {
+    smol::spawn(future).detach();
+}
@@ -0,0 +1,278 @@
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+use crate::{Diagnostic, DiagnosticKind, Error, diagnostics, manager};
+
+/// Returns the JSON Schema of the config type, see [`crate::Config::json_schema`].
+pub(crate) fn json_schema<T>() -> Value
+    where T: schemars::JsonSchema {
+    // A schema only has string keys, so it always converts.
+    serde_json::to_value(schemars::schema_for!(T)).unwrap_or_default()
+}
+
+/// Checks a config file against the JSON Schema of `T` and returns every violation, instead of stopping at the first error like deserializing does.
+/// The file is parsed in the format of its extension but not deserialized, so it can be checked before it is loaded, for example by a `config check` subcommand.
+/// Migrations, includes, profiles and the other options of [`crate::ConfigBuilder`] are not applied. A file which cannot be parsed is reported with the error of the parser.
+///
+/// The checks cover what schemars generates for Rust types: types, required and unknown keys, enums, the ranges of integer types and the length of arrays and strings.
+/// Patterns of strings are not checked. Unknown keys are only reported for types with `#[serde(deny_unknown_fields)]`, which is what their schema allows.
+///
+/// # Example
+/// ```
+/// use opzioni::DiagnosticKind;
+/// use schemars::JsonSchema;
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, JsonSchema, Default)]
+/// struct Server {
+///   host: String,
+///   port: u16,
+/// }
+///
+/// #[derive(Serialize, Deserialize, JsonSchema, Default)]
+/// struct MyConfig {
+///   name: String,
+///   servers: Vec<Server>,
+/// }
+///
+/// let path = std::env::temp_dir().join("opzioni-validate-file.json");
+/// std::fs::write(&path, r#"{ "servers": [{ "host": "localhost", "port": 70000 }, { "host": 42, "port": 80 }] }"#).unwrap();
+/// let violations = opzioni::validate_file::<MyConfig>(&path).unwrap();
+/// for violation in &violations {
+///     println!("{}", violation);
+/// }
+/// assert_eq!(violations.len(), 3);
+/// assert!(violations.iter().any(|v| v.path == "name" && v.kind == DiagnosticKind::MissingKey));
+/// assert!(violations.iter().any(|v| v.path == "servers[0].port"));
+/// assert!(violations.iter().any(|v| v.path == "servers[1].host" && matches!(v.kind, DiagnosticKind::InvalidType { .. })));
+/// ```
+pub fn validate_file<T>(path: impl AsRef<Path>) -> Result<Vec<Diagnostic>, Error>
+    where T: schemars::JsonSchema {
+    let path = path.as_ref();
+    let value = match manager::for_file::<Value>(path)?.load_value() {
+        Ok(value) => value,
+        Err(err) => match &err {
+            Error::ParseError { line, column, .. } => return Ok(vec![Diagnostic::invalid(err.parser_message(), line.zip(*column))]),
+            _ => return Err(err),
+        },
+    };
+    let schema = json_schema::<T>();
+    let mut violations = Vec::new();
+    Validator { root: &schema }.check(&value, &schema, "", &mut violations);
+    Ok(violations)
+}
+
+/// Checks values against the schemas of a root schema, whose definitions are referenced with `$ref`.
+struct Validator<'a> {
+    root: &'a Value,
+}
+
+impl Validator<'_> {
+    fn check(&self, value: &Value, schema: &Value, path: &str, violations: &mut Vec<Diagnostic>) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return violations.push(violation(path, "is not allowed".to_string())),
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+        if let Some(target) = schema.get("$ref").and_then(Value::as_str) {
+            if let Some(resolved) = self.resolve(target) {
+                self.check(value, resolved, path, violations);
+            }
+        }
+        if let Some(expected) = schema.get("type") {
+            let types: Vec<&str> = match expected {
+                Value::String(name) => vec![name.as_str()],
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.is_empty() && !types.iter().any(|name| has_type(value, name)) {
+                let expected = types.iter().map(|name| type_name(name)).collect::<Vec<_>>().join(" or ");
+                let kind = DiagnosticKind::InvalidType { expected, found: diagnostics::type_name(value).to_string() };
+                // The other keywords describe values of the expected type, so they would only repeat the mismatch.
+                return violations.push(Diagnostic { path: path.to_string(), kind, line: None, column: None });
+            }
+        }
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+            if !allowed.contains(value) {
+                let allowed = allowed.iter().map(Value::to_string).collect::<Vec<_>>().join(", ");
+                violations.push(violation(path, format!("must be one of {}", allowed)));
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            if value != constant {
+                violations.push(violation(path, format!("must be {}", constant)));
+            }
+        }
+        match value {
+            Value::Object(map) => self.check_map(map, schema, path, violations),
+            Value::Array(values) => self.check_array(values, schema, path, violations),
+            Value::Number(_) => check_number(value, schema, path, violations),
+            Value::String(string) => check_string(string, schema, path, violations),
+            _ => {}
+        }
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            for schema in all {
+                self.check(value, schema, path, violations);
+            }
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(any) = schema.get(keyword).and_then(Value::as_array) {
+                self.check_any(value, any, path, violations);
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            let mut matched = Vec::new();
+            self.check(value, not, path, &mut matched);
+            if matched.is_empty() {
+                violations.push(violation(path, "matches a schema it must not match".to_string()));
+            }
+        }
+    }
+
+    fn check_map(&self, map: &Map<String, Value>, schema: &Map<String, Value>, path: &str, violations: &mut Vec<Diagnostic>) {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for key in required.iter().filter_map(Value::as_str).filter(|key| !map.contains_key(*key)) {
+                violations.push(Diagnostic { path: join(path, key), kind: DiagnosticKind::MissingKey, line: None, column: None });
+            }
+        }
+        for (key, child) in map {
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(property) => self.check(child, property, &join(path, key), violations),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => violations.push(Diagnostic { path: join(path, key), kind: DiagnosticKind::UnknownKey, line: None, column: None }),
+                    Some(additional) => self.check(child, additional, &join(path, key), violations),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    fn check_array(&self, values: &[Value], schema: &Map<String, Value>, path: &str, violations: &mut Vec<Diagnostic>) {
+        let index = |i: usize| format!("{}[{}]", path, i);
+        match schema.get("items") {
+            // Tuples have a schema per position.
+            Some(Value::Array(items)) => {
+                for (i, (value, item)) in values.iter().zip(items).enumerate() {
+                    self.check(value, item, &index(i), violations);
+                }
+            }
+            Some(item) => {
+                for (i, value) in values.iter().enumerate() {
+                    self.check(value, item, &index(i), violations);
+                }
+            }
+            None => {}
+        }
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64).filter(|&min| (values.len() as u64) < min) {
+            violations.push(violation(path, format!("must have at least {} elements", min)));
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64).filter(|&max| (values.len() as u64) > max) {
+            violations.push(violation(path, format!("must have at most {} elements", max)));
+        }
+    }
+
+    /// Checks a value against alternatives, like the variants of an enum or an `Option`. If none matches, the violations of the closest one are reported.
+    /// Alternatives for values of another type are the farthest, then the ones with more violations.
+    fn check_any(&self, value: &Value, any: &[Value], path: &str, violations: &mut Vec<Diagnostic>) {
+        let mut closest: Option<((bool, usize), Vec<Diagnostic>)> = None;
+        for schema in any {
+            let mut found = Vec::new();
+            self.check(value, schema, path, &mut found);
+            if found.is_empty() {
+                return;
+            }
+            let mismatched = found.iter().any(|violation| violation.path == path && matches!(violation.kind, DiagnosticKind::InvalidType { .. }));
+            let distance = (mismatched, found.len());
+            if closest.as_ref().map_or(true, |(closest, _)| distance < *closest) {
+                closest = Some((distance, found));
+            }
+        }
+        violations.extend(closest.map(|(_, found)| found).unwrap_or_default());
+    }
+
+    /// Returns the schema a `$ref` like `#/definitions/Server` points to.
+    fn resolve(&self, target: &str) -> Option<&Value> {
+        let pointer = target.strip_prefix('#')?;
+        self.root.pointer(pointer)
+    }
+}
+
+fn check_number(value: &Value, schema: &Map<String, Value>, path: &str, violations: &mut Vec<Diagnostic>) {
+    let Some(number) = value.as_f64() else {
+        return;
+    };
+    let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+    // schemars describes the size of integer types with their format, the bounds only narrow it.
+    let range = match schema.get("format").and_then(Value::as_str) {
+        Some("uint8") => Some((0.0, u8::MAX as f64)),
+        Some("uint16") => Some((0.0, u16::MAX as f64)),
+        Some("uint32") => Some((0.0, u32::MAX as f64)),
+        Some("uint64" | "uint") => Some((0.0, u64::MAX as f64)),
+        Some("int8") => Some((i8::MIN as f64, i8::MAX as f64)),
+        Some("int16") => Some((i16::MIN as f64, i16::MAX as f64)),
+        Some("int32") => Some((i32::MIN as f64, i32::MAX as f64)),
+        Some("int64" | "int") => Some((i64::MIN as f64, i64::MAX as f64)),
+        _ => None,
+    };
+    let min = [bound("minimum"), range.map(|(min, _)| min)].into_iter().flatten().reduce(f64::max);
+    let max = [bound("maximum"), range.map(|(_, max)| max)].into_iter().flatten().reduce(f64::min);
+    if min.is_some_and(|min| number < min) || max.is_some_and(|max| number > max) {
+        let message = match (min, max) {
+            (Some(min), Some(max)) => format!("must be between {} and {}", min, max),
+            (Some(min), None) => format!("must be at least {}", min),
+            (None, Some(max)) => format!("must be at most {}", max),
+            (None, None) => unreachable!(),
+        };
+        violations.push(violation(path, message));
+    }
+    if let Some(min) = bound("exclusiveMinimum").filter(|&min| number <= min) {
+        violations.push(violation(path, format!("must be greater than {}", min)));
+    }
+    if let Some(max) = bound("exclusiveMaximum").filter(|&max| number >= max) {
+        violations.push(violation(path, format!("must be less than {}", max)));
+    }
+}
+
+fn check_string(string: &str, schema: &Map<String, Value>, path: &str, violations: &mut Vec<Diagnostic>) {
+    let length = string.chars().count() as u64;
+    if let Some(min) = schema.get("minLength").and_then(Value::as_u64).filter(|&min| length < min) {
+        violations.push(violation(path, format!("must have at least {} characters", min)));
+    }
+    if let Some(max) = schema.get("maxLength").and_then(Value::as_u64).filter(|&max| length > max) {
+        violations.push(violation(path, format!("must have at most {} characters", max)));
+    }
+}
+
+fn violation(path: &str, message: String) -> Diagnostic {
+    Diagnostic { path: path.to_string(), kind: DiagnosticKind::Invalid(message), line: None, column: None }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) }
+}
+
+/// Returns whether the value has the given JSON Schema type.
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
+
+/// Returns the name of a JSON Schema type like the names of [`DiagnosticKind::InvalidType`].
+fn type_name(name: &str) -> &str {
+    match name {
+        "boolean" => "bool",
+        "object" => "map",
+        name => name,
+    }
+}
@@ -0,0 +1,54 @@
+use std::path::{Path, PathBuf};
+
+use crate::manager::extensions;
+
+/// A list of directories and file names to search for a config file, used by [`crate::ConfigBuilder::search`].
+/// Every combination of directory, name and the extension of an enabled format is tried, directories first, then names, then extensions in the order `json`, `toml`, `yaml` and `yml`, followed by the ones registered with [`crate::FormatRegistry`].
+/// The first existing file wins, so more specific directories and names should come first.
+///
+/// # Example
+/// ```
+/// use opzioni::SearchPaths;
+/// use std::path::Path;
+///
+/// let search = SearchPaths::new().dir(Path::new("/etc/myapp")).dir(Path::new(".")).name("myapp").name("testconfig");
+/// assert_eq!(search.find(), Some(Path::new(".").join("testconfig.json")));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchPaths {
+    dirs: Vec<PathBuf>,
+    names: Vec<String>,
+}
+
+impl SearchPaths {
+    /// Creates an empty list, which finds nothing until directories and names are added.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a directory to search. Directories are searched in the order they were added.
+    pub fn dir(mut self, dir: &Path) -> Self {
+        self.dirs.push(dir.to_path_buf());
+        self
+    }
+
+    /// Adds a file name without extension to look for. Names are tried in the order they were added.
+    pub fn name(mut self, name: &str) -> Self {
+        self.names.push(name.to_string());
+        self
+    }
+
+    /// Returns the first existing file, or `None` if there is none.
+    pub fn find(&self) -> Option<PathBuf> {
+        self.candidates().find(|path| path.is_file())
+    }
+
+    /// Returns every path which is tried, in the order they are tried.
+    pub(crate) fn candidates(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.dirs.iter().flat_map(move |dir| {
+            self.names
+                .iter()
+                .flat_map(move |name| extensions().into_iter().map(move |ext| dir.join(format!("{}.{}", name, ext))))
+        })
+    }
+}
@@ -0,0 +1,147 @@
+#[cfg(feature = "tracing")]
+use std::cell::Cell;
+use std::fmt::{Debug, Formatter};
+use std::str::FromStr;
+
+/// Types which can be wrapped in a [`Secret`].
+/// Without the `zeroize` feature every type qualifies. With the `zeroize` feature the type has to implement [`zeroize::Zeroize`] so it can be wiped on drop.
+#[cfg(not(feature = "zeroize"))]
+pub trait SecretValue {}
+
+#[cfg(not(feature = "zeroize"))]
+impl<T> SecretValue for T {}
+
+/// Types which can be wrapped in a [`Secret`].
+/// Without the `zeroize` feature every type qualifies. With the `zeroize` feature the type has to implement [`zeroize::Zeroize`] so it can be wiped on drop.
+#[cfg(feature = "zeroize")]
+pub trait SecretValue: zeroize::Zeroize {}
+
+#[cfg(feature = "zeroize")]
+impl<T> SecretValue for T where T: zeroize::Zeroize {}
+
+/// A config value which must not leak, like a password or an API token.
+/// The wrapped value is serialized and deserialized as if it was not wrapped, but its `Debug` output is redacted. It is also masked in the configs logged by the `tracing` feature.
+/// With the `zeroize` feature the value is wiped from memory when the Secret is dropped.
+/// With the `keyring` feature and [`crate::ConfigBuilder::use_keyring`], the value is kept in the credential store of the operating system instead of the config file.
+///
+/// # Example
+/// ```
+/// use opzioni::Secret;
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, Debug)]
+/// struct MyConfig {
+///   user: String,
+///   password: Secret<String>,
+/// }
+///
+/// let config: MyConfig = serde_json::from_str(r#"{"user": "john", "password": "hunter2"}"#).unwrap();
+/// assert_eq!(config.password.expose(), "hunter2");
+/// assert!(!format!("{:?}", config).contains("hunter2"));
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret<T>(T) where T: SecretValue;
+
+impl<T> Secret<T> where T: SecretValue {
+    /// Wraps the given value.
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Returns a reference to the wrapped value.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Returns a mutable reference to the wrapped value.
+    pub fn expose_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> where T: SecretValue {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> FromStr for Secret<T> where T: SecretValue + FromStr {
+    type Err = T::Err;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value.parse().map(Self)
+    }
+}
+
+impl<T> Debug for Secret<T> where T: SecretValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret([redacted])")
+    }
+}
+
+impl<T> serde::Serialize for Secret<T> where T: SecretValue + serde::Serialize {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: serde::Serializer {
+        #[cfg(feature = "tracing")]
+        if REDACTING.with(Cell::get) {
+            return serializer.serialize_str(REDACTED);
+        }
+        #[cfg(feature = "keyring")]
+        if let Some(marker) = crate::credentials::collect(&self.0) {
+            return serializer.serialize_str(&marker);
+        }
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T> serde::Deserialize<'de> for Secret<T> where T: SecretValue + serde::Deserialize<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: serde::Deserializer<'de> {
+        T::deserialize(deserializer).map(Self)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T> zeroize::Zeroize for Secret<T> where T: SecretValue {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T> Drop for Secret<T> where T: SecretValue {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T> zeroize::ZeroizeOnDrop for Secret<T> where T: SecretValue {}
+
+/// Replaces secret values in logged configs.
+#[cfg(feature = "tracing")]
+pub(crate) const REDACTED: &str = "[redacted]";
+
+#[cfg(feature = "tracing")]
+thread_local! {
+    /// Whether a config is serialized for the logs on this thread, so every [`Secret`] is masked.
+    static REDACTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `serialize` with every [`Secret`] serialized as [`REDACTED`], to log a config without leaking its secrets.
+#[cfg(feature = "tracing")]
+pub(crate) fn redacting<R>(serialize: impl FnOnce() -> R) -> R {
+    REDACTING.with(|redacting| redacting.set(true));
+    let serialized = serialize();
+    REDACTING.with(|redacting| redacting.set(false));
+    serialized
+}
+
+/// Drops a buffer holding a serialized config. With the `zeroize` feature the buffer is wiped first, because it may contain secrets.
+pub(crate) fn wipe(data: impl Into<Vec<u8>>) {
+    #[cfg(feature = "zeroize")]
+    {
+        let mut data = data.into();
+        zeroize::Zeroize::zeroize(&mut data);
+    }
+    #[cfg(not(feature = "zeroize"))]
+    drop(data);
+}
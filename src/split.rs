@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::config::is_empty_file;
+use crate::manager::{self, ConfigManager};
+use crate::secret::wipe;
+use crate::{Error, Format, SaveOptions};
+
+/// The length which marks a section file that does not exist.
+const ABSENT: u64 = u64::MAX;
+
+/// Keeps top-level sections of a config in their own files, see [`crate::ConfigBuilder::map_section`].
+/// The data of the main file and of the section files is passed around as one, each part prefixed with its length, so it is hashed, compared and written like the data of a single file.
+pub(crate) struct Split<'a, T> {
+    pub(crate) inner: Box<dyn ConfigManager<T> + 'a>,
+    /// The keys of the sections and their files, relative to the directory of the main file unless they are absolute.
+    pub(crate) sections: Vec<(String, PathBuf)>,
+    /// The permissions of the section files, like the ones of the main file.
+    pub(crate) mode: Option<u32>,
+    /// The layout of the section files, like the one of the main file.
+    pub(crate) options: SaveOptions,
+}
+
+impl<'a, T> Split<'a, T> {
+    pub(crate) fn new(inner: Box<dyn ConfigManager<T> + 'a>, sections: &[(String, PathBuf)], path: &Path, mode: Option<u32>, options: &SaveOptions) -> Self {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let sections = sections.iter().map(|(key, file)| (key.clone(), dir.join(file))).collect();
+        Self { inner, sections, mode, options: options.clone() }
+    }
+}
+
+impl<T> ConfigManager<T> for Split<'_, T>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    fn format(&self) -> Format {
+        self.inner.format()
+    }
+
+    fn path(&self) -> &Path {
+        self.inner.path()
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Error> {
+        let mut parts = vec![Some(self.inner.read()?)];
+        for (_, file) in &self.sections {
+            let part = match file.exists() && !is_empty_file(file) {
+                true => Some(manager::for_file::<Value>(file)?.read()?),
+                false => None,
+            };
+            parts.push(part);
+        }
+        Ok(join(parts))
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), Error> {
+        let mut parts = self.parts(data)?.into_iter();
+        let main = parts.next().flatten().unwrap_or_default();
+        for ((_, file), part) in self.sections.iter().zip(parts) {
+            if let Some(part) = part {
+                // A new section file gets the permissions of the main file, so secrets moved out of it are not more exposed than before.
+                let mode = match file.exists() {
+                    true => self.mode,
+                    false => self.mode.or_else(|| manager::mode_of(self.inner.path())),
+                };
+                manager::with_mode(manager::for_file::<Value>(file)?, mode).write(part)?;
+            }
+        }
+        self.inner.write(main)
+    }
+
+    fn load(&self) -> Result<T, Error> {
+        Ok(serde_json::from_value(self.load_value()?)?)
+    }
+
+    fn load_value(&self) -> Result<Value, Error> {
+        let data = self.read()?;
+        let value = self.parse(&data);
+        wipe(data);
+        value
+    }
+
+    fn diagnose(&self) -> Result<Vec<crate::Diagnostic>, Error> {
+        self.inner.diagnose()
+    }
+
+    fn serialize(&self, config: &T) -> Result<Vec<u8>, Error> {
+        self.serialize_value(&serde_json::to_value(config).map_err(Error::serialize)?)
+    }
+
+    fn serialize_value(&self, value: &Value) -> Result<Vec<u8>, Error> {
+        let mut main = value.clone();
+        let mut sections = Vec::new();
+        for (key, file) in &self.sections {
+            let section = match main.as_object_mut().and_then(|map| map.remove(key)) {
+                Some(section) => {
+                    let format = Format::from_path(file).map_err(|err| err.at(file, None))?;
+                    Some(format.serialize_with(&section, &self.options).map_err(|err| err.at(file, Some(format)))?)
+                }
+                None => None,
+            };
+            sections.push(section);
+        }
+        let mut parts = vec![Some(self.inner.serialize_value(&main)?)];
+        parts.extend(sections);
+        Ok(join(parts))
+    }
+}
+
+impl<T> Split<'_, T>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned + Default,
+{
+    /// Splits the data into the main file and the section files.
+    fn parts<'d>(&self, mut data: &'d [u8]) -> Result<Vec<Option<&'d [u8]>>, Error> {
+        let mut parts = Vec::new();
+        while !data.is_empty() {
+            let (length, rest) = data.split_at(8.min(data.len()));
+            let length = u64::from_le_bytes(length.try_into().map_err(|_| Error::load("the split config data is truncated"))?);
+            if length == ABSENT {
+                parts.push(None);
+                data = rest;
+                continue;
+            }
+            let length = usize::try_from(length).ok().filter(|&length| length <= rest.len()).ok_or_else(|| Error::load("the split config data is truncated"))?;
+            let (part, rest) = rest.split_at(length);
+            parts.push(Some(part));
+            data = rest;
+        }
+        if parts.len() != self.sections.len() + 1 {
+            return Err(Error::load("the split config data does not match the sections"));
+        }
+        Ok(parts)
+    }
+
+    /// Parses the main file and puts every section file which exists into it.
+    fn parse(&self, data: &[u8]) -> Result<Value, Error> {
+        let mut parts = self.parts(data)?.into_iter();
+        let mut value: Value = self.inner.format().parse(parts.next().flatten().unwrap_or_default())?;
+        for ((key, file), part) in self.sections.iter().zip(parts) {
+            let Some(part) = part else {
+                continue;
+            };
+            let format = Format::from_path(file).map_err(|err| err.at(file, None))?;
+            let section: Value = format.parse(part).map_err(|err| err.at(file, Some(format)))?;
+            if !value.is_object() {
+                value = Value::Object(Map::new());
+            }
+            if let Value::Object(map) = &mut value {
+                map.insert(key.clone(), section);
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// Joins the parts, each prefixed with its length.
+fn join(parts: Vec<Option<Vec<u8>>>) -> Vec<u8> {
+    let mut data = Vec::new();
+    for part in parts {
+        match part {
+            Some(part) => {
+                data.extend_from_slice(&(part.len() as u64).to_le_bytes());
+                data.extend_from_slice(&part);
+                wipe(part);
+            }
+            None => data.extend_from_slice(&ABSENT.to_le_bytes()),
+        }
+    }
+    data
+}
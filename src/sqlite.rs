@@ -0,0 +1,91 @@
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::{Backend, Error};
+
+/// A [`Backend`] keeping the config in a table of a SQLite database, so applications which already ship a database do not need a separate config file.
+/// Every config is a row of the table `opzioni_configs`, identified by its name, together with a version and the time it was last saved. The table is created if it does not exist.
+/// Saving is optimistic: if another connection saved the config since it was last read or saved through this backend, the save fails with [`Error::Conflict`] and the config has to be reloaded first.
+///
+/// # Example
+/// ```
+/// use opzioni::{Config, Format, Sqlite};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   name: String,
+///   age: u8,
+/// }
+///
+/// let path = std::env::temp_dir().join("opzioni-sqlite.db");
+/// let sqlite = Sqlite::open(&path, "settings").unwrap();
+/// let config = Config::<MyConfig>::configure().create_if_missing().load_from_backend(sqlite, Format::Json).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Sqlite {
+    connection: Mutex<Connection>,
+    name: String,
+    version: Mutex<Option<i64>>,
+}
+
+impl Sqlite {
+    /// Opens the SQLite database at the given path, creating it if needed, and uses the config with the given name.
+    pub fn open(path: &Path, name: &str) -> Result<Self, Error> {
+        Self::new(Connection::open(path)?, name)
+    }
+
+    /// Uses the config with the given name in an already open database, for example the one of the application.
+    pub fn new(connection: Connection, name: &str) -> Result<Self, Error> {
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS opzioni_configs (name TEXT PRIMARY KEY, data TEXT NOT NULL, version INTEGER NOT NULL, updated_at INTEGER NOT NULL)",
+            [],
+        )?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+            name: name.to_string(),
+            version: Mutex::new(None),
+        })
+    }
+
+    /// Returns the version of the config which was last read or saved through this backend. It starts at 1 and grows by one with every save.
+    /// Returns `None` if the config was not read or saved yet or the database holds no config with the name.
+    pub fn version(&self) -> Option<i64> {
+        *self.version.lock().unwrap()
+    }
+}
+
+impl Backend for Sqlite {
+    fn read(&self) -> Result<Option<String>, Error> {
+        let row: Option<(String, i64)> = self.connection.lock().unwrap()
+            .query_row("SELECT data, version FROM opzioni_configs WHERE name = ?1", params![self.name], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()?;
+        *self.version.lock().unwrap() = row.as_ref().map(|(_, version)| *version);
+        Ok(row.map(|(data, _)| data))
+    }
+
+    fn write(&self, data: &str) -> Result<(), Error> {
+        let updated_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs() as i64).unwrap_or_default();
+        let mut version = self.version.lock().unwrap();
+        let next = version.unwrap_or(0) + 1;
+        let connection = self.connection.lock().unwrap();
+        let changed = match *version {
+            Some(current) => connection.execute(
+                "UPDATE opzioni_configs SET data = ?1, version = ?2, updated_at = ?3 WHERE name = ?4 AND version = ?5",
+                params![data, next, updated_at, self.name, current],
+            )?,
+            None => connection.execute(
+                "INSERT INTO opzioni_configs (name, data, version, updated_at) VALUES (?1, ?2, ?3, ?4) ON CONFLICT (name) DO NOTHING",
+                params![self.name, data, next, updated_at],
+            )?,
+        };
+        if changed == 0 {
+            return Err(Error::Conflict { path: None, format: None });
+        }
+        *version = Some(next);
+        Ok(())
+    }
+}
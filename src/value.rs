@@ -0,0 +1,682 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde_json::{Map, Value};
+
+use crate::config::fingerprint;
+use crate::{manager, Error};
+
+/// The key of include directives, see [`crate::ConfigBuilder::allow_includes`].
+const INCLUDE: &str = "$include";
+
+/// Returns the dotted paths of all keys in `value` which do not exist in `reference`.
+/// Maps which are empty in `reference` accept any key, because they usually belong to a map type like `HashMap` rather than to a struct.
+pub(crate) fn unknown_keys(value: &Value, reference: &Value) -> Vec<String> {
+    let mut unknown = Vec::new();
+    if let (Value::Object(value), Value::Object(reference)) = (value, reference) {
+        collect_unknown_keys(value, reference, "", &mut unknown);
+    }
+    unknown
+}
+
+fn collect_unknown_keys(value: &Map<String, Value>, reference: &Map<String, Value>, prefix: &str, unknown: &mut Vec<String>) {
+    if reference.is_empty() {
+        return;
+    }
+    for (key, child) in value {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match (child, reference.get(key)) {
+            (_, None) => unknown.push(path),
+            (Value::Object(child), Some(Value::Object(reference))) => collect_unknown_keys(child, reference, &path, unknown),
+            _ => {}
+        }
+    }
+}
+
+/// Returns the value at the given dotted path.
+pub(crate) fn get_path<'a>(root: &'a Value, path: &str) -> Result<&'a Value, Error> {
+    let mut current = root;
+    for key in path.split('.') {
+        if key.is_empty() {
+            return Err(Error::InvalidPath(format!("{} contains an empty key", path)));
+        }
+        current = match current {
+            Value::Object(map) => map.get(key).ok_or_else(|| Error::InvalidPath(format!("{} has no key {}", path, key)))?,
+            _ => return Err(Error::InvalidPath(format!("{} is not a map at {}", path, key))),
+        };
+    }
+    Ok(current)
+}
+
+/// Replaces the value at the given dotted path with a string, if there is one. Used to mask secrets in logs.
+#[cfg(feature = "tracing")]
+pub(crate) fn mask_path(root: &mut Value, path: &str, mask: &str) {
+    let mut current = root;
+    for key in path.split('.') {
+        current = match current {
+            Value::Object(map) => match map.get_mut(key) {
+                Some(value) => value,
+                None => return,
+            },
+            _ => return,
+        };
+    }
+    *current = Value::String(mask.to_string());
+}
+
+/// Sets the value at the given dotted path, creating missing or null intermediate maps on the way.
+pub(crate) fn set_path(root: &mut Value, path: &str, new: Value) -> Result<(), Error> {
+    let mut current = root;
+    let mut keys = path.split('.').peekable();
+    while let Some(key) = keys.next() {
+        if key.is_empty() {
+            return Err(Error::InvalidPath(format!("{} contains an empty key", path)));
+        }
+        if current.is_null() {
+            *current = Value::Object(Map::new());
+        }
+        let map = match current {
+            Value::Object(map) => map,
+            _ => return Err(Error::InvalidPath(format!("{} is not a map at {}", path, key))),
+        };
+        if keys.peek().is_none() {
+            map.insert(key.to_string(), new);
+            return Ok(());
+        }
+        current = map.entry(key.to_string()).or_insert(Value::Null);
+    }
+    Err(Error::InvalidPath(format!("{} is empty", path)))
+}
+
+/// Removes the key at the given dotted path, if there is one.
+fn remove_path(root: &mut Value, path: &str) {
+    let (parents, key) = path.rsplit_once('.').unwrap_or(("", path));
+    let mut current = root;
+    for parent in parents.split('.').filter(|parent| !parent.is_empty()) {
+        current = match current {
+            Value::Object(map) => match map.get_mut(parent) {
+                Some(value) => value,
+                None => return,
+            },
+            _ => return,
+        };
+    }
+    if let Value::Object(map) = current {
+        map.remove(key);
+    }
+}
+
+/// The values which replaced others while loading, by the dotted paths of their fields, with the values they replaced and their own fingerprints.
+/// Saving puts the replaced values back for the fields which still hold their replacements, like Vault restores its references, so values which do not belong in the file are not written to it.
+/// Keys which were added or removed have no value or fingerprint on one side, and are removed or put back again if they are still added or removed.
+#[derive(Default)]
+pub(crate) struct Replaced(Mutex<HashMap<String, Replacement>>);
+
+/// The replaced value and the fingerprint of its replacement, where `None` stands for a key which was added or removed.
+type Replacement = (Option<Value>, Option<u64>);
+
+impl Replaced {
+    /// Remembers every value which differs between `before` and `after`, in place of the ones remembered before.
+    /// The fields of added maps are remembered one by one as well, so a map which was changed in part keeps only the changed fields.
+    pub(crate) fn record(&self, before: &Value, after: &Value) -> Result<(), Error> {
+        let mut replaced = HashMap::new();
+        for change in crate::diff(before, after)? {
+            if let (None, Some(new @ Value::Object(_))) = (&change.old, &change.new) {
+                let mut fields = Vec::new();
+                collect_fields(new, "", &mut fields);
+                for field in fields {
+                    let hash = get_path(new, &field).ok().map(|value| fingerprint(value.to_string()));
+                    replaced.insert(format!("{}.{}", change.path, field), (None, hash));
+                }
+            }
+            replaced.insert(change.path, (change.old, change.new.map(|new| fingerprint(new.to_string()))));
+        }
+        *self.0.lock().unwrap() = replaced;
+        Ok(())
+    }
+
+    /// Puts the replaced values back into `value` for the fields which were not changed since. Returns whether any value was put back.
+    /// Maps are handled before their fields, so an unchanged map is put back as a whole.
+    pub(crate) fn restore(&self, value: &mut Value) -> Result<bool, Error> {
+        let replaced = self.0.lock().unwrap();
+        let mut paths = replaced.keys().collect::<Vec<_>>();
+        paths.sort();
+        let mut restored = false;
+        for path in paths {
+            let (replaced, hash) = &replaced[path];
+            if get_path(value, path).ok().map(|current| fingerprint(current.to_string())) != *hash {
+                continue;
+            }
+            match replaced {
+                Some(replaced) => set_path(value, path, replaced.clone())?,
+                None => remove_path(value, path),
+            }
+            restored = true;
+        }
+        Ok(restored)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+}
+
+impl Clone for Replaced {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.0.lock().unwrap().clone()))
+    }
+}
+
+/// A value given as a string for the field at a dotted path, like a command line argument or an environment variable.
+pub(crate) struct Override {
+    pub(crate) path: String,
+    /// The values of lists are given one by one.
+    pub(crate) values: Vec<String>,
+    /// Names where the value comes from in error messages, like `the environment variable MYAPP_PORT`.
+    pub(crate) origin: String,
+}
+
+/// Sets fields of the config to values given as strings, parsed into the type of the current value of each field.
+/// `select` picks the overrides from the serialized config, `source` names all of them in error messages, like `the command line`.
+pub(crate) fn override_fields<T>(config: &mut T, source: &str, select: impl FnOnce(&Value) -> Result<Vec<Override>, Error>) -> Result<(), Error>
+    where T: serde::Serialize + serde::de::DeserializeOwned {
+    let mut root = serde_json::to_value(&*config).map_err(Error::serialize)?;
+    let overrides = select(&root)?;
+    if overrides.is_empty() {
+        return Ok(());
+    }
+    for Override { path, values, origin } in overrides {
+        let Ok(current) = get_path(&root, &path) else {
+            continue;
+        };
+        let unset = current.is_null();
+        let new = match current {
+            Value::Array(elements) => values.iter().map(|value| parse_string(value, elements.first())).collect::<Option<_>>().map(Value::Array),
+            current => match values.as_slice() {
+                [value] => parse_string(value, Some(current)),
+                _ => None,
+            },
+        };
+        let new = new.ok_or_else(|| Error::load(format!("{} is not a valid value for {}", origin, path)))?;
+        let fallback = match (&new, values.as_slice()) {
+            (Value::Number(_) | Value::Bool(_), [value]) if unset => Some(Value::String(value.clone())),
+            _ => None,
+        };
+        set_path(&mut root, &path, new)?;
+        // Without a current value the type of the field is unknown, so a value which looks like a number is kept as a string if the field does not take a number.
+        if let Some(fallback) = fallback {
+            if serde_json::from_value::<T>(root.clone()).is_err() {
+                set_path(&mut root, &path, fallback)?;
+            }
+        }
+    }
+    *config = serde_json::from_value(root).map_err(|err| Error::load(format!("the values from {} do not fit the config: {}", source, err)))?;
+    Ok(())
+}
+
+/// Parses a value given as a string into the type of the current value. Fields without a value, like `None`, take numbers and booleans as such and everything else as a string.
+fn parse_string(value: &str, current: Option<&Value>) -> Option<Value> {
+    match current {
+        Some(Value::String(_)) => Some(Value::String(value.to_string())),
+        Some(Value::Bool(_)) => value.parse().map(Value::Bool).ok(),
+        Some(Value::Number(_)) => match serde_json::from_str(value) {
+            Ok(Value::Number(number)) => Some(Value::Number(number)),
+            _ => None,
+        },
+        Some(Value::Array(_) | Value::Object(_)) => serde_json::from_str(value).ok(),
+        Some(Value::Null) | None => match serde_json::from_str(value) {
+            Ok(scalar @ (Value::Number(_) | Value::Bool(_))) => Some(scalar),
+            _ => Some(Value::String(value.to_string())),
+        },
+    }
+}
+
+/// Returns the overrides from the environment variables named after the fields of the serialized config, see [`crate::ConfigBuilder::env_options`].
+/// The names are built from the keys as they are serialized, so renamed fields are found by their new names.
+pub(crate) fn env_overrides(root: &Value, options: &EnvOptions) -> Result<Vec<Override>, Error> {
+    let mut paths = Vec::new();
+    collect_fields(root, "", &mut paths);
+    let mut overrides = Vec::new();
+    for path in paths {
+        let var = options.var(&path, "");
+        let (value, origin) = match (std::env::var(&var).ok(), options.file_var(&path)) {
+            (Some(_), Some((file_var, Some(_)))) => {
+                return Err(Error::load(format!("both the environment variables {} and {} are set", var, file_var)));
+            }
+            (Some(value), _) => (value, format!("the environment variable {}", var)),
+            (None, Some((file_var, Some(file)))) => {
+                let value = std::fs::read_to_string(&file).map_err(|err| Error::from(err).at(Path::new(&file), None))?;
+                // Secrets files usually end with a newline, which is not part of the value.
+                (value.trim_end_matches(['\n', '\r']).to_string(), format!("the file {} of the environment variable {}", file, file_var))
+            }
+            _ => continue,
+        };
+        let values = match get_path(root, &path) {
+            Ok(Value::Array(_)) => value.split(',').map(|value| value.trim().to_string()).filter(|value| !value.is_empty()).collect(),
+            _ => vec![value],
+        };
+        overrides.push(Override { path, values, origin });
+    }
+    Ok(overrides)
+}
+
+/// Collects the dotted paths of all values which are not maps. Lists are collected as a whole.
+fn collect_fields(value: &Value, prefix: &str, paths: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                collect_fields(child, &path, paths);
+            }
+        }
+        _ if !prefix.is_empty() => paths.push(prefix.to_string()),
+        _ => {}
+    }
+}
+
+/// How the environment variables which override fields are named, set with [`crate::ConfigBuilder::env_options`].
+/// A name joins the prefix and the serialized keys of the path of the field with the separator, like `MYAPP_SERVER_PORT` for `server.port`.
+/// Every other character of a key which is not a letter or a digit becomes an underscore.
+///
+/// # Example
+/// ```
+/// use opzioni::{blocking::Config, EnvCase, EnvOptions, Format};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct Server {
+///   port: u16,
+/// }
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct Database {
+///   url: String,
+/// }
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   server: Server,
+///   database: Database,
+/// }
+///
+/// std::env::set_var("myapp__server__port", "8080");
+/// std::env::set_var("db__url", "postgres://localhost");
+/// let options = EnvOptions::new().prefix("myapp").separator("__").case(EnvCase::Lower).section_prefix("database", "db");
+/// let data = r#"{ "server": { "port": 80 }, "database": { "url": "" } }"#;
+/// let config = Config::<MyConfig>::configure().env_options(options).load_from_str(data, Format::Json).unwrap();
+/// assert_eq!(config.read().server.port, 8080);
+/// assert_eq!(config.read().database.url, "postgres://localhost");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvOptions {
+    prefix: String,
+    separator: String,
+    case: EnvCase,
+    sections: Vec<(String, String)>,
+    file_suffix: String,
+}
+
+impl Default for EnvOptions {
+    fn default() -> Self {
+        Self { prefix: String::new(), separator: "_".to_string(), case: EnvCase::Upper, sections: Vec::new(), file_suffix: String::new() }
+    }
+}
+
+impl EnvOptions {
+    /// Creates the default options, which name variables in uppercase without a prefix and with `_` between the keys, like `SERVER_PORT`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the prefix which comes before the keys, like `MYAPP`. It is joined to the keys with the separator and changed to the case like them. Defaults to no prefix.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Sets what is put between the prefix and the keys of nested fields, like `__` for `MYAPP__SERVER__PORT`, which keeps the keys apart from the underscores within them. Defaults to `_`.
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    /// Sets the case of the names. Defaults to [`EnvCase::Upper`].
+    pub fn case(mut self, case: EnvCase) -> Self {
+        self.case = case;
+        self
+    }
+
+    /// Names the variables of the fields in the section at the dotted path with their own prefix instead of the prefix and the keys of the section, like `DB_URL` instead of `MYAPP_DATABASE_URL` for `database.url`.
+    /// An empty prefix leaves only the keys within the section. If sections are nested, the innermost one applies.
+    pub fn section_prefix(mut self, section: &str, prefix: &str) -> Self {
+        self.sections.push((section.to_string(), prefix.to_string()));
+        self
+    }
+
+    /// Reads the value of a field from the file named by the variable with the given suffix, if the variable of the field is not set.
+    /// With the suffix `_FILE`, `MYAPP_DB_PASSWORD_FILE=/run/secrets/db` sets `db.password` to the content of `/run/secrets/db`, like Docker and Kubernetes mount secrets.
+    /// A newline at the end of the file is removed. If both variables are set, loading fails, because it is unclear which one is meant. The suffix is changed to the case of the names. Defaults to no suffix, which reads no files.
+    /// Like all overrides, the secrets are not written to the config file when it is saved, unless they were changed in memory.
+    ///
+    /// # Example
+    /// ```
+    /// use opzioni::{blocking::Config, EnvOptions};
+    /// use serde::{Serialize, Deserialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Default, Clone)]
+    /// struct MyConfig {
+    ///   name: String,
+    ///   password: String,
+    /// }
+    ///
+    /// let secret = std::env::temp_dir().join("opzioni-password");
+    /// std::fs::write(&secret, "hunter2\n").unwrap();
+    /// std::env::set_var("MYAPP_PASSWORD_FILE", &secret);
+    /// let path = std::env::temp_dir().join("opzioni-password.json");
+    /// std::fs::write(&path, r#"{ "name": "John", "password": "" }"#).unwrap();
+    /// let options = EnvOptions::new().prefix("MYAPP").file_suffix("_FILE");
+    /// let config = Config::<MyConfig>::configure().env_options(options).load(&path).unwrap();
+    /// assert_eq!(config.read().password, "hunter2");
+    ///
+    /// config.write().name = "Jane".to_string();
+    /// config.save().unwrap();
+    /// let saved = std::fs::read_to_string(&path).unwrap();
+    /// assert!(saved.contains("Jane"));
+    /// assert!(!saved.contains("hunter2"));
+    /// ```
+    pub fn file_suffix(mut self, suffix: &str) -> Self {
+        self.file_suffix = suffix.to_string();
+        self
+    }
+
+    /// Returns the name of the environment variable of the field at the dotted path, with the suffix appended to it.
+    fn var(&self, path: &str, suffix: &str) -> String {
+        let section = self.sections.iter()
+            .filter_map(|(section, prefix)| Some((path.strip_prefix(section.as_str())?.strip_prefix('.')?, section.len(), prefix)))
+            .max_by_key(|(_, length, _)| *length);
+        let (prefix, rest) = match section {
+            Some((rest, _, prefix)) => (prefix.as_str(), rest),
+            None => (self.prefix.as_str(), path),
+        };
+        let keys = rest.split('.').map(|key| key.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect::<String>());
+        let name = std::iter::once(prefix.to_string()).filter(|prefix| !prefix.is_empty()).chain(keys).collect::<Vec<_>>().join(&self.separator) + suffix;
+        match self.case {
+            EnvCase::Upper => name.to_uppercase(),
+            EnvCase::Lower => name.to_lowercase(),
+            EnvCase::Preserve => name,
+        }
+    }
+
+    /// Returns the name of the variable holding the path of the file of the field at the dotted path, and the path if it is set. `None` without a file suffix.
+    fn file_var(&self, path: &str) -> Option<(String, Option<String>)> {
+        if self.file_suffix.is_empty() {
+            return None;
+        }
+        let var = self.var(path, &self.file_suffix);
+        let file = std::env::var(&var).ok().filter(|file| !file.is_empty());
+        Some((var, file))
+    }
+}
+
+/// The case of the names of environment variables, see [`EnvOptions::case`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EnvCase {
+    /// All letters are uppercase, like `MYAPP_SERVER_PORT`, the convention of Docker and most shells.
+    #[default]
+    Upper,
+    /// All letters are lowercase, like `myapp_server_port`.
+    Lower,
+    /// The prefix and the keys keep their case, like `MyApp_server_port` for the prefix `MyApp`.
+    Preserve,
+}
+
+/// How two config files, or a config file and the default config, are merged, set with [`crate::ConfigBuilder::merge_strategy`].
+/// One value is merged over a base value, like the including file over the included ones, a profile over the `default` section or the config file over the default config.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum MergeStrategy {
+    /// Maps are merged key by key and arrays replace the arrays they are merged over.
+    #[default]
+    Merge,
+    /// Values replace the values they are merged over, including maps, so only the top level keys are merged.
+    Replace,
+    /// Maps are merged key by key and arrays are appended to the arrays they are merged over. Elements of the base which the array already contains are not added again.
+    Append,
+    /// Maps are merged key by key and arrays of maps are merged by the value of the given key.
+    /// Maps with the same value are merged, the other elements of the base are added before the array unless it already contains them.
+    MergeByKey(String),
+}
+
+/// Merges `value` over `base` with the given strategy, so everything set in `value` is kept and completed with `base`.
+/// Returns whether `value` changed.
+pub(crate) fn merge(value: &mut Value, base: Value, strategy: &MergeStrategy) -> bool {
+    match (value, base) {
+        (Value::Object(value), Value::Object(base)) => {
+            let mut merged = false;
+            for (key, base) in base {
+                match value.get_mut(&key) {
+                    Some(_) if *strategy == MergeStrategy::Replace => {}
+                    Some(child) => merged |= merge(child, base, strategy),
+                    None => {
+                        value.insert(key, base);
+                        merged = true;
+                    }
+                }
+            }
+            merged
+        }
+        (Value::Array(values), Value::Array(base)) => match strategy {
+            MergeStrategy::Append => prepend(values, base),
+            MergeStrategy::MergeByKey(key) => {
+                let mut merged = false;
+                let mut missing = Vec::new();
+                for element in base {
+                    let position = element.get(key).and_then(|id| values.iter().position(|value| value.get(key) == Some(id)));
+                    match position {
+                        Some(position) => merged |= merge(&mut values[position], element, strategy),
+                        None => missing.push(element),
+                    }
+                }
+                prepend(values, missing) || merged
+            }
+            MergeStrategy::Merge | MergeStrategy::Replace => false,
+        },
+        _ => false,
+    }
+}
+
+/// Adds the elements of `base` which `values` does not contain yet before them. Returns whether one was added.
+fn prepend(values: &mut Vec<Value>, base: Vec<Value>) -> bool {
+    let missing = base.into_iter().filter(|element| !values.contains(element)).collect::<Vec<_>>();
+    let added = !missing.is_empty();
+    values.splice(0..0, missing);
+    added
+}
+
+/// What [`crate::ConfigBuilder::expand_env`] does with placeholders of environment variables which are not set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnsetVariable {
+    /// Loading fails with [`Error::ConfigLoadError`] naming the variable.
+    Fail,
+    /// The placeholder is replaced with an empty string.
+    Empty,
+    /// The placeholder is kept as it is.
+    Keep,
+}
+
+/// Replaces `${NAME}` placeholders in all strings of `value` with the values of the environment variables. `$${` is replaced with a literal `${`.
+pub(crate) fn expand_env(value: &mut Value, unset: UnsetVariable) -> Result<(), Error> {
+    match value {
+        Value::String(string) if string.contains("${") => {
+            *string = expand(string, unset)?;
+        }
+        Value::Array(values) => {
+            for value in values {
+                expand_env(value, unset)?;
+            }
+        }
+        Value::Object(map) => {
+            for value in map.values_mut() {
+                expand_env(value, unset)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn expand(string: &str, unset: UnsetVariable) -> Result<String, Error> {
+    let mut expanded = String::with_capacity(string.len());
+    let mut rest = string;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            expanded.push_str(&rest[..start - 1]);
+            expanded.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        expanded.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(Error::load(format!("unterminated placeholder in {}", string)));
+        };
+        let name = &rest[start + 2..start + end];
+        match (std::env::var(name), unset) {
+            (Ok(value), _) => expanded.push_str(&value),
+            (Err(_), UnsetVariable::Fail) => {
+                return Err(Error::load(format!("the environment variable {} is not set", name)));
+            }
+            (Err(_), UnsetVariable::Empty) => {}
+            (Err(_), UnsetVariable::Keep) => expanded.push_str(&rest[start..start + end + 1]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Replaces the include directives in `value`, which was loaded from `path`, with the content of the included files.
+pub(crate) fn resolve_includes(value: &mut Value, path: &Path, strategy: &MergeStrategy) -> Result<(), Error> {
+    let canonical = path.canonicalize()?;
+    include(value, &canonical, &mut vec![canonical.clone()], strategy)
+}
+
+fn include(value: &mut Value, path: &Path, stack: &mut Vec<PathBuf>, strategy: &MergeStrategy) -> Result<(), Error> {
+    match value {
+        Value::Object(map) => {
+            for child in map.values_mut() {
+                include(child, path, stack, strategy)?;
+            }
+            let targets = match map.remove(INCLUDE) {
+                None => return Ok(()),
+                Some(Value::String(target)) => vec![target],
+                Some(Value::Array(targets)) => targets
+                    .into_iter()
+                    .map(|target| match target {
+                        Value::String(target) => Ok(target),
+                        _ => Err(Error::load(format!("{} must contain paths", INCLUDE))),
+                    })
+                    .collect::<Result<_, _>>()?,
+                Some(_) => return Err(Error::load(format!("{} must contain a path or a list of paths", INCLUDE))),
+            };
+            // Later includes override earlier ones and the including map overrides all of them.
+            for target in targets.iter().rev() {
+                let target = path.parent().unwrap_or(Path::new("")).join(target).canonicalize()?;
+                if stack.contains(&target) {
+                    let cycle = stack.iter().chain([&target]).map(|path| path.display().to_string()).collect::<Vec<_>>();
+                    return Err(Error::load(format!("include cycle: {}", cycle.join(" -> "))));
+                }
+                let mut included = manager::for_file::<Value>(&target)?.load_value()?;
+                stack.push(target.clone());
+                include(&mut included, &target, stack, strategy)?;
+                stack.pop();
+                merge(value, included, strategy);
+            }
+            Ok(())
+        }
+        Value::Array(values) => {
+            for value in values {
+                include(value, path, stack, strategy)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Selects the section of `profile` from `value`, merged over the `default` section with the given strategy.
+/// See [`crate::ConfigBuilder::profile`].
+pub(crate) fn select_profile(value: Value, profile: &str, strategy: &MergeStrategy) -> Result<Value, Error> {
+    let mut sections = match value {
+        Value::Object(sections) => sections,
+        _ => return Err(Error::load("a config with profiles must contain sections")),
+    };
+    let default = sections.remove(PROFILE_DEFAULT);
+    let mut selected = match (sections.remove(profile), &default) {
+        (Some(selected), _) => selected,
+        (None, Some(_)) => Value::Object(Map::new()),
+        (None, None) => return Err(Error::load(format!("the profile {} is not defined", profile))),
+    };
+    if let Some(default) = default {
+        merge(&mut selected, default, strategy);
+    }
+    Ok(selected)
+}
+
+/// Puts `config` into the section of `profile` of the `existing` document and returns the document.
+/// Only the values which differ from the `default` section are written, so the other sections are kept as they are.
+pub(crate) fn store_profile(existing: Option<Value>, profile: &str, mut config: Value) -> Value {
+    let mut sections = match existing {
+        Some(Value::Object(sections)) => sections,
+        _ => Map::new(),
+    };
+    if profile != PROFILE_DEFAULT {
+        if let Some(default) = sections.get(PROFILE_DEFAULT) {
+            remove_equal(&mut config, default);
+        }
+    }
+    sections.insert(profile.to_string(), config);
+    Value::Object(sections)
+}
+
+/// Sorts the keys of every map in the value, whatever order its maps keep their keys in.
+pub(crate) fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(entries.into_iter().map(|(key, value)| (key, sort_keys(value))).collect())
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(sort_keys).collect()),
+        value => value,
+    }
+}
+
+/// Removes the keys whose value is null from every map in the value.
+#[cfg(feature = "toml")]
+pub(crate) fn remove_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(remove_nulls);
+        }
+        Value::Array(values) => values.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+/// The section of a config with profiles which all profiles are merged over.
+const PROFILE_DEFAULT: &str = "default";
+
+/// Removes the keys of `value` whose values equal the ones in `base`, keeping maps which still differ.
+fn remove_equal(value: &mut Value, base: &Value) {
+    if let (Value::Object(value), Value::Object(base)) = (value, base) {
+        value.retain(|key, child| match base.get(key) {
+            Some(base) if child == base => false,
+            Some(base) => {
+                remove_equal(child, base);
+                !child.as_object().is_some_and(Map::is_empty)
+            }
+            None => true,
+        });
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use crate::config::fingerprint;
+use crate::{value, Error};
+
+/// The start of the strings which refer to a secret in Vault, like `vault:secret/myapp/db#password`.
+const SCHEME: &str = "vault:";
+
+/// A HashiCorp Vault server which resolves the references to its secrets in config files, see [`crate::ConfigBuilder::with_vault`].
+/// A reference is a string value like `vault:secret/myapp/db#password`, which is replaced by the key `password` of the secret `myapp/db` in the KV secrets engine mounted at `secret`.
+/// Without `#key` the whole secret is used, as a map of its keys.
+///
+/// # Example
+/// ```no_run
+/// use opzioni::{Config, Vault};
+/// use serde::{Serialize, Deserialize};
+/// use std::path::Path;
+///
+/// #[derive(Serialize, Deserialize, Default, Clone)]
+/// struct MyConfig {
+///   // "vault:secret/myapp/db#password" in the file
+///   db_password: String,
+/// }
+///
+/// let vault = Vault::new("https://vault.example.com:8200", "hvs.token");
+/// let config = Config::<MyConfig>::configure().with_vault(vault).load(Path::new("config.json")).unwrap();
+/// ```
+pub struct Vault {
+    address: String,
+    token: String,
+    namespace: Option<String>,
+    kv_version: u8,
+    /// The references which were resolved by the dotted paths of their values, with the fingerprints of the values, so unchanged values are saved as references again.
+    resolved: Mutex<HashMap<String, (String, u64)>>,
+}
+
+impl Vault {
+    /// Creates a resolver for the Vault server at `address`, like `https://127.0.0.1:8200`, which authenticates with the given token.
+    pub fn new(address: &str, token: &str) -> Self {
+        Self {
+            address: address.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            namespace: None,
+            kv_version: 2,
+            resolved: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a resolver from the `VAULT_ADDR` and `VAULT_TOKEN` environment variables, like the Vault CLI. `VAULT_NAMESPACE` is used if it is set.
+    /// Fails with [`Error::ConfigLoadError`] if the address or the token is not set.
+    pub fn from_env() -> Result<Self, Error> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let address = var("VAULT_ADDR").ok_or_else(|| Error::load("the environment variable VAULT_ADDR is not set"))?;
+        let token = var("VAULT_TOKEN").ok_or_else(|| Error::load("the environment variable VAULT_TOKEN is not set"))?;
+        let vault = Self::new(&address, &token);
+        Ok(match var("VAULT_NAMESPACE") {
+            Some(namespace) => vault.namespace(&namespace),
+            None => vault,
+        })
+    }
+
+    /// Sends the given namespace with every request, for Vault Enterprise and HCP Vault.
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Sets the version of the KV secrets engine the references point to, 1 or 2. Defaults to 2, the version Vault mounts at `secret` in development mode.
+    /// With version 2 the first segment of the path of a reference is the mount of the engine, like `secret` in `secret/myapp/db`, and the latest version of the secret is read.
+    pub fn kv_version(mut self, version: u8) -> Self {
+        self.kv_version = version;
+        self
+    }
+
+    /// Replaces every reference in the string values with the secret it refers to. Every secret is read once, however many of its keys are used.
+    pub(crate) fn resolve(&self, value: &mut Value) -> Result<(), Error> {
+        let mut references = Vec::new();
+        collect_references(value, String::new(), &mut references);
+        let mut secrets = HashMap::new();
+        let mut resolved = HashMap::new();
+        for (path, reference) in references {
+            let (secret, key) = reference[SCHEME.len()..].split_once('#').map_or((&reference[SCHEME.len()..], None), |(secret, key)| (secret, Some(key)));
+            if !secrets.contains_key(secret) {
+                secrets.insert(secret.to_string(), self.read(secret).map_err(|err| match err {
+                    Error::ConfigLoadError { message: Some(message), .. } => Error::load(format!("{}: {}", reference, message)),
+                    err => err,
+                })?);
+            }
+            let data = &secrets[secret];
+            let new = match key {
+                Some(key) => data.get(key).cloned().ok_or_else(|| Error::load(format!("{}: the secret has no key `{}`", reference, key)))?,
+                None => data.clone(),
+            };
+            let hash = fingerprint(new.to_string());
+            value::set_path(value, &path, new)?;
+            resolved.insert(path, (reference, hash));
+        }
+        *self.resolved.lock().unwrap() = resolved;
+        Ok(())
+    }
+
+    /// Puts the references back in place of the secrets which were not changed, so saving the config does not write them to the file.
+    /// Secrets which were changed are written as they are, because they are not stored in Vault.
+    pub(crate) fn restore(&self, value: &mut Value) -> Result<(), Error> {
+        for (path, (reference, hash)) in self.resolved.lock().unwrap().iter() {
+            if matches!(value::get_path(value, path), Ok(current) if fingerprint(current.to_string()) == *hash) {
+                value::set_path(value, path, Value::String(reference.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the data of the secret at the given path.
+    fn read(&self, secret: &str) -> Result<Value, Error> {
+        let secret = secret.trim_matches('/');
+        let url = match (self.kv_version, secret.split_once('/')) {
+            (1, _) => format!("{}/v1/{}", self.address, secret),
+            (_, Some((mount, path))) => format!("{}/v1/{}/data/{}", self.address, mount, path),
+            (_, None) => return Err(Error::load("the path of the secret has no mount")),
+        };
+        let mut request = ureq::get(&url).set("X-Vault-Token", &self.token);
+        if let Some(namespace) = &self.namespace {
+            request = request.set("X-Vault-Namespace", namespace);
+        }
+        let response = match request.call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Err(Error::load("the secret does not exist")),
+            Err(ureq::Error::Status(403, _)) => return Err(Error::load("the token may not read the secret")),
+            Err(err) => return Err(err.into()),
+        };
+        let mut body: Value = response.into_json()?;
+        let data = match self.kv_version {
+            1 => body.get_mut("data"),
+            _ => body.get_mut("data").and_then(|data| data.get_mut("data")),
+        };
+        match data.map(Value::take) {
+            Some(data @ Value::Object(_)) => Ok(data),
+            _ => Err(Error::load("the response of Vault holds no secret")),
+        }
+    }
+}
+
+impl Clone for Vault {
+    fn clone(&self) -> Self {
+        Self {
+            address: self.address.clone(),
+            token: self.token.clone(),
+            namespace: self.namespace.clone(),
+            kv_version: self.kv_version,
+            resolved: Mutex::new(self.resolved.lock().unwrap().clone()),
+        }
+    }
+}
+
+impl std::fmt::Debug for Vault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Vault")
+            .field("address", &self.address)
+            .field("token", &format_args!("[redacted]"))
+            .field("namespace", &self.namespace)
+            .field("kv_version", &self.kv_version)
+            .finish()
+    }
+}
+
+/// Collects the dotted paths of the string values which are references, together with the references.
+fn collect_references(value: &Value, path: String, references: &mut Vec<(String, String)>) {
+    let join = |key: &str| if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+    match value {
+        Value::String(string) if string.starts_with(SCHEME) => references.push((path, string.clone())),
+        Value::Object(map) => {
+            for (key, child) in map {
+                collect_references(child, join(key), references);
+            }
+        }
+        _ => {}
+    }
+}